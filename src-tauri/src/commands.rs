@@ -1,14 +1,241 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, MutexGuard};
+use std::time::Duration;
+
 use portable_pty::{CommandBuilder, PtySize};
 
 use crate::editor::highlight;
-use crate::editor::text_buffer::{Cursor, Language, LineTextBuffer, Selection};
+use crate::editor::language;
+use crate::editor::state::{BufferId, EditorState, SearchId};
+use crate::editor::text_buffer::{Cursor, LineTextBuffer, Selection};
+use crate::editor::theme::{ResolvedTheme, Theme};
+use crate::editor::undo_tree::{HistoryNode, UndoNodeId};
+use crate::editor_io::encoding::{self, FileEncoding};
 use crate::editor_io::file_handling;
+use crate::editor_io::line_ending::{self, LineEnding, LineEndings};
+use crate::editor_io::recovery::{self, RecoverableBuffer, RestoredBuffer};
+use crate::editor_io::undo_history;
+use crate::editor_io::watcher::{FileEvent, WorkspaceWatcher};
+use crate::error::PapyrusError;
+use crate::search::file_index::{FileIndex, IndexOptions};
+use crate::search::find_in_files::{self, FileSearch, FindInFilesOptions};
+use crate::search::query::SearchOptions;
+use crate::search::quick_open::{self, QuickOpenMatch};
+use crate::search::replace_in_files::{
+    self, FileReplacementError, FileReplacementPreview, ReplaceInFilesSummary,
+};
 use crate::terminal::io::read_output;
 use crate::EDITOR_STATE;
 
+/// Locks the editor state, recovering it if a previous command panicked
+/// while holding the lock
+pub fn lock_editor_state() -> MutexGuard<'static, EditorState> {
+    EDITOR_STATE
+        .get()
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Returns the buffer content joined with the given line break, or with
+/// the line breaks of its file if none is given
+fn get_buffer_content(buffer: &LineTextBuffer, eol_sequence: Option<String>) -> String {
+    match eol_sequence {
+        Some(eol_sequence) => buffer.get_content(eol_sequence),
+        None => buffer.get_file_content(),
+    }
+}
+
+/// Writes the content to the file of the buffer. Files modified on disk
+/// since the buffer was loaded or saved, or whose malformed bytes were
+/// replaced when decoding them, are only overwritten when forced.
+fn write_buffer_file(
+    buffer: &mut LineTextBuffer,
+    content: String,
+    file_encoding: &FileEncoding,
+    force: bool,
+) -> Result<(), PapyrusError> {
+    let path = buffer.file_path.clone().ok_or(PapyrusError::NoFilePath)?;
+    if !force {
+        file_handling::check_unchanged(&path, buffer.file_modified_time)?;
+        if buffer.had_decoding_errors {
+            return Err(PapyrusError::MalformedContent(path));
+        }
+    }
+    file_handling::override_file_content(&path, content, file_encoding)?;
+    buffer.file_modified_time = file_handling::modified_time(&path);
+    buffer.had_decoding_errors = false;
+    Ok(())
+}
+
+/// Stores the undo history of the buffer in the app data dir. Failing to
+/// store it does not fail the calling command, the history is just lost.
+fn store_undo_history(editor_state: &EditorState, buffer: &LineTextBuffer) {
+    if let Some(data_dir) = &editor_state.data_dir {
+        let _ = undo_history::store(data_dir, buffer);
+    }
+}
+
+#[derive(Clone, serde::Serialize)]
+struct DirtyStatePayload {
+    buffer_id: BufferId,
+    is_dirty: bool,
+}
+
+/// Emits a `dirty_state_changed` event if the buffer became dirty or clean
+/// since the last event
+fn emit_dirty_state(window: &tauri::Window, buffer_id: BufferId, buffer: &mut LineTextBuffer) {
+    if let Some(is_dirty) = buffer.take_dirty_change() {
+        let _ = window.emit(
+            "dirty_state_changed",
+            DirtyStatePayload {
+                buffer_id,
+                is_dirty,
+            },
+        );
+    }
+}
+
+/// Returns the rows changed by an edit, emitting the dirty state of the
+/// buffer if the edit changed it
+fn take_line_changes(
+    window: &tauri::Window,
+    buffer_id: BufferId,
+    buffer: &mut LineTextBuffer,
+) -> highlight::LineChanges {
+    emit_dirty_state(window, buffer_id, buffer);
+    buffer.take_line_changes()
+}
+
+#[derive(Clone, serde::Serialize)]
+struct BufferPathPayload {
+    buffer_id: BufferId,
+    path: String,
+}
+
+/// Reloads the buffer from its file if the file no longer has the content
+/// the buffer was loaded or saved with. Dirty buffers are not reloaded, a
+/// `file_conflict` event is emitted instead.
+fn sync_buffer_with_file(window: &tauri::Window, buffer_id: BufferId, buffer: &mut LineTextBuffer) {
+    let path = match &buffer.file_path {
+        Some(path) => path.clone(),
+        None => return,
+    };
+    let modified_time = file_handling::modified_time(&path);
+    let (text, had_errors) =
+        match file_handling::read_file_content_with_encoding(&path, Some(buffer.encoding)) {
+            Ok((text, _, had_errors)) => (text, had_errors),
+            Err(_) => return,
+        };
+    let content_hash =
+        undo_history::fnv1a_hash(line_ending::normalize_line_endings(text.clone()).bytes());
+    // The file was only touched or saved by the editor itself
+    if content_hash == buffer.saved_content_hash {
+        buffer.file_modified_time = modified_time;
+        return;
+    }
+
+    if buffer.is_dirty() {
+        let _ = window.emit("file_conflict", BufferPathPayload { buffer_id, path });
+    } else {
+        reload_buffer_content(window, buffer_id, buffer, text);
+        buffer.file_modified_time = modified_time;
+        buffer.had_decoding_errors = had_errors;
+    }
+}
+
+/// Replaces the content of the buffer with the text read from its file and
+/// emits a `buffer_reloaded` event
+fn reload_buffer_content(
+    window: &tauri::Window,
+    buffer_id: BufferId,
+    buffer: &mut LineTextBuffer,
+    text: String,
+) {
+    buffer.reload(text);
+    // The frontend reloads the whole buffer instead
+    buffer.take_line_changes();
+    emit_dirty_state(window, buffer_id, buffer);
+    if let Some(path) = buffer.file_path.clone() {
+        let _ = window.emit("buffer_reloaded", BufferPathPayload { buffer_id, path });
+    }
+}
+
+/// Updates the open buffers after files changed on disk and emits the
+/// changes as a `file_system_changed` event
+fn sync_buffers_with_files(window: &tauri::Window, events: Vec<FileEvent>) {
+    let mut editor_state = lock_editor_state();
+    for event in &events {
+        let path = match event {
+            FileEvent::Created { path } | FileEvent::Modified { path } => path,
+            FileEvent::Renamed { from, to } => {
+                editor_state.recent_files.rename(from, to);
+                if let Some(buffer_id) = editor_state.find_buffer_by_path(from) {
+                    if let Ok(buffer) = editor_state.get_buffer_mut(buffer_id) {
+                        buffer.file_path = Some(to.clone());
+                        let payload = BufferPathPayload {
+                            buffer_id,
+                            path: to.clone(),
+                        };
+                        let _ = window.emit("buffer_renamed", payload);
+                    }
+                }
+                to
+            }
+            // Buffers of removed files keep their content until closed
+            FileEvent::Removed { .. } => continue,
+        };
+        if let Some(buffer_id) = editor_state.find_buffer_by_path(path) {
+            if let Ok(buffer) = editor_state.get_buffer_mut(buffer_id) {
+                sync_buffer_with_file(window, buffer_id, buffer);
+            }
+        }
+    }
+    editor_state.apply_file_events(&events);
+    drop(editor_state);
+    let _ = window.emit("file_system_changed", events);
+}
+
+/// Returns an error unless the cursor is inside the buffer
+fn check_cursor(buffer: &LineTextBuffer, cursor: &Cursor) -> Result<(), PapyrusError> {
+    if buffer.is_valid_cursor(cursor) {
+        Ok(())
+    } else {
+        Err(PapyrusError::InvalidCursor(cursor.clone()))
+    }
+}
+
+/// Returns an error unless the selection is inside the buffer
+fn check_selection(buffer: &LineTextBuffer, selection: &Selection) -> Result<(), PapyrusError> {
+    if buffer.is_valid_selection(selection) {
+        Ok(())
+    } else {
+        Err(PapyrusError::InvalidSelection(selection.clone()))
+    }
+}
+
+/// Returns an error unless the selection covers existing rows
+fn check_selected_rows(buffer: &LineTextBuffer, selection: &Selection) -> Result<(), PapyrusError> {
+    if selection.start.row <= selection.end.row && selection.end.row < buffer.get_lines_length() {
+        Ok(())
+    } else {
+        Err(PapyrusError::InvalidSelection(selection.clone()))
+    }
+}
+
+/// Returns an error unless the row exists in the buffer
+fn check_row(buffer: &LineTextBuffer, row: usize) -> Result<(), PapyrusError> {
+    if row < buffer.get_lines_length() {
+        Ok(())
+    } else {
+        Err(PapyrusError::InvalidRow(row))
+    }
+}
+
 #[tauri::command]
-pub fn init_pty(window: tauri::Window) -> Result<(), String> {
-    let mut editor_state = EDITOR_STATE.get().lock().unwrap();
+pub fn init_pty(window: tauri::Window) -> Result<(), PapyrusError> {
+    let mut editor_state = lock_editor_state();
     let pty_system = portable_pty::native_pty_system();
     let pty_pair = pty_system
         .openpty(PtySize {
@@ -17,24 +244,24 @@ pub fn init_pty(window: tauri::Window) -> Result<(), String> {
             pixel_width: 0,
             pixel_height: 0,
         })
-        .map_err(|err| err.to_string())?;
+        .map_err(|err| PapyrusError::Pty(err.to_string()))?;
     let mut cmd = CommandBuilder::new("zsh");
     cmd.env("TERM", "xterm-256color");
     cmd.env("COLORTERM", "truecolor");
     let _child = pty_pair
         .slave
         .spawn_command(cmd)
-        .map_err(|err| err.to_string())?;
+        .map_err(|err| PapyrusError::Pty(err.to_string()))?;
 
     let reader = pty_pair
         .master
         .try_clone_reader()
-        .map_err(|err| err.to_string())?;
+        .map_err(|err| PapyrusError::Pty(err.to_string()))?;
 
     let writer = pty_pair
         .master
         .take_writer()
-        .map_err(|err| err.to_string())?;
+        .map_err(|err| PapyrusError::Pty(err.to_string()))?;
 
     std::thread::spawn(|| read_output(window, reader));
 
@@ -44,17 +271,23 @@ pub fn init_pty(window: tauri::Window) -> Result<(), String> {
 }
 
 #[tauri::command]
-pub fn send_to_pty(input: String) -> Result<(), String> {
-    let mut editor_state = EDITOR_STATE.get().lock().unwrap();
-    let writer = editor_state.pty_writer.as_mut().unwrap();
-    write!(writer, "{}", input).map_err(|err| err.to_string())?;
+pub fn send_to_pty(input: String) -> Result<(), PapyrusError> {
+    let mut editor_state = lock_editor_state();
+    let writer = editor_state
+        .pty_writer
+        .as_mut()
+        .ok_or(PapyrusError::PtyNotInitialized)?;
+    write!(writer, "{}", input)?;
     Ok(())
 }
 
 #[tauri::command]
-pub fn resize_pty(rows: u16, cols: u16) -> Result<(), String> {
-    let mut editor_state = EDITOR_STATE.get().lock().unwrap();
-    let pty_pair = editor_state.pty_pair.as_mut().unwrap();
+pub fn resize_pty(rows: u16, cols: u16) -> Result<(), PapyrusError> {
+    let mut editor_state = lock_editor_state();
+    let pty_pair = editor_state
+        .pty_pair
+        .as_mut()
+        .ok_or(PapyrusError::PtyNotInitialized)?;
     pty_pair
         .master
         .resize(PtySize {
@@ -63,17 +296,310 @@ pub fn resize_pty(rows: u16, cols: u16) -> Result<(), String> {
             pixel_width: 0,
             pixel_height: 0,
         })
-        .map_err(|err| err.to_string())?;
+        .map_err(|err| PapyrusError::Pty(err.to_string()))?;
     Ok(())
 }
 
+/// Lists a folder, skipping ignored and excluded entries once the
+/// workspace is indexed
 #[tauri::command]
-pub fn get_folder_content(path: String) -> Result<Vec<file_handling::FolderEntry>, String> {
-    let entries = file_handling::get_folder_content(&path).map_err(|err| err.to_string())?;
+pub fn get_folder_content(path: String) -> Result<Vec<file_handling::FolderEntry>, PapyrusError> {
+    {
+        let editor_state = lock_editor_state();
+        if let Some(file_index) = &editor_state.file_index {
+            if file_index.root() == Path::new(&path) || file_index.contains(&path) {
+                return Ok(file_index.folder_content(&path));
+            }
+        }
+    }
+    let entries = file_handling::get_folder_content(&path)?;
 
     Ok(entries)
 }
 
+#[derive(Clone, serde::Serialize)]
+struct WorkspaceIndexedPayload {
+    root: String,
+    entry_count: usize,
+}
+
+/// Indexes the files of the workspace folder in the background, replacing
+/// the index of the previous workspace. A `workspace_indexed` event is
+/// emitted once done, unless another workspace was indexed meanwhile.
+#[tauri::command]
+pub fn index_workspace(
+    window: tauri::Window,
+    root: String,
+    options: IndexOptions,
+) -> Result<(), PapyrusError> {
+    let mut file_index = FileIndex::new(&root, options)?;
+    // The folders of the previous workspace are listed from disk until
+    // the new index is ready
+    let generation = lock_editor_state().start_indexing();
+    std::thread::spawn(move || {
+        file_index.build();
+        let mut editor_state = lock_editor_state();
+        if !editor_state.finish_indexing(generation, file_index) {
+            return;
+        }
+        let entry_count = workspace_index(&editor_state).map_or(0, FileIndex::len);
+        drop(editor_state);
+        let _ = window.emit(
+            "workspace_indexed",
+            WorkspaceIndexedPayload { root, entry_count },
+        );
+    });
+    Ok(())
+}
+
+/// Returns the index of the workspace, or an error if it is not indexed yet
+fn workspace_index(editor_state: &EditorState) -> Result<&FileIndex, PapyrusError> {
+    editor_state
+        .file_index
+        .as_ref()
+        .ok_or_else(|| PapyrusError::Search("The workspace is not indexed".into()))
+}
+
+/// Returns the indexed files matching any of the globs, or every indexed
+/// file if there are none
+#[tauri::command]
+pub fn query_workspace_index(
+    globs: Vec<String>,
+) -> Result<Vec<file_handling::FolderEntry>, PapyrusError> {
+    let editor_state = lock_editor_state();
+    let file_index = workspace_index(&editor_state)?;
+    Ok(file_index.query(&globs)?.into_iter().cloned().collect())
+}
+
+/// Returns the indexed files best matching the query, with the positions
+/// of the matched characters
+#[tauri::command]
+pub fn quick_open(query: String, limit: usize) -> Result<Vec<QuickOpenMatch>, PapyrusError> {
+    let editor_state = lock_editor_state();
+    let file_index = workspace_index(&editor_state)?;
+    Ok(quick_open::find_files(
+        file_index.root(),
+        file_index.files(),
+        &query,
+        &editor_state.recent_files,
+        limit,
+    ))
+}
+
+/// Watches the workspace folder for changes made outside the editor,
+/// replacing the watcher of the previous workspace
+#[tauri::command]
+pub fn watch_workspace(window: tauri::Window, path: String) -> Result<(), PapyrusError> {
+    let watcher = WorkspaceWatcher::new(&path, move |events| {
+        sync_buffers_with_files(&window, events);
+    })?;
+    lock_editor_state().watcher = Some(watcher);
+    Ok(())
+}
+
+/// Starts searching the files under the root in the background and returns
+/// the id of the search. Results are emitted as `search_result` events.
+#[tauri::command]
+pub fn find_in_files(
+    window: tauri::Window,
+    root: String,
+    options: FindInFilesOptions,
+) -> Result<SearchId, PapyrusError> {
+    let search = FileSearch::new(&root, &options)?;
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let mut editor_state = lock_editor_state();
+    let open_buffers = open_buffer_contents(&editor_state);
+    let search_id = editor_state.add_search(cancelled.clone());
+    drop(editor_state);
+
+    std::thread::spawn(move || {
+        find_in_files::emit_search_results(&window, search_id, &search, &open_buffers, &cancelled);
+        lock_editor_state().searches.remove(&search_id);
+    });
+
+    Ok(search_id)
+}
+
+/// Returns the content of the open files by path, with `\n` line endings
+fn open_buffer_contents(editor_state: &EditorState) -> HashMap<String, String> {
+    editor_state
+        .text_buffers
+        .values()
+        .filter_map(|buffer| Some((buffer.file_path.clone()?, buffer.get_content("\n".into()))))
+        .collect()
+}
+
+/// Stops a running search. Searches that already finished are ignored.
+#[tauri::command]
+pub fn cancel_search(search_id: SearchId) {
+    let editor_state = lock_editor_state();
+    if let Some(cancelled) = editor_state.searches.get(&search_id) {
+        cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Returns the replacements planned in the files under the root with a
+/// diff of each file, without changing anything
+#[tauri::command]
+pub fn preview_replace_in_files(
+    root: String,
+    options: FindInFilesOptions,
+    replacement: String,
+) -> Result<Vec<FileReplacementPreview>, PapyrusError> {
+    let search = FileSearch::new(&root, &options)?;
+    let open_buffers = open_buffer_contents(&lock_editor_state());
+
+    Ok(replace_in_files::preview_replacements(
+        &search,
+        &options.search,
+        &replacement,
+        &open_buffers,
+    ))
+}
+
+/// Replaces every match in the given files. Open files are changed through
+/// their buffer so the change can be undone, other files are rewritten on
+/// disk. Files that fail are reported without stopping the others.
+#[tauri::command]
+pub fn replace_in_files(
+    window: tauri::Window,
+    query: String,
+    options: SearchOptions,
+    replacement: String,
+    paths: Vec<String>,
+) -> Result<ReplaceInFilesSummary, PapyrusError> {
+    let regex = options.to_regex(&query)?;
+    let mut summary = ReplaceInFilesSummary::default();
+
+    for path in paths {
+        let mut editor_state = lock_editor_state();
+        let result = match editor_state.find_buffer_by_path(&path) {
+            Some(buffer_id) => {
+                let buffer = editor_state.get_buffer_mut(buffer_id)?;
+                let result = buffer.replace_all(&query, &options, &replacement);
+                // The frontend shows the whole buffer again instead of the
+                // changed rows
+                buffer.take_line_changes();
+                emit_dirty_state(&window, buffer_id, buffer);
+                if result.as_ref().map_or(false, |count| *count > 0) {
+                    let payload = BufferPathPayload {
+                        buffer_id,
+                        path: path.clone(),
+                    };
+                    let _ = window.emit("buffer_reloaded", payload);
+                }
+                result
+            }
+            None => {
+                drop(editor_state);
+                replace_in_files::replace_in_file(&path, &regex, &options, &replacement)
+                    .map_err(PapyrusError::from)
+            }
+        };
+
+        match result {
+            Ok(0) => {}
+            Ok(replacement_count) => {
+                summary.replacement_count += replacement_count;
+                summary.changed_files.push(path);
+            }
+            Err(err) => summary.errors.push(FileReplacementError {
+                path,
+                message: err.to_string(),
+            }),
+        }
+    }
+
+    Ok(summary)
+}
+
+#[tauri::command]
+pub fn set_cursor(buffer_id: BufferId, cursor: Cursor) -> Result<(), PapyrusError> {
+    let mut editor_state = lock_editor_state();
+    let buffer = editor_state.get_buffer_mut(buffer_id)?;
+    check_cursor(buffer, &cursor)?;
+    buffer.cursor = cursor;
+    Ok(())
+}
+
+/// Snapshots the buffers modified since their last snapshot and marks the
+/// session as running. The snapshots are written after releasing the
+/// editor state.
+pub fn snapshot_unsaved_buffers() {
+    let writes = {
+        let mut editor_state = lock_editor_state();
+        let editor_state = &mut *editor_state;
+        match &mut editor_state.recovery {
+            Some(recovery) => {
+                let _ = recovery.lock_session();
+                recovery.pending_writes(&editor_state.text_buffers)
+            }
+            None => return,
+        }
+    };
+    recovery::write_snapshots(writes);
+}
+
+/// Snapshots the unsaved buffers a last time and marks the session as
+/// ended, so the next session offers them right away
+pub fn end_recovery_session() {
+    snapshot_unsaved_buffers();
+    if let Some(recovery) = &lock_editor_state().recovery {
+        let _ = recovery.unlock_session();
+    }
+}
+
+#[tauri::command]
+pub fn get_recoverable_buffers() -> Vec<RecoverableBuffer> {
+    let editor_state = lock_editor_state();
+    editor_state
+        .recovery
+        .as_ref()
+        .map_or_else(Vec::new, |recovery| recovery.recoverable())
+}
+
+#[tauri::command]
+pub fn restore_buffers(ids: Vec<String>) -> Result<Vec<RestoredBuffer>, PapyrusError> {
+    let mut editor_state = lock_editor_state();
+    let mut restored = vec![];
+    for id in ids {
+        let recovery = editor_state
+            .recovery
+            .as_ref()
+            .ok_or_else(|| PapyrusError::Other("Recovery is not set up".into()))?;
+        let snapshot = recovery.read(&id)?;
+        // A file opened since the crash keeps its buffer and the snapshot
+        // can still be restored once it is closed
+        if let Some(path) = &snapshot.file_path {
+            if editor_state.find_buffer_by_path(path).is_some() {
+                continue;
+            }
+        }
+        recovery.discard(&id)?;
+
+        let buffer = snapshot.restore();
+        let file_path = buffer.file_path.clone();
+        let cursor = buffer.cursor.clone();
+        restored.push(RestoredBuffer {
+            buffer_id: editor_state.add_buffer(buffer),
+            file_path,
+            cursor,
+        });
+    }
+    Ok(restored)
+}
+
+#[tauri::command]
+pub fn discard_recoverable_buffers(ids: Vec<String>) -> Result<(), PapyrusError> {
+    let editor_state = lock_editor_state();
+    if let Some(recovery) = &editor_state.recovery {
+        for id in ids {
+            recovery.discard(&id)?;
+        }
+    }
+    Ok(())
+}
+
 #[tauri::command]
 pub fn get_file_info(path: String) -> file_handling::FileEntry {
     let entry = file_handling::FileEntry::new(path);
@@ -81,215 +607,677 @@ pub fn get_file_info(path: String) -> file_handling::FileEntry {
 }
 
 #[tauri::command]
-pub fn get_relative_path(from: String, to: String) -> Result<String, String> {
-    let path = file_handling::get_relative_path(&from, &to).map_err(|err| err.to_string())?;
+pub fn get_relative_path(from: String, to: String) -> Result<String, PapyrusError> {
+    let path = file_handling::get_relative_path(&from, &to)?;
     Ok(path)
 }
 
 #[tauri::command]
-pub fn get_parent(path: String) -> Result<String, String> {
-    let parent = file_handling::get_parent(&path).map_err(|err| err.to_string())?;
+pub fn get_parent(path: String) -> Result<String, PapyrusError> {
+    let parent = file_handling::get_parent(&path)?;
     Ok(parent)
 }
 
 #[tauri::command]
-pub fn join_paths(start: String, end: String) -> Result<String, String> {
-    let path = file_handling::join_paths(&start, &end).map_err(|err| err.to_string())?;
+pub fn join_paths(start: String, end: String) -> Result<String, PapyrusError> {
+    let path = file_handling::join_paths(&start, &end)?;
     Ok(path)
 }
 
+/// Updates the index of the workspace after a file operation, so the file
+/// tree shows the change before the watcher reports it
+fn update_file_index(editor_state: &mut EditorState, event: FileEvent) {
+    editor_state.apply_file_events(&[event]);
+}
+
 #[tauri::command]
-pub fn create_file(path: String) -> Result<(), String> {
-    file_handling::create_file(&path).map_err(|err| err.to_string())?;
+pub fn create_file(path: String) -> Result<(), PapyrusError> {
+    file_handling::create_file(&path)?;
+    update_file_index(&mut lock_editor_state(), FileEvent::Created { path });
     Ok(())
 }
 
 #[tauri::command]
-pub fn create_folder(path: String) -> Result<(), String> {
-    file_handling::create_folder(&path).map_err(|err| err.to_string())?;
+pub fn create_folder(path: String) -> Result<(), PapyrusError> {
+    file_handling::create_folder(&path)?;
+    update_file_index(&mut lock_editor_state(), FileEvent::Created { path });
     Ok(())
 }
 
+/// Renames or moves a file or folder and updates the paths of the buffers
+/// opened from it, emitting a `buffer_renamed` event for each
 #[tauri::command]
-pub fn create_buffer_from_file_path(path: String) -> Result<usize, String> {
-    let mut editor_state = EDITOR_STATE.get().lock().unwrap();
-    for (idx, buffer) in editor_state.text_buffers.iter().enumerate() {
-        if let Some(file_path) = &buffer.file_path {
-            if path.contains(file_path) {
-                return Ok(idx);
-            }
-        }
+pub fn rename_path(window: tauri::Window, from: String, to: String) -> Result<(), PapyrusError> {
+    // Held so no buffer is saved to the old path while it is renamed
+    let mut editor_state = lock_editor_state();
+    file_handling::rename_path(&from, &to)?;
+    for (buffer_id, path) in editor_state.rename_buffer_paths(&from, &to) {
+        let _ = window.emit("buffer_renamed", BufferPathPayload { buffer_id, path });
     }
+    editor_state.recent_files.rename(&from, &to);
+    update_file_index(&mut editor_state, FileEvent::Renamed { from, to });
+    Ok(())
+}
 
-    let buf = file_handling::read_file_content(&path).map_err(|err| err.to_string())?;
-    editor_state
-        .text_buffers
-        .push(LineTextBuffer::from_file(buf, path));
-    let buffer_idx = editor_state.text_buffers.len() - 1;
-    editor_state.text_buffers[buffer_idx].language = Language::Python;
+#[tauri::command]
+pub fn copy_path(from: String, to: String) -> Result<(), PapyrusError> {
+    file_handling::copy_path(&from, &to)?;
+    update_file_index(&mut lock_editor_state(), FileEvent::Created { path: to });
+    Ok(())
+}
+
+/// Moves a file or folder to the trash, or deletes it permanently. Buffers
+/// opened from it stay open.
+#[tauri::command]
+pub fn delete_path(path: String, permanently: Option<bool>) -> Result<(), PapyrusError> {
+    if permanently.unwrap_or(false) {
+        file_handling::remove_path(&path)?;
+    } else {
+        file_handling::trash_path(&path)?;
+    }
+    update_file_index(&mut lock_editor_state(), FileEvent::Removed { path });
+    Ok(())
+}
+
+#[tauri::command]
+pub fn create_buffer_from_file_path(path: String) -> Result<BufferId, PapyrusError> {
+    let mut editor_state = lock_editor_state();
+    if let Some(buffer_id) = editor_state.find_buffer_by_path(&path) {
+        editor_state.recent_files.open(&path);
+        return Ok(buffer_id);
+    }
+
+    let modified_time = file_handling::modified_time(&path);
+    let (buf, file_encoding, had_errors) =
+        file_handling::read_file_content_with_encoding(&path, None)?;
+    editor_state.recent_files.open(&path);
+    let language = language::detect_language(&path, &buf);
+    let mut buffer = LineTextBuffer::from_file(buf, path);
+    buffer.set_language(language);
+    buffer.encoding = file_encoding;
+    buffer.file_modified_time = modified_time;
+    buffer.had_decoding_errors = had_errors;
+    if let Some(data_dir) = &editor_state.data_dir {
+        undo_history::restore(data_dir, &mut buffer);
+    }
 
-    Ok(buffer_idx)
+    Ok(editor_state.add_buffer(buffer))
 }
 
 #[tauri::command]
-pub fn delete_buffer(buffer_idx: usize) -> Result<String, String> {
-    let mut editor_state = EDITOR_STATE.get().lock().unwrap();
-    editor_state.text_buffers.remove(buffer_idx);
+pub fn delete_buffer(buffer_id: BufferId) -> Result<String, PapyrusError> {
+    let mut editor_state = lock_editor_state();
+    let buffer = editor_state
+        .remove_buffer(buffer_id)
+        .ok_or(PapyrusError::BufferNotFound(buffer_id))?;
+    store_undo_history(&editor_state, &buffer);
     Ok("Success".into())
 }
 
 #[tauri::command]
-pub fn save_buffer(buffer_idx: usize, eol_sequence: String) -> Result<String, String> {
-    let editor_state = EDITOR_STATE.get().lock().unwrap();
-    let content = editor_state.text_buffers[buffer_idx].get_content(eol_sequence);
-    let path = editor_state.text_buffers[buffer_idx]
-        .file_path
-        .as_ref()
-        .unwrap();
-    file_handling::override_file_content(path, content).map_err(|err| err.to_string())?;
+pub fn get_language(buffer_id: BufferId) -> Result<String, PapyrusError> {
+    let editor_state = lock_editor_state();
+    let buffer = editor_state.get_buffer(buffer_id)?;
+    Ok(language::get_language_name(buffer.language).to_string())
+}
+
+#[tauri::command]
+pub fn get_theme() -> ResolvedTheme {
+    let editor_state = lock_editor_state();
+    editor_state.theme.resolve_all()
+}
+
+#[tauri::command]
+pub fn load_theme(path: String) -> Result<ResolvedTheme, PapyrusError> {
+    let theme = if path.is_empty() {
+        Theme::default()
+    } else {
+        Theme::load(&path).map_err(|err| PapyrusError::Theme(err.to_string()))?
+    };
+    let resolved_theme = theme.resolve_all();
+
+    let mut editor_state = lock_editor_state();
+    editor_state.theme = theme;
+    Ok(resolved_theme)
+}
+
+#[tauri::command]
+pub fn save_buffer(
+    window: tauri::Window,
+    buffer_id: BufferId,
+    eol_sequence: Option<String>,
+    force: Option<bool>,
+) -> Result<String, PapyrusError> {
+    let mut editor_state = lock_editor_state();
+    let buffer = editor_state.get_buffer_mut(buffer_id)?;
+    let content = get_buffer_content(buffer, eol_sequence);
+    let file_encoding = buffer.encoding;
+    write_buffer_file(buffer, content, &file_encoding, force.unwrap_or(false))?;
+    buffer.mark_saved();
+    emit_dirty_state(&window, buffer_id, buffer);
+    let buffer = editor_state.get_buffer(buffer_id)?;
+    store_undo_history(&editor_state, buffer);
 
     Ok("Success".into())
 }
 
 #[tauri::command]
 pub fn save_buffer_to_new_file(
-    buffer_idx: usize,
+    window: tauri::Window,
+    buffer_id: BufferId,
     path: String,
-    eol_sequence: String,
-) -> Result<String, String> {
-    let editor_state = EDITOR_STATE.get().lock().unwrap();
-    let content = editor_state.text_buffers[buffer_idx].get_content(eol_sequence);
-    file_handling::override_file_content(&path, content).map_err(|err| err.to_string())?;
+    eol_sequence: Option<String>,
+) -> Result<String, PapyrusError> {
+    let mut editor_state = lock_editor_state();
+    let buffer = editor_state.get_buffer_mut(buffer_id)?;
+    let content = get_buffer_content(buffer, eol_sequence);
+    file_handling::override_file_content(&path, content, &buffer.encoding)?;
+    // Untitled buffers are linked to the file they are first saved to
+    if buffer.file_path.is_none() {
+        buffer.file_modified_time = file_handling::modified_time(&path);
+        buffer.file_path = Some(path);
+        buffer.mark_saved();
+        emit_dirty_state(&window, buffer_id, buffer);
+    }
 
     Ok("Success".into())
 }
 
 #[tauri::command]
-pub fn get_highlighted_text(buffer_idx: usize) -> highlight::HighlightedText {
-    let mut editor_state = EDITOR_STATE.get().lock().unwrap();
-    editor_state.text_buffers[buffer_idx].highlight_complete_text()
+pub fn get_encoding(buffer_id: BufferId) -> Result<FileEncoding, PapyrusError> {
+    let editor_state = lock_editor_state();
+    let buffer = editor_state.get_buffer(buffer_id)?;
+    Ok(buffer.encoding)
 }
 
 #[tauri::command]
-pub fn get_row_length(buffer_idx: usize, row: usize) -> usize {
-    let editor_state = EDITOR_STATE.get().lock().unwrap();
-    let row_length = editor_state.text_buffers[buffer_idx].get_row_length(row);
-    row_length
+pub fn get_encodings() -> Vec<&'static str> {
+    encoding::COMMON_ENCODINGS.to_vec()
 }
 
 #[tauri::command]
-pub fn get_lines_length(buffer_idx: usize) -> usize {
-    let editor_state = EDITOR_STATE.get().lock().unwrap();
-    let row_length = editor_state.text_buffers[buffer_idx].get_lines_length();
-    row_length
+pub fn reopen_with_encoding(
+    window: tauri::Window,
+    buffer_id: BufferId,
+    encoding: String,
+    force: Option<bool>,
+) -> Result<FileEncoding, PapyrusError> {
+    let mut editor_state = lock_editor_state();
+    let buffer = editor_state.get_buffer_mut(buffer_id)?;
+    let path = buffer.file_path.clone().ok_or(PapyrusError::NoFilePath)?;
+    // Reopening discards the unsaved changes and the undo history
+    if buffer.is_dirty() && !force.unwrap_or(false) {
+        return Err(PapyrusError::UnsavedChanges(path));
+    }
+    let file_encoding = FileEncoding::from_label(&encoding, false)?;
+
+    let modified_time = file_handling::modified_time(&path);
+    let (buf, file_encoding, had_errors) =
+        file_handling::read_file_content_with_encoding(&path, Some(file_encoding))?;
+    let mut reopened_buffer = LineTextBuffer::from_file(buf, path);
+    reopened_buffer.set_language(buffer.language);
+    reopened_buffer.encoding = file_encoding;
+    reopened_buffer.file_modified_time = modified_time;
+    reopened_buffer.had_decoding_errors = had_errors;
+    buffer.replace_with(reopened_buffer);
+    emit_dirty_state(&window, buffer_id, buffer);
+
+    Ok(file_encoding)
 }
 
 #[tauri::command]
-pub fn select_token_under_cursor(buffer_idx: usize, cursor: Cursor) -> Option<Selection> {
-    let editor_state = EDITOR_STATE.get().lock().unwrap();
-    let selection = editor_state.text_buffers[buffer_idx].select_token_under_cursor(cursor);
-    selection
+pub fn save_with_encoding(
+    window: tauri::Window,
+    buffer_id: BufferId,
+    encoding: String,
+    eol_sequence: Option<String>,
+    force: Option<bool>,
+) -> Result<FileEncoding, PapyrusError> {
+    let mut editor_state = lock_editor_state();
+    let buffer = editor_state.get_buffer_mut(buffer_id)?;
+    let mut file_encoding = FileEncoding::from_label(&encoding, false)?;
+    // Keep the byte order mark when saving in the same encoding
+    if file_encoding.encoding == buffer.encoding.encoding {
+        file_encoding.bom = buffer.encoding.bom;
+    }
+
+    let content = get_buffer_content(buffer, eol_sequence);
+    write_buffer_file(buffer, content, &file_encoding, force.unwrap_or(false))?;
+    buffer.encoding = file_encoding;
+    buffer.mark_saved();
+    emit_dirty_state(&window, buffer_id, buffer);
+    let buffer = editor_state.get_buffer(buffer_id)?;
+    store_undo_history(&editor_state, buffer);
+
+    Ok(file_encoding)
+}
+
+/// Reloads the buffer from its file, discarding its unsaved changes. The
+/// reload can be undone.
+#[tauri::command]
+pub fn reload_buffer(window: tauri::Window, buffer_id: BufferId) -> Result<(), PapyrusError> {
+    let mut editor_state = lock_editor_state();
+    let buffer = editor_state.get_buffer_mut(buffer_id)?;
+    let path = buffer.file_path.clone().ok_or(PapyrusError::NoFilePath)?;
+    let modified_time = file_handling::modified_time(&path);
+    let (text, _, had_errors) =
+        file_handling::read_file_content_with_encoding(&path, Some(buffer.encoding))?;
+    reload_buffer_content(&window, buffer_id, buffer, text);
+    buffer.file_modified_time = modified_time;
+    buffer.had_decoding_errors = had_errors;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn is_dirty(buffer_id: BufferId) -> Result<bool, PapyrusError> {
+    let editor_state = lock_editor_state();
+    Ok(editor_state.get_buffer(buffer_id)?.is_dirty())
+}
+
+/// Sets whether buffers that were edited back to their saved content are
+/// considered clean
+#[tauri::command]
+pub fn set_compare_content_hash(
+    window: tauri::Window,
+    buffer_id: BufferId,
+    enabled: bool,
+) -> Result<(), PapyrusError> {
+    let mut editor_state = lock_editor_state();
+    let buffer = editor_state.get_buffer_mut(buffer_id)?;
+    buffer.compare_content_hash = enabled;
+    emit_dirty_state(&window, buffer_id, buffer);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_line_endings(buffer_id: BufferId) -> Result<LineEndings, PapyrusError> {
+    let editor_state = lock_editor_state();
+    let buffer = editor_state.get_buffer(buffer_id)?;
+    Ok(buffer.line_endings)
+}
+
+/// Sets the line ending written when the buffer is next saved, replacing
+/// the line breaks of a file mixing several line endings
+#[tauri::command]
+pub fn convert_line_endings(
+    window: tauri::Window,
+    buffer_id: BufferId,
+    line_ending: LineEnding,
+) -> Result<LineEndings, PapyrusError> {
+    let mut editor_state = lock_editor_state();
+    let buffer = editor_state.get_buffer_mut(buffer_id)?;
+    buffer.line_endings = LineEndings {
+        line_ending,
+        mixed: false,
+    };
+    buffer.line_breaks.clear();
+    emit_dirty_state(&window, buffer_id, buffer);
+    Ok(buffer.line_endings)
+}
+
+#[tauri::command]
+pub fn get_highlighted_text(
+    buffer_id: BufferId,
+) -> Result<highlight::HighlightedText, PapyrusError> {
+    let mut editor_state = lock_editor_state();
+    let buffer = editor_state.get_buffer_mut(buffer_id)?;
+    Ok(buffer.highlight_complete_text())
+}
+
+#[tauri::command]
+pub fn get_highlighted_rows(
+    buffer_id: BufferId,
+    start_row: usize,
+    end_row: usize,
+) -> Result<highlight::HighlightedText, PapyrusError> {
+    let mut editor_state = lock_editor_state();
+    let buffer = editor_state.get_buffer_mut(buffer_id)?;
+    Ok(buffer.highlight_rows(start_row, end_row))
+}
+
+#[tauri::command]
+pub fn get_row_length(buffer_id: BufferId, row: usize) -> Result<usize, PapyrusError> {
+    let editor_state = lock_editor_state();
+    let buffer = editor_state.get_buffer(buffer_id)?;
+    check_row(buffer, row)?;
+    Ok(buffer.get_row_length(row))
+}
+
+#[tauri::command]
+pub fn get_lines_length(buffer_id: BufferId) -> Result<usize, PapyrusError> {
+    let editor_state = lock_editor_state();
+    let buffer = editor_state.get_buffer(buffer_id)?;
+    Ok(buffer.get_lines_length())
+}
+
+#[tauri::command]
+pub fn select_token_under_cursor(
+    buffer_id: BufferId,
+    cursor: Cursor,
+) -> Result<Option<Selection>, PapyrusError> {
+    let editor_state = lock_editor_state();
+    let buffer = editor_state.get_buffer(buffer_id)?;
+    check_cursor(buffer, &cursor)?;
+    Ok(buffer.select_token_under_cursor(cursor))
 }
 
 #[tauri::command]
 pub fn insert_text(
-    buffer_idx: usize,
+    window: tauri::Window,
+    buffer_id: BufferId,
     text: String,
     cursor: Cursor,
-) -> (highlight::HighlightedText, Cursor) {
-    let mut editor_state = EDITOR_STATE.get().lock().unwrap();
-    let updated_cursor = editor_state.text_buffers[buffer_idx].insert_text(text, cursor);
-    (
-        editor_state.text_buffers[buffer_idx].highlight_complete_text(),
+) -> Result<(highlight::LineChanges, Cursor), PapyrusError> {
+    let mut editor_state = lock_editor_state();
+    let buffer = editor_state.get_buffer_mut(buffer_id)?;
+    check_cursor(buffer, &cursor)?;
+    let updated_cursor = buffer.insert_text(text, cursor);
+    Ok((
+        take_line_changes(&window, buffer_id, buffer),
         updated_cursor,
-    )
+    ))
 }
 
 #[tauri::command]
 pub fn remove_text(
-    buffer_idx: usize,
+    window: tauri::Window,
+    buffer_id: BufferId,
     selection: Selection,
-) -> (highlight::HighlightedText, String, Cursor) {
-    let mut editor_state = EDITOR_STATE.get().lock().unwrap();
-    let (removed_text, updated_cursor) =
-        editor_state.text_buffers[buffer_idx].remove_text(selection);
-    (
-        editor_state.text_buffers[buffer_idx].highlight_complete_text(),
+) -> Result<(highlight::LineChanges, String, Cursor), PapyrusError> {
+    let mut editor_state = lock_editor_state();
+    let buffer = editor_state.get_buffer_mut(buffer_id)?;
+    check_selection(buffer, &selection)?;
+    let (removed_text, updated_cursor) = buffer.remove_text(selection);
+    Ok((
+        take_line_changes(&window, buffer_id, buffer),
         removed_text,
         updated_cursor,
-    )
+    ))
 }
 
 #[tauri::command]
-pub fn undo(buffer_idx: usize) -> Option<(highlight::HighlightedText, Cursor)> {
-    let mut editor_state = EDITOR_STATE.get().lock().unwrap();
-    let updated_cursor = editor_state.text_buffers[buffer_idx].undo();
-    match updated_cursor {
-        Some(cursor) => {
-            return Some((
-                editor_state.text_buffers[buffer_idx].highlight_complete_text(),
-                cursor,
-            ));
-        }
-        None => return None,
-    };
+pub fn insert_text_at_selections(
+    window: tauri::Window,
+    buffer_id: BufferId,
+    text: String,
+    selections: Vec<Selection>,
+) -> Result<(highlight::LineChanges, Vec<Selection>), PapyrusError> {
+    let mut editor_state = lock_editor_state();
+    let buffer = editor_state.get_buffer_mut(buffer_id)?;
+    for selection in &selections {
+        check_selection(buffer, selection)?;
+    }
+    let cursors = buffer.insert_text_at_selections(text, &selections);
+    Ok((take_line_changes(&window, buffer_id, buffer), cursors))
 }
 
 #[tauri::command]
-pub fn redo(buffer_idx: usize) -> Option<(highlight::HighlightedText, Cursor)> {
-    let mut editor_state = EDITOR_STATE.get().lock().unwrap();
-    let updated_cursor = editor_state.text_buffers[buffer_idx].redo();
-    match updated_cursor {
-        Some(cursor) => {
-            return Some((
-                editor_state.text_buffers[buffer_idx].highlight_complete_text(),
-                cursor,
-            ));
-        }
-        None => return None,
-    };
+pub fn remove_text_at_selections(
+    window: tauri::Window,
+    buffer_id: BufferId,
+    selections: Vec<Selection>,
+    forward: bool,
+) -> Result<(highlight::LineChanges, Vec<Selection>), PapyrusError> {
+    let mut editor_state = lock_editor_state();
+    let buffer = editor_state.get_buffer_mut(buffer_id)?;
+    for selection in &selections {
+        check_selection(buffer, selection)?;
+    }
+    let cursors = buffer.remove_text_at_selections(&selections, forward);
+    Ok((take_line_changes(&window, buffer_id, buffer), cursors))
+}
+
+#[tauri::command]
+pub fn add_next_occurrence(
+    buffer_id: BufferId,
+    selections: Vec<Selection>,
+) -> Result<Vec<Selection>, PapyrusError> {
+    let editor_state = lock_editor_state();
+    let buffer = editor_state.get_buffer(buffer_id)?;
+    for selection in &selections {
+        check_selection(buffer, selection)?;
+    }
+    Ok(buffer.add_next_occurrence(&selections))
+}
+
+#[tauri::command]
+pub fn box_selection(
+    buffer_id: BufferId,
+    anchor: Cursor,
+    head: Cursor,
+) -> Result<Vec<Selection>, PapyrusError> {
+    let editor_state = lock_editor_state();
+    let buffer = editor_state.get_buffer(buffer_id)?;
+    check_cursor(buffer, &anchor)?;
+    check_cursor(buffer, &head)?;
+    Ok(buffer.box_selection(&anchor, &head))
+}
+
+#[tauri::command]
+pub fn split_selection_into_lines(
+    buffer_id: BufferId,
+    selections: Vec<Selection>,
+) -> Result<Vec<Selection>, PapyrusError> {
+    let editor_state = lock_editor_state();
+    let buffer = editor_state.get_buffer(buffer_id)?;
+    for selection in &selections {
+        check_selection(buffer, selection)?;
+    }
+    Ok(buffer.split_selection_into_lines(&selections))
+}
+
+#[tauri::command]
+pub fn undo(
+    window: tauri::Window,
+    buffer_id: BufferId,
+) -> Result<Option<(highlight::LineChanges, Cursor)>, PapyrusError> {
+    let mut editor_state = lock_editor_state();
+    let buffer = editor_state.get_buffer_mut(buffer_id)?;
+    Ok(buffer
+        .undo()
+        .map(|cursor| (take_line_changes(&window, buffer_id, buffer), cursor)))
+}
+
+#[tauri::command]
+pub fn redo(
+    window: tauri::Window,
+    buffer_id: BufferId,
+) -> Result<Option<(highlight::LineChanges, Cursor)>, PapyrusError> {
+    let mut editor_state = lock_editor_state();
+    let buffer = editor_state.get_buffer_mut(buffer_id)?;
+    Ok(buffer
+        .redo()
+        .map(|cursor| (take_line_changes(&window, buffer_id, buffer), cursor)))
+}
+
+#[tauri::command]
+pub fn get_undo_history(buffer_id: BufferId) -> Result<Vec<HistoryNode>, PapyrusError> {
+    let editor_state = lock_editor_state();
+    Ok(editor_state.get_buffer(buffer_id)?.history.history())
+}
+
+#[tauri::command]
+pub fn goto_history(
+    window: tauri::Window,
+    buffer_id: BufferId,
+    node: UndoNodeId,
+) -> Result<Option<(highlight::LineChanges, Cursor)>, PapyrusError> {
+    let mut editor_state = lock_editor_state();
+    let buffer = editor_state.get_buffer_mut(buffer_id)?;
+    Ok(buffer
+        .goto_history(node)
+        .map(|cursor| (take_line_changes(&window, buffer_id, buffer), cursor)))
+}
+
+#[tauri::command]
+pub fn undo_earlier(
+    window: tauri::Window,
+    buffer_id: BufferId,
+    seconds: u64,
+) -> Result<Option<(highlight::LineChanges, Cursor)>, PapyrusError> {
+    let mut editor_state = lock_editor_state();
+    let buffer = editor_state.get_buffer_mut(buffer_id)?;
+    Ok(buffer
+        .earlier(Duration::from_secs(seconds))
+        .map(|cursor| (take_line_changes(&window, buffer_id, buffer), cursor)))
+}
+
+#[tauri::command]
+pub fn redo_later(
+    window: tauri::Window,
+    buffer_id: BufferId,
+    seconds: u64,
+) -> Result<Option<(highlight::LineChanges, Cursor)>, PapyrusError> {
+    let mut editor_state = lock_editor_state();
+    let buffer = editor_state.get_buffer_mut(buffer_id)?;
+    Ok(buffer
+        .later(Duration::from_secs(seconds))
+        .map(|cursor| (take_line_changes(&window, buffer_id, buffer), cursor)))
+}
+
+#[tauri::command]
+pub fn switch_undo_branch(
+    window: tauri::Window,
+    buffer_id: BufferId,
+    forward: bool,
+) -> Result<Option<(highlight::LineChanges, Cursor)>, PapyrusError> {
+    let mut editor_state = lock_editor_state();
+    let buffer = editor_state.get_buffer_mut(buffer_id)?;
+    Ok(buffer
+        .switch_branch(forward)
+        .map(|cursor| (take_line_changes(&window, buffer_id, buffer), cursor)))
+}
+
+#[tauri::command]
+pub fn begin_undo_group(buffer_id: BufferId) -> Result<(), PapyrusError> {
+    let mut editor_state = lock_editor_state();
+    editor_state.get_buffer_mut(buffer_id)?.begin_group();
+    Ok(())
+}
+
+#[tauri::command]
+pub fn end_undo_group(buffer_id: BufferId) -> Result<(), PapyrusError> {
+    let mut editor_state = lock_editor_state();
+    editor_state.get_buffer_mut(buffer_id)?.end_group();
+    Ok(())
+}
+
+#[tauri::command]
+pub fn find_all(
+    buffer_id: BufferId,
+    query: String,
+    options: SearchOptions,
+) -> Result<Vec<Selection>, PapyrusError> {
+    let editor_state = lock_editor_state();
+    let buffer = editor_state.get_buffer(buffer_id)?;
+    buffer.find_all(&query, &options)
+}
+
+#[tauri::command]
+pub fn find_next(
+    buffer_id: BufferId,
+    query: String,
+    options: SearchOptions,
+    cursor: Cursor,
+) -> Result<Option<Selection>, PapyrusError> {
+    let editor_state = lock_editor_state();
+    let buffer = editor_state.get_buffer(buffer_id)?;
+    check_cursor(buffer, &cursor)?;
+    buffer.find_next(&query, &options, &cursor)
+}
+
+#[tauri::command]
+pub fn find_prev(
+    buffer_id: BufferId,
+    query: String,
+    options: SearchOptions,
+    cursor: Cursor,
+) -> Result<Option<Selection>, PapyrusError> {
+    let editor_state = lock_editor_state();
+    let buffer = editor_state.get_buffer(buffer_id)?;
+    check_cursor(buffer, &cursor)?;
+    buffer.find_prev(&query, &options, &cursor)
+}
+
+#[tauri::command]
+pub fn replace(
+    window: tauri::Window,
+    buffer_id: BufferId,
+    query: String,
+    options: SearchOptions,
+    replacement: String,
+    cursor: Cursor,
+) -> Result<(highlight::LineChanges, Option<Selection>), PapyrusError> {
+    let mut editor_state = lock_editor_state();
+    let buffer = editor_state.get_buffer_mut(buffer_id)?;
+    check_cursor(buffer, &cursor)?;
+    let replaced = buffer.replace(&query, &options, &replacement, &cursor)?;
+    Ok((take_line_changes(&window, buffer_id, buffer), replaced))
+}
+
+#[tauri::command]
+pub fn replace_all(
+    window: tauri::Window,
+    buffer_id: BufferId,
+    query: String,
+    options: SearchOptions,
+    replacement: String,
+) -> Result<(highlight::LineChanges, usize), PapyrusError> {
+    let mut editor_state = lock_editor_state();
+    let buffer = editor_state.get_buffer_mut(buffer_id)?;
+    let count = buffer.replace_all(&query, &options, &replacement)?;
+    Ok((take_line_changes(&window, buffer_id, buffer), count))
 }
 
 #[tauri::command]
 pub fn add_indentation(
-    buffer_idx: usize,
+    window: tauri::Window,
+    buffer_id: BufferId,
     selection: Selection,
     tab_size: usize,
-) -> (highlight::HighlightedText, Selection) {
-    let mut editor_state = EDITOR_STATE.get().lock().unwrap();
-    let updated_selection =
-        editor_state.text_buffers[buffer_idx].add_indentation(selection, tab_size);
-    (
-        editor_state.text_buffers[buffer_idx].highlight_complete_text(),
+) -> Result<(highlight::LineChanges, Selection), PapyrusError> {
+    let mut editor_state = lock_editor_state();
+    let buffer = editor_state.get_buffer_mut(buffer_id)?;
+    check_selected_rows(buffer, &selection)?;
+    let updated_selection = buffer.add_indentation(selection, tab_size);
+    Ok((
+        take_line_changes(&window, buffer_id, buffer),
         updated_selection,
-    )
+    ))
 }
 
 #[tauri::command]
 pub fn remove_indentation(
-    buffer_idx: usize,
+    window: tauri::Window,
+    buffer_id: BufferId,
     selection: Selection,
     tab_size: usize,
-) -> (highlight::HighlightedText, Selection) {
-    let mut editor_state = EDITOR_STATE.get().lock().unwrap();
-    let updated_selection =
-        editor_state.text_buffers[buffer_idx].remove_indentation(selection, tab_size);
-    (
-        editor_state.text_buffers[buffer_idx].highlight_complete_text(),
+) -> Result<(highlight::LineChanges, Selection), PapyrusError> {
+    let mut editor_state = lock_editor_state();
+    let buffer = editor_state.get_buffer_mut(buffer_id)?;
+    check_selected_rows(buffer, &selection)?;
+    let updated_selection = buffer.remove_indentation(selection, tab_size);
+    Ok((
+        take_line_changes(&window, buffer_id, buffer),
         updated_selection,
-    )
+    ))
 }
 
 #[tauri::command]
-pub fn get_indent_size(buffer_idx: usize, row: usize) -> usize {
-    let editor_state = EDITOR_STATE.get().lock().unwrap();
-    let indent_size = editor_state.text_buffers[buffer_idx].get_indent_size(row);
-    indent_size
+pub fn get_indent_size(buffer_id: BufferId, row: usize) -> Result<usize, PapyrusError> {
+    let editor_state = lock_editor_state();
+    let buffer = editor_state.get_buffer(buffer_id)?;
+    check_row(buffer, row)?;
+    Ok(buffer.get_indent_size(row))
 }
 
 #[tauri::command]
-pub fn get_selected_text(buffer_idx: usize, selection: Selection) -> String {
-    let editor_state = EDITOR_STATE.get().lock().unwrap();
-    let selected_text = editor_state.text_buffers[buffer_idx].get_selected_text(selection);
-    selected_text
+pub fn get_selected_text(
+    buffer_id: BufferId,
+    selection: Selection,
+) -> Result<String, PapyrusError> {
+    let editor_state = lock_editor_state();
+    let buffer = editor_state.get_buffer(buffer_id)?;
+    check_selection(buffer, &selection)?;
+    Ok(buffer.get_selected_text(selection))
 }
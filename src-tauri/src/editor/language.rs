@@ -0,0 +1,250 @@
+use std::path::Path;
+
+use state::InitCell;
+
+use crate::editor::highlight::HighlightConfiguration;
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Language {
+    PlainText,
+    Python,
+    Rust,
+    JavaScript,
+    TypeScript,
+    Tsx,
+    Json,
+    Toml,
+    Markdown,
+    C,
+    Go,
+    Bash,
+}
+
+/// Grammar, highlights query and file associations of a language
+pub struct LanguageConfiguration {
+    pub language: Language,
+    pub name: &'static str,
+    pub file_extensions: &'static [&'static str],
+    pub file_names: &'static [&'static str],
+    pub shebangs: &'static [&'static str],
+    grammar: fn() -> tree_sitter::Language,
+    highlights_queries: &'static [&'static str],
+    highlight_configuration: InitCell<Option<HighlightConfiguration>>,
+}
+
+impl LanguageConfiguration {
+    /// Returns the tree sitter grammar of the language
+    pub fn grammar(&self) -> tree_sitter::Language {
+        (self.grammar)()
+    }
+
+    /// Returns the compiled highlights query of the language, compiling
+    /// it on first use. Languages whose query fails to compile are not
+    /// highlighted.
+    pub fn highlight_configuration(&self) -> Option<&HighlightConfiguration> {
+        self.highlight_configuration
+            .get_or_init(|| {
+                HighlightConfiguration::new(self.grammar(), &self.highlights_queries.join("\n"))
+                    .ok()
+            })
+            .as_ref()
+    }
+}
+
+static LANGUAGES: [LanguageConfiguration; 11] = [
+    LanguageConfiguration {
+        language: Language::Python,
+        name: "Python",
+        file_extensions: &["py", "pyi", "pyw"],
+        file_names: &[],
+        shebangs: &["python"],
+        grammar: tree_sitter_python::language,
+        highlights_queries: &[tree_sitter_python::HIGHLIGHT_QUERY],
+        highlight_configuration: InitCell::new(),
+    },
+    LanguageConfiguration {
+        language: Language::Rust,
+        name: "Rust",
+        file_extensions: &["rs"],
+        file_names: &[],
+        shebangs: &[],
+        grammar: tree_sitter_rust::language,
+        highlights_queries: &[tree_sitter_rust::HIGHLIGHT_QUERY],
+        highlight_configuration: InitCell::new(),
+    },
+    LanguageConfiguration {
+        language: Language::JavaScript,
+        name: "JavaScript",
+        file_extensions: &["js", "mjs", "cjs", "jsx"],
+        file_names: &[],
+        shebangs: &["node"],
+        grammar: tree_sitter_javascript::language,
+        highlights_queries: &[
+            tree_sitter_javascript::JSX_HIGHLIGHT_QUERY,
+            tree_sitter_javascript::HIGHLIGHT_QUERY,
+        ],
+        highlight_configuration: InitCell::new(),
+    },
+    LanguageConfiguration {
+        language: Language::TypeScript,
+        name: "TypeScript",
+        file_extensions: &["ts", "mts", "cts"],
+        file_names: &[],
+        shebangs: &["deno", "ts-node"],
+        grammar: tree_sitter_typescript::language_typescript,
+        highlights_queries: &[
+            tree_sitter_typescript::HIGHLIGHT_QUERY,
+            tree_sitter_javascript::HIGHLIGHT_QUERY,
+        ],
+        highlight_configuration: InitCell::new(),
+    },
+    LanguageConfiguration {
+        language: Language::Tsx,
+        name: "TypeScript JSX",
+        file_extensions: &["tsx"],
+        file_names: &[],
+        shebangs: &[],
+        grammar: tree_sitter_typescript::language_tsx,
+        highlights_queries: &[
+            tree_sitter_typescript::HIGHLIGHT_QUERY,
+            tree_sitter_javascript::JSX_HIGHLIGHT_QUERY,
+            tree_sitter_javascript::HIGHLIGHT_QUERY,
+        ],
+        highlight_configuration: InitCell::new(),
+    },
+    LanguageConfiguration {
+        language: Language::Json,
+        name: "JSON",
+        file_extensions: &["json", "jsonc"],
+        file_names: &[".prettierrc", ".eslintrc", ".babelrc"],
+        shebangs: &[],
+        grammar: tree_sitter_json::language,
+        highlights_queries: &[tree_sitter_json::HIGHLIGHT_QUERY],
+        highlight_configuration: InitCell::new(),
+    },
+    LanguageConfiguration {
+        language: Language::Toml,
+        name: "TOML",
+        file_extensions: &["toml"],
+        file_names: &["Cargo.lock", "Pipfile", "poetry.lock"],
+        shebangs: &[],
+        grammar: tree_sitter_toml::language,
+        highlights_queries: &[tree_sitter_toml::HIGHLIGHT_QUERY],
+        highlight_configuration: InitCell::new(),
+    },
+    LanguageConfiguration {
+        language: Language::Markdown,
+        name: "Markdown",
+        file_extensions: &["md", "markdown"],
+        file_names: &[],
+        shebangs: &[],
+        grammar: tree_sitter_md::language,
+        highlights_queries: &[tree_sitter_md::HIGHLIGHTS_QUERY],
+        highlight_configuration: InitCell::new(),
+    },
+    LanguageConfiguration {
+        language: Language::C,
+        name: "C",
+        file_extensions: &["c", "h"],
+        file_names: &[],
+        shebangs: &[],
+        grammar: tree_sitter_c::language,
+        highlights_queries: &[tree_sitter_c::HIGHLIGHT_QUERY],
+        highlight_configuration: InitCell::new(),
+    },
+    LanguageConfiguration {
+        language: Language::Go,
+        name: "Go",
+        file_extensions: &["go"],
+        file_names: &[],
+        shebangs: &[],
+        grammar: tree_sitter_go::language,
+        highlights_queries: &[tree_sitter_go::HIGHLIGHT_QUERY],
+        highlight_configuration: InitCell::new(),
+    },
+    LanguageConfiguration {
+        language: Language::Bash,
+        name: "Bash",
+        file_extensions: &["sh", "bash", "zsh"],
+        file_names: &[".bashrc", ".bash_profile", ".zshrc", ".profile", "PKGBUILD"],
+        shebangs: &["bash", "sh", "zsh", "dash", "ksh"],
+        grammar: tree_sitter_bash::language,
+        highlights_queries: &[tree_sitter_bash::HIGHLIGHT_QUERY],
+        highlight_configuration: InitCell::new(),
+    },
+];
+
+/// Returns the configurations of all registered languages
+pub fn get_language_configurations() -> &'static [LanguageConfiguration] {
+    &LANGUAGES
+}
+
+/// Returns the configuration of the given language, or none for plain text
+pub fn get_language_configuration(language: Language) -> Option<&'static LanguageConfiguration> {
+    LANGUAGES
+        .iter()
+        .find(|configuration| configuration.language == language)
+}
+
+/// Returns the display name of the given language
+pub fn get_language_name(language: Language) -> &'static str {
+    match get_language_configuration(language) {
+        Some(configuration) => configuration.name,
+        None => "Plain Text",
+    }
+}
+
+/// Returns the interpreter named by the shebang line of the content if present
+fn get_shebang_interpreter(content: &str) -> Option<&str> {
+    let command = content.lines().next()?.strip_prefix("#!")?;
+    let mut parts = command.split_whitespace();
+    let interpreter = parts.next()?.rsplit('/').next()?;
+    if interpreter == "env" {
+        return parts.find(|part| !part.starts_with('-') && !part.contains('='));
+    }
+    Some(interpreter)
+}
+
+/// Detects the language of a file from its name, its extension and
+/// finally the shebang line of its content
+pub fn detect_language(path: &str, content: &str) -> Language {
+    let path = Path::new(path);
+    let file_name = path
+        .file_name()
+        .unwrap_or_default()
+        .to_str()
+        .unwrap_or_default();
+    let extension = path
+        .extension()
+        .unwrap_or_default()
+        .to_str()
+        .unwrap_or_default()
+        .to_lowercase();
+
+    if let Some(configuration) = LANGUAGES
+        .iter()
+        .find(|configuration| configuration.file_names.contains(&file_name))
+    {
+        return configuration.language;
+    }
+
+    if let Some(configuration) = LANGUAGES
+        .iter()
+        .find(|configuration| configuration.file_extensions.contains(&extension.as_str()))
+    {
+        return configuration.language;
+    }
+
+    if let Some(interpreter) = get_shebang_interpreter(content) {
+        // Versioned interpreters like `python3.11` run the same language
+        let interpreter = interpreter.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+        if let Some(configuration) = LANGUAGES
+            .iter()
+            .find(|configuration| configuration.shebangs.contains(&interpreter))
+        {
+            return configuration.language;
+        }
+    }
+
+    Language::PlainText
+}
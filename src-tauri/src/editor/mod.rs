@@ -1,3 +1,6 @@
 pub mod highlight;
+pub mod language;
 pub mod state;
 pub mod text_buffer;
+pub mod theme;
+pub mod undo_tree;
@@ -1,19 +1,191 @@
+use std::collections::HashMap;
+use std::fmt;
 use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
 
 use crate::editor::text_buffer;
+use crate::editor::theme::Theme;
+use crate::editor_io::recovery::Recovery;
+use crate::editor_io::watcher::{FileEvent, WorkspaceWatcher};
+use crate::error::PapyrusError;
+use crate::search::file_index::FileIndex;
+use crate::search::quick_open::RecentFiles;
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(transparent)]
+/// Opaque identifier of an open buffer, never reused once the buffer is closed
+pub struct BufferId(u64);
+
+impl fmt::Display for BufferId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(transparent)]
+/// Identifier of a running find in files search
+pub struct SearchId(u64);
 
 pub struct EditorState {
-    pub text_buffers: Vec<text_buffer::LineTextBuffer>,
+    pub text_buffers: HashMap<BufferId, text_buffer::LineTextBuffer>,
+    next_buffer_id: u64,
     pub pty_pair: Option<portable_pty::PtyPair>,
     pub pty_writer: Option<Box<dyn Write + Send>>,
+    pub theme: Theme,
+    /// Cancellation flags of the running find in files searches
+    pub searches: HashMap<SearchId, Arc<AtomicBool>>,
+    next_search_id: u64,
+    /// App data dir, where undo histories are stored. Unset until the app
+    /// is set up.
+    pub data_dir: Option<PathBuf>,
+    /// Snapshots of the unsaved buffers, unset like the data dir
+    pub recovery: Option<Recovery>,
+    /// Watcher of the open workspace folder
+    pub watcher: Option<WorkspaceWatcher>,
+    /// Files of the open workspace folder, unset until it is indexed
+    pub file_index: Option<FileIndex>,
+    /// Number of the latest indexing started, so an index built for a
+    /// workspace that was replaced meanwhile is dropped
+    index_generation: u64,
+    /// File events seen while the index is built, applied to it once done
+    pending_file_events: Option<Vec<FileEvent>>,
+    /// Files opened recently, ranked higher by quick open
+    pub recent_files: RecentFiles,
 }
 
 impl EditorState {
     pub fn new() -> Self {
         Self {
-            text_buffers: vec![],
+            text_buffers: HashMap::new(),
+            next_buffer_id: 0,
             pty_pair: None,
             pty_writer: None,
+            theme: Theme::default(),
+            searches: HashMap::new(),
+            next_search_id: 0,
+            data_dir: None,
+            recovery: None,
+            watcher: None,
+            file_index: None,
+            index_generation: 0,
+            pending_file_events: None,
+            recent_files: RecentFiles::new(),
+        }
+    }
+
+    /// Adds a buffer and returns its newly allocated id
+    pub fn add_buffer(&mut self, buffer: text_buffer::LineTextBuffer) -> BufferId {
+        let buffer_id = BufferId(self.next_buffer_id);
+        self.next_buffer_id += 1;
+        self.text_buffers.insert(buffer_id, buffer);
+        buffer_id
+    }
+
+    /// Sets the app data dir, where undo histories and the snapshots of
+    /// unsaved buffers are stored
+    pub fn set_data_dir(&mut self, data_dir: PathBuf) {
+        self.recovery = Some(Recovery::new(&data_dir));
+        self.data_dir = Some(data_dir);
+    }
+
+    /// Drops the index of the previous workspace and returns the
+    /// generation of the index about to be built
+    pub fn start_indexing(&mut self) -> u64 {
+        self.index_generation += 1;
+        self.file_index = None;
+        self.pending_file_events = Some(vec![]);
+        self.index_generation
+    }
+
+    /// Installs an index built in the background, after applying the file
+    /// events seen meanwhile. Returns false, dropping the index, if another
+    /// indexing started since.
+    pub fn finish_indexing(&mut self, generation: u64, mut file_index: FileIndex) -> bool {
+        if generation != self.index_generation {
+            return false;
         }
+        if let Some(events) = self.pending_file_events.take() {
+            file_index.apply(&events);
+        }
+        self.file_index = Some(file_index);
+        true
+    }
+
+    /// Applies file events to the index of the workspace, or queues them
+    /// while it is being built
+    pub fn apply_file_events(&mut self, events: &[FileEvent]) {
+        if let Some(file_index) = &mut self.file_index {
+            file_index.apply(events);
+        } else if let Some(pending_file_events) = &mut self.pending_file_events {
+            pending_file_events.extend_from_slice(events);
+        }
+    }
+
+    /// Removes the buffer with the given id, returning it if it was open
+    pub fn remove_buffer(&mut self, buffer_id: BufferId) -> Option<text_buffer::LineTextBuffer> {
+        self.text_buffers.remove(&buffer_id)
+    }
+
+    pub fn get_buffer(
+        &self,
+        buffer_id: BufferId,
+    ) -> Result<&text_buffer::LineTextBuffer, PapyrusError> {
+        self.text_buffers
+            .get(&buffer_id)
+            .ok_or(PapyrusError::BufferNotFound(buffer_id))
+    }
+
+    pub fn get_buffer_mut(
+        &mut self,
+        buffer_id: BufferId,
+    ) -> Result<&mut text_buffer::LineTextBuffer, PapyrusError> {
+        self.text_buffers
+            .get_mut(&buffer_id)
+            .ok_or(PapyrusError::BufferNotFound(buffer_id))
+    }
+
+    /// Returns the id of the buffer opened from the given path if any
+    pub fn find_buffer_by_path(&self, path: &str) -> Option<BufferId> {
+        self.text_buffers
+            .iter()
+            .find(|(_, buffer)| buffer.file_path.as_deref() == Some(path))
+            .map(|(buffer_id, _)| *buffer_id)
+    }
+
+    /// Updates the paths of the buffers opened from a renamed file or from
+    /// the files of a renamed folder. Returns the ids and new paths of the
+    /// updated buffers.
+    pub fn rename_buffer_paths(&mut self, from: &str, to: &str) -> Vec<(BufferId, String)> {
+        let mut renamed = vec![];
+        for (buffer_id, buffer) in self.text_buffers.iter_mut() {
+            let rest = match &buffer.file_path {
+                Some(path) => match Path::new(path).strip_prefix(from) {
+                    Ok(rest) => rest.to_path_buf(),
+                    Err(_) => continue,
+                },
+                None => continue,
+            };
+            // Joining an empty path would add a trailing separator
+            let path = if rest.as_os_str().is_empty() {
+                to.to_string()
+            } else {
+                Path::new(to).join(rest).to_string_lossy().to_string()
+            };
+            buffer.file_path = Some(path.clone());
+            renamed.push((*buffer_id, path));
+        }
+        renamed
+    }
+
+    /// Registers a running search with its cancellation flag and returns
+    /// its newly allocated id
+    pub fn add_search(&mut self, cancelled: Arc<AtomicBool>) -> SearchId {
+        let search_id = SearchId(self.next_search_id);
+        self.next_search_id += 1;
+        self.searches.insert(search_id, cancelled);
+        search_id
     }
 }
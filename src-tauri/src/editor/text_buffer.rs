@@ -1,27 +1,38 @@
-use std::collections::VecDeque;
+use std::ops::Range;
+use std::time::{Duration, Instant, SystemTime};
 
-use crate::editor::highlight;
+use regex::Regex;
+use ropey::Rope;
 
-use super::highlight::LanguageHighlightTypeMapping;
+use crate::editor::highlight;
+use crate::editor::language;
+pub use crate::editor::language::Language;
+use crate::editor::undo_tree::{UndoNodeId, UndoTree};
+use crate::editor_io::encoding::FileEncoding;
+use crate::editor_io::line_ending::{self, LineEnding, LineEndings};
+use crate::editor_io::undo_history;
+use crate::error::PapyrusError;
+use crate::search::query::{self, SearchOptions};
 
-#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+/// Position in the buffer. The column counts UTF-16 code units from the
+/// start of the row, the same unit as the length of a JavaScript string.
 pub struct Cursor {
     pub row: usize,
     pub column: usize,
 }
 
-#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct Selection {
     pub start: Cursor,
     pub end: Cursor,
 }
 
-pub enum Language {
-    PlainText,
-    Python,
-}
+/// Typing or deleting within this time of the previous edit is undone
+/// together with it
+const COALESCE_TIMEOUT: Duration = Duration::from_millis(1000);
 
-#[derive(Debug)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
 pub enum Update {
     InsertUpdate {
         start: Cursor,
@@ -32,273 +43,566 @@ pub enum Update {
         selection: Selection,
         text: String,
     },
+    /// Updates undone and redone together, in the order they were applied
+    GroupUpdate {
+        updates: Vec<Update>,
+    },
 }
 
-/// Basic text buffer implementation using lines
+/// Rows changed by edits that have not been reported to the frontend yet
+#[derive(Debug)]
+struct PendingLineChanges {
+    start_row: usize,
+    unchanged_suffix: usize,
+    old_lines_length: usize,
+    invalidated_rows: Vec<(usize, usize)>,
+}
+
+/// Text buffer implementation backed by a rope
+///
+/// Rows and columns are exposed the same way as a line based buffer,
+/// with columns counted in UTF-16 code units (see `Cursor`). They are
+/// converted to char and byte offsets internally, tree-sitter points use
+/// byte columns.
 pub struct LineTextBuffer {
     pub file_path: Option<String>,
-    pub lines: Vec<String>,
+    pub rope: Rope,
     pub syntax_tree: Option<tree_sitter::Tree>,
+    pub parser: Option<tree_sitter::Parser>,
     pub language: Language,
-    pub tokens: Option<Vec<(tree_sitter::Range, String)>>,
-    pub updates: VecDeque<Update>,
-    pub update_idx: usize,
+    pub encoding: FileEncoding,
+    pub line_endings: LineEndings,
+    /// Line break ending each line of a file mixing several line endings,
+    /// written back when saving. Empty unless the line endings are mixed.
+    pub line_breaks: Vec<LineEnding>,
+    pending_changes: Option<PendingLineChanges>,
+    pub history: UndoTree,
+    /// Last cursor position reported by the frontend, restored along with
+    /// the unsaved buffer after a crash
+    pub cursor: Cursor,
+    /// Undo tree node and content hash when the buffer was loaded or last
+    /// saved. The node is None when the saved state is not in the history.
+    pub saved_node: Option<UndoNodeId>,
+    pub saved_content_hash: u64,
+    pub saved_line_endings: LineEndings,
+    /// Modification time of the linked file when it was loaded or last
+    /// saved, to avoid overwriting changes made by other programs
+    pub file_modified_time: Option<SystemTime>,
+    /// Whether the linked file had bytes that are malformed in its
+    /// encoding, which saving would replace with U+FFFD
+    pub had_decoding_errors: bool,
+    /// Whether a buffer whose undo position moved away from the saved one
+    /// is still clean when its content hashes to the saved content
+    pub compare_content_hash: bool,
+    /// Dirty state last reported to the frontend
+    reported_dirty: bool,
+    /// Number of nested groups that are open and the updates logged since
+    /// the outermost one began
+    group_depth: usize,
+    group_updates: Vec<Update>,
+    /// Time of the last update that later edits can be coalesced with
+    last_update_time: Option<Instant>,
 }
 
 impl LineTextBuffer {
-    /// Creates a new line based text buffer from the given initial text
+    /// Creates a new line based text buffer from the given initial text.
+    /// The line ending of the text is detected and line breaks are
+    /// normalized to LF.
     pub fn new(initial_text: String) -> Self {
-        let mut lines: Vec<String> = initial_text.lines().map(String::from).collect();
-
-        if initial_text.ends_with("\n") && !initial_text.ends_with("\\n") {
-            lines.push("".into());
-        }
-
+        let line_endings = line_ending::detect_line_endings(&initial_text);
+        let line_breaks = if line_endings.mixed {
+            line_ending::detect_line_breaks(&initial_text)
+        } else {
+            vec![]
+        };
+        let initial_text = line_ending::normalize_line_endings(initial_text);
+        let saved_content_hash = undo_history::fnv1a_hash(initial_text.bytes());
         Self {
             file_path: None,
-            lines,
+            rope: Rope::from_str(&initial_text),
             syntax_tree: None,
+            parser: None,
             language: Language::PlainText,
-            tokens: None,
-            updates: VecDeque::new(),
-            update_idx: 0,
+            encoding: FileEncoding::default(),
+            line_endings,
+            line_breaks,
+            pending_changes: None,
+            history: UndoTree::new(),
+            cursor: Cursor { row: 0, column: 0 },
+            saved_node: Some(UndoNodeId(0)),
+            saved_content_hash,
+            saved_line_endings: line_endings,
+            file_modified_time: None,
+            had_decoding_errors: false,
+            compare_content_hash: false,
+            reported_dirty: false,
+            group_depth: 0,
+            group_updates: vec![],
+            last_update_time: None,
         }
     }
 
     /// Creates a new line based text buffer from the given initial text
     /// with a linked file
     pub fn from_file(initial_text: String, path: String) -> Self {
-        let mut lines: Vec<String> = initial_text.lines().map(String::from).collect();
+        let mut buffer = Self::new(initial_text);
+        buffer.file_path = Some(path);
+        buffer
+    }
+
+    /// Replaces the buffer with one reopened from its file, keeping the
+    /// dirty state last reported so a change of it is still emitted
+    pub fn replace_with(&mut self, buffer: LineTextBuffer) {
+        let reported_dirty = self.reported_dirty;
+        *self = buffer;
+        self.reported_dirty = reported_dirty;
+    }
 
-        if lines.len() == 0 {
-            lines.push("".into());
+    /// Returns the content of the buffer joined with the given line break
+    pub fn get_content(&self, eol_sequence: String) -> String {
+        if eol_sequence == "\n" {
+            return self.rope.to_string();
         }
+        self.get_lines().join(&eol_sequence)
+    }
 
-        if initial_text.ends_with("\n") && !initial_text.ends_with("\\n") {
-            lines.push("".into());
+    /// Returns the content of the buffer as written to its file, with the
+    /// line ending of the buffer or, for lines of a file mixing several
+    /// line endings, the line break they had
+    pub fn get_file_content(&self) -> String {
+        let line_ending = self.line_endings.line_ending;
+        if self.line_breaks.is_empty() {
+            return self.get_content(line_ending.sequence().to_string());
+        }
+        let mut content = String::with_capacity(self.rope.len_bytes());
+        for (row, line) in self.get_lines().iter().enumerate() {
+            if row > 0 {
+                let line_break = self.line_breaks.get(row - 1).unwrap_or(&line_ending);
+                content.push_str(line_break.sequence());
+            }
+            content.push_str(line);
         }
+        content
+    }
 
-        Self {
-            file_path: Some(path),
-            lines,
-            syntax_tree: None,
-            language: Language::PlainText,
-            tokens: None,
-            updates: VecDeque::new(),
-            update_idx: 0,
+    /// Returns a hash of the content of the buffer, with LF line breaks
+    pub fn content_hash(&self) -> u64 {
+        undo_history::fnv1a_hash(self.rope.bytes())
+    }
+
+    /// Records the current undo position and content as saved
+    pub fn mark_saved(&mut self) {
+        self.saved_node = Some(self.history.current());
+        self.saved_content_hash = self.content_hash();
+        self.saved_line_endings = self.line_endings;
+    }
+
+    /// Returns whether saving would change the file, because the undo
+    /// position or the line endings differ from when the buffer was loaded
+    /// or last saved
+    pub fn is_dirty(&self) -> bool {
+        if self.line_endings != self.saved_line_endings {
+            return true;
         }
+        if self.saved_node == Some(self.history.current()) {
+            return false;
+        }
+        !self.compare_content_hash || self.content_hash() != self.saved_content_hash
     }
 
-    pub fn get_content(&self, eol_sequence: String) -> String {
-        let content = self.lines.join(&eol_sequence);
-        content
+    /// Replaces the content with the text of the linked file, which changed
+    /// on disk, as a single undo step and marks the buffer saved
+    pub fn reload(&mut self, text: String) {
+        let line_endings = line_ending::detect_line_endings(&text);
+        let line_breaks = if line_endings.mixed {
+            line_ending::detect_line_breaks(&text)
+        } else {
+            vec![]
+        };
+        let text = line_ending::normalize_line_endings(text);
+        if undo_history::fnv1a_hash(text.bytes()) != self.content_hash() {
+            self.end_all_groups();
+            self.replace_char_ranges(vec![(0..self.rope.len_chars(), text)]);
+            self.last_update_time = None;
+        }
+        self.line_endings = line_endings;
+        self.line_breaks = line_breaks;
+        if !self.is_valid_cursor(&self.cursor) {
+            self.cursor = Cursor { row: 0, column: 0 };
+        }
+        self.mark_saved();
+    }
+
+    /// Returns the dirty state if it changed since it was last taken
+    pub fn take_dirty_change(&mut self) -> Option<bool> {
+        let is_dirty = self.is_dirty();
+        if is_dirty == self.reported_dirty {
+            return None;
+        }
+        self.reported_dirty = is_dirty;
+        Some(is_dirty)
+    }
+
+    /// Returns the text of the given row without the line break
+    pub fn get_line(&self, row: usize) -> String {
+        let line = self.rope.line(row);
+        let mut line = line.to_string();
+        if line.ends_with('\n') {
+            line.pop();
+        }
+        line
+    }
+
+    /// Returns the text of every row without line breaks
+    pub fn get_lines(&self) -> Vec<String> {
+        (0..self.get_lines_length())
+            .map(|row| self.get_line(row))
+            .collect()
+    }
+
+    /// Sets the language of the buffer and discards the parser and
+    /// syntax tree of the previous language
+    pub fn set_language(&mut self, language: Language) {
+        self.language = language;
+        self.parser = None;
+        self.syntax_tree = None;
+    }
+
+    /// Converts a cursor into a char index into the rope. Columns past the
+    /// end of the buffer are clamped and columns inside a surrogate pair are
+    /// rounded down to the start of the character.
+    fn cursor_to_char_idx(&self, cursor: &Cursor) -> usize {
+        let row = cursor.row.min(self.get_lines_length() - 1);
+        let line_start = self.rope.line_to_char(row);
+        let line_start_utf16 = self.rope.char_to_utf16_cu(line_start);
+        let column = if row == cursor.row {
+            cursor.column.min(self.get_row_length(row))
+        } else {
+            self.get_row_length(row)
+        };
+        self.rope.utf16_cu_to_char(line_start_utf16 + column)
+    }
+
+    /// Converts a char index into the rope into a cursor
+    fn char_idx_to_cursor(&self, char_idx: usize) -> Cursor {
+        let row = self.rope.char_to_line(char_idx);
+        let line_start = self.rope.line_to_char(row);
+        Cursor {
+            row,
+            column: self.rope.char_to_utf16_cu(char_idx) - self.rope.char_to_utf16_cu(line_start),
+        }
+    }
+
+    /// Converts a byte index into the rope into a tree-sitter point
+    fn byte_idx_to_point(&self, byte_idx: usize) -> tree_sitter::Point {
+        let row = self.rope.byte_to_line(byte_idx);
+        tree_sitter::Point::new(row, byte_idx - self.rope.line_to_byte(row))
+    }
+
+    /// Converts a tree-sitter point into a cursor
+    fn point_to_cursor(&self, point: tree_sitter::Point) -> Cursor {
+        let byte_idx = self.rope.line_to_byte(point.row) + point.column;
+        self.char_idx_to_cursor(self.rope.byte_to_char(byte_idx))
+    }
+
+    /// Applies an edit to the stored syntax tree so that the next parse
+    /// only has to reparse the changed region
+    fn edit_syntax_tree(&mut self, edit: tree_sitter::InputEdit) {
+        if let Some(syntax_tree) = self.syntax_tree.as_mut() {
+            syntax_tree.edit(&edit);
+        }
     }
 
     /// Highlights the entire text in plain text
     pub fn get_highlighted_text(&self) -> highlight::HighlightedText {
         let mut highlighted_text = highlight::HighlightedText { text: vec![] };
 
-        for line in self.lines.iter() {
+        for line in self.get_lines() {
             highlighted_text
                 .text
-                .push(vec![(highlight::HighlightType::None, line.clone())])
+                .push(vec![(highlight::HighlightType::None, line)])
         }
 
         highlighted_text
     }
 
-    /// Create syntax tree for the current language
+    /// Create or incrementally update the syntax tree for the current language
+    ///
+    /// Rows whose syntax changed because of the reparse are marked as
+    /// invalidated in the pending line changes. The buffer falls back to
+    /// plain text if the grammar cannot be loaded or the text parsed.
     fn create_syntax_tree(&mut self) {
-        let mut parser = tree_sitter::Parser::new();
-        match self.language {
-            Language::Python => {
-                parser
-                    .set_language(tree_sitter_python::language())
-                    .expect("Tree sitter version mismatch");
-            }
-            _ => {
+        if self.parser.is_none() {
+            let configuration = match language::get_language_configuration(self.language) {
+                Some(configuration) => configuration,
+                None => {
+                    return;
+                }
+            };
+            let mut parser = tree_sitter::Parser::new();
+            if parser.set_language(configuration.grammar()).is_err() {
+                self.syntax_tree = None;
                 return;
             }
+            self.parser = Some(parser);
         }
-        let tree = parser
-            .parse_with(
-                &mut |_byte: usize, position: tree_sitter::Point| -> &[u8] {
-                    let row = position.row as usize;
-                    let column = position.column as usize;
-                    if row < self.lines.len() {
-                        if column < self.lines[row].as_bytes().len() {
-                            &self.lines[row].as_bytes()[column..]
-                        } else {
-                            "\n".as_bytes()
-                        }
+
+        let rope = &self.rope;
+        let tree = self.parser.as_mut().and_then(|parser| {
+            parser.parse_with(
+                &mut |byte: usize, _position: tree_sitter::Point| -> &[u8] {
+                    if byte < rope.len_bytes() {
+                        let (chunk, chunk_byte_idx, _, _) = rope.chunk_at_byte(byte);
+                        &chunk.as_bytes()[byte - chunk_byte_idx..]
                     } else {
                         &[]
                     }
                 },
-                None,
+                self.syntax_tree.as_ref(),
             )
-            .unwrap();
+        });
+        let tree = match tree {
+            Some(tree) => tree,
+            None => {
+                // Rows highlighted from the previous tree are now plain text
+                if let (Some(_), Some(pending)) =
+                    (self.syntax_tree.take(), self.pending_changes.as_mut())
+                {
+                    pending.invalidated_rows.push((0, self.rope.len_lines()));
+                }
+                self.parser = None;
+                return;
+            }
+        };
+
+        if let (Some(old_tree), Some(pending)) =
+            (self.syntax_tree.as_ref(), self.pending_changes.as_mut())
+        {
+            let lines_length = rope.len_lines();
+            for range in old_tree.changed_ranges(&tree) {
+                let start_row = range.start_point.row.min(lines_length);
+                let end_row = (range.end_point.row + 1).min(lines_length);
+                pending.invalidated_rows.push((start_row, end_row));
+            }
+        }
 
         self.syntax_tree = Some(tree);
     }
 
-    /// Gets leaf node information from syntax tree
-    fn get_highlighted_tokens(&mut self, cursor: &mut tree_sitter::TreeCursor, parent_kind: &str) {
-        let current_kind = parent_kind.to_owned() + "." + cursor.node().kind();
-        if cursor.node().child_count() == 0 {
-            self.tokens
-                .as_mut()
-                .unwrap()
-                .push((cursor.node().range(), current_kind));
-        } else {
-            if cursor.goto_first_child() {
-                self.get_highlighted_tokens(cursor, &current_kind);
-                cursor.goto_parent();
-            }
+    /// Runs the highlights query over the rows from start_row up to end_row
+    /// and returns the highlight type of every byte of those rows
+    fn get_highlight_types(
+        &self,
+        start_row: usize,
+        end_row: usize,
+    ) -> Vec<Vec<highlight::HighlightType>> {
+        let mut highlight_types: Vec<Vec<highlight::HighlightType>> = (start_row..end_row)
+            .map(|row| vec![highlight::HighlightType::None; self.get_line(row).len()])
+            .collect();
+
+        let (syntax_tree, configuration) = match (
+            self.syntax_tree.as_ref(),
+            language::get_language_configuration(self.language)
+                .and_then(|configuration| configuration.highlight_configuration()),
+        ) {
+            (Some(syntax_tree), Some(configuration)) => (syntax_tree, configuration),
+            _ => return highlight_types,
+        };
+
+        let rope = &self.rope;
+        let mut query_cursor = tree_sitter::QueryCursor::new();
+        query_cursor.set_point_range(
+            tree_sitter::Point::new(start_row, 0)..tree_sitter::Point::new(end_row, 0),
+        );
+        let captures = query_cursor.captures(
+            &configuration.query,
+            syntax_tree.root_node(),
+            |node: tree_sitter::Node| {
+                rope.byte_slice(node.byte_range())
+                    .chunks()
+                    .map(|chunk| chunk.as_bytes())
+            },
+        );
+
+        // Earlier patterns take precedence for the same node while nested
+        // nodes are painted over their parents
+        let mut spans = vec![];
+        for (query_match, capture_idx) in captures {
+            let capture = query_match.captures[capture_idx];
+            spans.push((
+                capture.node.byte_range(),
+                capture.node.start_position(),
+                capture.node.end_position(),
+                query_match.pattern_index,
+                configuration.get_highlight_type(capture.index),
+            ));
         }
+        spans.sort_by(|a, b| (a.0.start, b.0.end, a.3).cmp(&(b.0.start, a.0.end, b.3)));
+        spans.dedup_by(|span, previous| span.0 == previous.0);
 
-        if cursor.goto_next_sibling() {
-            self.get_highlighted_tokens(cursor, parent_kind);
+        for (_, start, end, _, highlight_type) in spans {
+            for row in start.row.max(start_row)..(end.row + 1).min(end_row) {
+                let row_types = &mut highlight_types[row - start_row];
+                let span_start = if row == start.row { start.column } else { 0 };
+                let span_end = if row == end.row {
+                    end.column
+                } else {
+                    row_types.len()
+                };
+                let span_end = span_end.min(row_types.len());
+                if span_start < span_end {
+                    row_types[span_start..span_end].fill(highlight_type);
+                }
+            }
         }
+
+        highlight_types
     }
 
     /// Highlights the entire text for the current language
     pub fn highlight_complete_text(&mut self) -> highlight::HighlightedText {
-        self.tokens = Some(vec![]);
-        self.create_syntax_tree();
-        let syntax_tree = self.syntax_tree.clone().unwrap();
-        self.get_highlighted_tokens(&mut syntax_tree.walk(), "root");
-        let mut highlighted_text = highlight::HighlightedText { text: vec![] };
+        let lines_length = self.get_lines_length();
+        self.highlight_rows(0, lines_length)
+    }
 
-        let mapping = highlight::PythonMapping::new();
+    /// Highlights the rows from start_row up to (but excluding) end_row
+    /// for the current language
+    pub fn highlight_rows(
+        &mut self,
+        start_row: usize,
+        end_row: usize,
+    ) -> highlight::HighlightedText {
+        self.create_syntax_tree();
 
-        let mut tokens_iter = self.tokens.as_ref().unwrap().iter();
-        let mut lines_iter = self.lines.iter();
+        let end_row = end_row.min(self.get_lines_length());
+        let start_row = start_row.min(end_row);
+        let highlight_types = self.get_highlight_types(start_row, end_row);
+        let mut highlighted_text = highlight::HighlightedText { text: vec![] };
 
-        let mut cursor = Cursor { row: 0, column: 0 };
-        let end_cursor = Cursor {
-            row: self.get_lines_length() - 1,
-            column: self.get_row_length(self.get_lines_length() - 1),
-        };
-        let mut highlighted_line = vec![];
-
-        let mut current_token = tokens_iter.next();
-        let mut current_line = lines_iter.next().unwrap();
-        while cursor.row <= end_cursor.row || cursor.column < end_cursor.column {
-            match current_token {
-                Some((current_range, kind)) => {
-                    if cursor.row <= current_range.start_point.row
-                        && cursor.column < current_range.start_point.column
-                    {
-                        // Need to add tokens before as none tokens
-                        let mut end_range = self.get_row_length(cursor.row);
-                        if cursor.row == current_range.start_point.row {
-                            end_range = current_range.start_point.column;
-                        }
-                        let token_slice = &current_line[cursor.column..end_range];
-                        highlighted_line
-                            .push((highlight::HighlightType::None, token_slice.to_string()));
-                        cursor.column = end_range;
-                        if cursor.row < current_range.start_point.row {
-                            cursor.row += 1;
-                            cursor.column = 0;
-                            highlighted_text.text.push(highlighted_line);
-                            highlighted_line = vec![];
-                            current_line = lines_iter.next().unwrap();
-                        }
-                    } else {
-                        // Add current range
-                        let mut end_range = self.get_row_length(cursor.row);
-                        if cursor.row == current_range.end_point.row {
-                            end_range = current_range.end_point.column;
-                        }
-                        let token_slice = &current_line[cursor.column..end_range];
-                        highlighted_line
-                            .push((mapping.get_highlight_type(&kind), token_slice.to_string()));
-                        cursor.column = end_range;
-                        if cursor.row < current_range.end_point.row && cursor.row < end_cursor.row {
-                            cursor.row += 1;
-                            cursor.column = 0;
-                            highlighted_text.text.push(highlighted_line);
-                            highlighted_line = vec![];
-                            current_line = lines_iter.next().unwrap();
-                        } else if cursor.row == end_cursor.row {
-                            highlighted_text.text.push(highlighted_line);
-                            break;
-                        } else {
-                            // Go to next token
-                            current_token = tokens_iter.next();
-                        }
-                    }
-                }
-                None => {
-                    // Add remaining tokens
-                    let mut end_range = self.get_row_length(cursor.row);
-                    if cursor.row == end_cursor.row {
-                        end_range = end_cursor.column;
-                    }
-                    let token_slice = &current_line[cursor.column..end_range];
-                    highlighted_line
-                        .push((highlight::HighlightType::None, token_slice.to_string()));
-                    cursor.column = end_range;
-                    if cursor.row < end_cursor.row {
-                        cursor.row += 1;
-                        cursor.column = 0;
-                        highlighted_text.text.push(highlighted_line);
-                        highlighted_line = vec![];
-                        current_line = lines_iter.next().unwrap();
-                    } else if cursor.row == end_cursor.row {
-                        highlighted_text.text.push(highlighted_line);
-                        break;
-                    }
+        for (row, row_types) in (start_row..end_row).zip(highlight_types) {
+            let line = self.get_line(row);
+            let mut highlighted_line = vec![];
+            let mut token_start = 0;
+            for column in 1..=row_types.len() {
+                if column == row_types.len() || row_types[column] != row_types[token_start] {
+                    highlighted_line.push((
+                        row_types[token_start],
+                        line[token_start..column].to_string(),
+                    ));
+                    token_start = column;
                 }
             }
+            if highlighted_line.is_empty() {
+                highlighted_line.push((highlight::HighlightType::None, line));
+            }
+
+            highlighted_text.text.push(highlighted_line);
         }
 
         highlighted_text
     }
 
-    /// Returns the column length of the given row
+    /// Records that the rows from start_row up to (but excluding) old_end_row
+    /// are about to be replaced by an edit
+    fn record_line_change(&mut self, start_row: usize, old_end_row: usize) {
+        let lines_length = self.get_lines_length();
+        let unchanged_suffix = lines_length - old_end_row;
+        let pending = self.pending_changes.get_or_insert(PendingLineChanges {
+            start_row,
+            unchanged_suffix,
+            old_lines_length: lines_length,
+            invalidated_rows: vec![],
+        });
+
+        // Rows invalidated by a previous reparse are folded into the edited
+        // range since their positions are not tracked through edits
+        for (invalidated_start, invalidated_end) in pending.invalidated_rows.drain(..) {
+            pending.start_row = pending.start_row.min(invalidated_start);
+            pending.unchanged_suffix = pending.unchanged_suffix.min(lines_length - invalidated_end);
+        }
+        pending.start_row = pending.start_row.min(start_row);
+        pending.unchanged_suffix = pending.unchanged_suffix.min(unchanged_suffix);
+    }
+
+    /// Returns the rows changed since the last call and updates the syntax
+    /// tree so that rows with changed highlighting are included
+    pub fn take_line_changes(&mut self) -> highlight::LineChanges {
+        self.create_syntax_tree();
+
+        let lines_length = self.get_lines_length();
+        match self.pending_changes.take() {
+            Some(pending) => highlight::LineChanges {
+                start_row: pending.start_row,
+                removed_rows: pending.old_lines_length
+                    - pending.start_row
+                    - pending.unchanged_suffix,
+                inserted_rows: lines_length - pending.start_row - pending.unchanged_suffix,
+                invalidated_rows: pending.invalidated_rows,
+                lines_length,
+            },
+            None => highlight::LineChanges {
+                start_row: 0,
+                removed_rows: 0,
+                inserted_rows: 0,
+                invalidated_rows: vec![],
+                lines_length,
+            },
+        }
+    }
+
+    /// Returns the column length of the given row in UTF-16 code units
     pub fn get_row_length(&self, row: usize) -> usize {
-        self.lines[row].len()
+        let line = self.rope.line(row);
+        let length = line.len_utf16_cu();
+        let bytes = line.len_bytes();
+        if bytes > 0 && line.byte(bytes - 1) == b'\n' {
+            length - 1
+        } else {
+            length
+        }
     }
 
     /// Returns the number of lines in the buffer
     pub fn get_lines_length(&self) -> usize {
-        self.lines.len()
+        self.rope.len_lines()
+    }
+
+    /// Returns whether the cursor points at a character boundary within
+    /// the buffer
+    pub fn is_valid_cursor(&self, cursor: &Cursor) -> bool {
+        if cursor.row >= self.get_lines_length() || cursor.column > self.get_row_length(cursor.row)
+        {
+            return false;
+        }
+        // Columns inside a surrogate pair are rounded down when converted
+        self.char_idx_to_cursor(self.cursor_to_char_idx(cursor)) == *cursor
     }
 
+    /// Returns whether both ends of the selection are valid cursors and
+    /// the start does not come after the end
+    pub fn is_valid_selection(&self, selection: &Selection) -> bool {
+        self.is_valid_cursor(&selection.start)
+            && self.is_valid_cursor(&selection.end)
+            && (selection.start.row, selection.start.column)
+                <= (selection.end.row, selection.end.column)
+    }
+
+    /// Selects the identifier under (or right before) the cursor
     pub fn select_token_under_cursor(&self, cursor: Cursor) -> Option<Selection> {
-        let tokens = self.tokens.as_ref().unwrap();
-        let mut start = None;
-        let mut end = None;
-        let mut is_identifier = false;
-        for (range, kind) in tokens.iter() {
-            if cursor.row >= range.start_point.row
-                && cursor.row <= range.end_point.row
-                && cursor.column >= range.start_point.column
-                && cursor.column <= range.end_point.column
-            {
-                start = Some(Cursor {
-                    row: range.start_point.row as usize,
-                    column: range.start_point.column as usize,
-                });
-                end = Some(Cursor {
-                    row: range.end_point.row as usize,
-                    column: range.end_point.column as usize,
-                });
-                if kind.ends_with(".identifier") {
-                    is_identifier = true;
-                }
-                break;
-            }
+        let root_node = self.syntax_tree.as_ref()?.root_node();
+        let char_idx = self.cursor_to_char_idx(&cursor);
+        let mut points = vec![self.byte_idx_to_point(self.rope.char_to_byte(char_idx))];
+        if cursor.column > 0 {
+            points.push(self.byte_idx_to_point(self.rope.char_to_byte(char_idx - 1)));
         }
-        if start.is_some() && end.is_some() {
-            if is_identifier {
-                return Some(Selection {
-                    start: start.unwrap(),
-                    end: end.unwrap(),
-                });
+        for point in points {
+            if let Some(node) = root_node.descendant_for_point_range(point, point) {
+                if node.child_count() == 0 && node.kind().ends_with("identifier") {
+                    return Some(Selection {
+                        start: self.point_to_cursor(node.start_position()),
+                        end: self.point_to_cursor(node.end_position()),
+                    });
+                }
             }
         }
         None
@@ -306,38 +610,41 @@ impl LineTextBuffer {
 
     /// Insert text at cursor position and returns the updated cursor position
     pub fn insert_text_no_log(&mut self, text: &String, cursor: &Cursor) -> Cursor {
-        let mut updated_cursor = cursor.clone();
-        let current_line = self.lines[cursor.row].clone();
-        let mut text_iter = text.split('\n');
-        let (s1, s2) = current_line.split_at(cursor.column);
-        let mut s1 = s1.to_string();
-        let e = text_iter.next().unwrap();
-        updated_cursor.column += e.len();
-        s1.push_str(e);
-        self.lines[cursor.row] = s1;
-        for i in text_iter {
-            updated_cursor.row += 1;
-            updated_cursor.column = i.len();
-            self.lines.insert(updated_cursor.row, i.to_owned());
-        }
-        let mut current_line = self.lines[updated_cursor.row].clone();
-        current_line.push_str(s2);
-        self.lines[updated_cursor.row] = current_line;
+        self.record_line_change(cursor.row, cursor.row + 1);
+        let char_idx = self.cursor_to_char_idx(cursor);
+        let start_byte = self.rope.char_to_byte(char_idx);
+        let start_position = self.byte_idx_to_point(start_byte);
+        if !self.line_breaks.is_empty() {
+            // Inserted lines end with the line ending of the buffer
+            let row = self.rope.char_to_line(char_idx);
+            let line_break = self.line_endings.line_ending;
+            let count = text.matches('\n').count();
+            self.line_breaks
+                .splice(row..row, std::iter::repeat(line_break).take(count));
+        }
+        self.rope.insert(char_idx, text);
+        let new_end_byte = start_byte + text.len();
+        self.edit_syntax_tree(tree_sitter::InputEdit {
+            start_byte,
+            old_end_byte: start_byte,
+            new_end_byte,
+            start_position,
+            old_end_position: start_position,
+            new_end_position: self.byte_idx_to_point(new_end_byte),
+        });
 
-        updated_cursor
+        self.char_idx_to_cursor(char_idx + text.chars().count())
     }
 
     /// Insert text and log it to updates
     pub fn insert_text(&mut self, text: String, cursor: Cursor) -> Cursor {
+        let text = line_ending::normalize_line_endings(text);
         let updated_cursor = self.insert_text_no_log(&text, &cursor);
-
-        self.updates.truncate(self.update_idx);
-        self.updates.push_back(Update::InsertUpdate {
+        self.push_update(Update::InsertUpdate {
             start: cursor,
             end: updated_cursor.clone(),
             text,
         });
-        self.update_idx = self.updates.len();
 
         updated_cursor
     }
@@ -345,100 +652,193 @@ impl LineTextBuffer {
     /// Remove the selected text and returns the updated cursor position
     /// and the deleted text
     pub fn remove_text_no_log(&mut self, selection: &Selection) -> (String, Cursor) {
-        if selection.start.row == selection.end.row {
-            let current_line = self.lines[selection.start.row].clone();
-            let (first, second) = current_line.split_at(selection.end.column);
-            let (first, middle) = first.split_at(selection.start.column);
-            self.lines[selection.start.row] = first.to_owned() + second;
-
-            (middle.to_owned(), selection.start.clone())
-        } else {
-            let mut buf = String::new();
-
-            let current_line = self.lines[selection.end.row].clone();
-            let (first, second) = current_line.split_at(selection.end.column);
-            buf.insert_str(0, first);
-            self.lines.remove(selection.end.row);
-
-            for i in (selection.start.row + 1..selection.end.row).rev() {
-                let current_line = self.lines.remove(i);
-                buf.insert(0, '\n');
-                buf.insert_str(0, &current_line);
-            }
-
-            let current_line = self.lines[selection.start.row].clone();
-            let (first, middle) = current_line.split_at(selection.start.column);
-            buf.insert(0, '\n');
-            buf.insert_str(0, middle);
-            self.lines[selection.start.row] = first.to_owned() + second;
-
-            (buf, selection.start.clone())
+        self.record_line_change(selection.start.row, selection.end.row + 1);
+        let start = self.cursor_to_char_idx(&selection.start);
+        let end = self.cursor_to_char_idx(&selection.end).max(start);
+        let start_byte = self.rope.char_to_byte(start);
+        let end_byte = self.rope.char_to_byte(end);
+        let start_position = self.byte_idx_to_point(start_byte);
+        let old_end_position = self.byte_idx_to_point(end_byte);
+        let buf = self.rope.slice(start..end).to_string();
+        if !self.line_breaks.is_empty() {
+            let start_row = self.rope.char_to_line(start);
+            let end_row = self.rope.char_to_line(end);
+            self.line_breaks.drain(start_row..end_row);
         }
+        self.rope.remove(start..end);
+        self.edit_syntax_tree(tree_sitter::InputEdit {
+            start_byte,
+            old_end_byte: end_byte,
+            new_end_byte: start_byte,
+            start_position,
+            old_end_position,
+            new_end_position: start_position,
+        });
+
+        (buf, self.char_idx_to_cursor(start))
     }
 
     /// Remove the selected text and log it to updates
     pub fn remove_text(&mut self, selection: Selection) -> (String, Cursor) {
         let (buf, _updated_cursor) = self.remove_text_no_log(&selection);
-        self.updates.truncate(self.update_idx);
-        self.updates.push_back(Update::RemoveUpdate {
+        self.push_update(Update::RemoveUpdate {
             selection: selection.clone(),
             text: buf.clone(),
         });
-        self.update_idx = self.updates.len();
 
         (buf, selection.start)
     }
 
-    /// Undo last change
-    pub fn undo(&mut self) -> Option<Cursor> {
-        if self.update_idx > 0 {
-            self.update_idx -= 1;
-            let update = self.updates.get(self.update_idx).unwrap();
-            match update {
-                Update::InsertUpdate {
-                    start,
-                    end,
-                    text: _,
-                } => {
-                    let (_removed_text, updated_cursor) = self.remove_text_no_log(&Selection {
-                        start: start.clone(),
-                        end: end.clone(),
-                    });
-                    return Some(updated_cursor);
-                }
-                Update::RemoveUpdate { selection, text } => {
-                    let text = text.clone();
-                    let selection = selection.clone();
-                    let updated_cursor = self.insert_text_no_log(&text, &selection.start);
-                    return Some(updated_cursor);
-                }
+    /// Logs an update, adding it to the open group if any. Typing and
+    /// deleting shortly after the previous edit is merged into it.
+    fn push_update(&mut self, update: Update) {
+        if self.group_depth > 0 {
+            self.group_updates.push(update);
+            return;
+        }
+
+        let now = Instant::now();
+        let recent = self
+            .last_update_time
+            .map_or(false, |time| now.duration_since(time) < COALESCE_TIMEOUT);
+        self.last_update_time = Some(now);
+        // Edits are not merged into the saved state so undoing returns to it
+        if recent
+            && self.saved_node != Some(self.history.current())
+            && self
+                .history
+                .merge_with_current(|previous| coalesce(previous, &update))
+        {
+            return;
+        }
+        self.history.push(update);
+    }
+
+    /// Starts a group of updates that are undone and redone together.
+    /// Groups can be nested, in which case only the outermost one is logged.
+    pub fn begin_group(&mut self) {
+        self.group_depth += 1;
+    }
+
+    /// Ends the current group, logging its updates as a single update once
+    /// the outermost group ends
+    pub fn end_group(&mut self) {
+        if self.group_depth == 0 {
+            return;
+        }
+        self.group_depth -= 1;
+        if self.group_depth > 0 {
+            return;
+        }
+
+        let mut updates = std::mem::take(&mut self.group_updates);
+        match updates.len() {
+            0 => {}
+            1 => self.history.push(updates.remove(0)),
+            _ => self.history.push(Update::GroupUpdate { updates }),
+        }
+        self.last_update_time = None;
+    }
+
+    /// Ends every open group
+    fn end_all_groups(&mut self) {
+        if self.group_depth > 0 {
+            self.group_depth = 1;
+            self.end_group();
+        }
+    }
+
+    /// Reverts an update and returns the updated cursor position
+    fn undo_update(&mut self, update: &Update) -> Option<Cursor> {
+        match update {
+            Update::InsertUpdate {
+                start,
+                end,
+                text: _,
+            } => {
+                let (_removed_text, updated_cursor) = self.remove_text_no_log(&Selection {
+                    start: start.clone(),
+                    end: end.clone(),
+                });
+                Some(updated_cursor)
+            }
+            Update::RemoveUpdate { selection, text } => {
+                Some(self.insert_text_no_log(text, &selection.start))
             }
+            Update::GroupUpdate { updates } => updates
+                .iter()
+                .rev()
+                .fold(None, |_, update| self.undo_update(update)),
         }
-        None
     }
 
-    /// Redo last change
-    pub fn redo(&mut self) -> Option<Cursor> {
-        if self.update_idx < self.updates.len() {
-            self.update_idx += 1;
-            let update = self.updates.get(self.update_idx - 1).unwrap();
-            match update {
-                Update::InsertUpdate {
-                    start,
-                    end: _,
-                    text,
-                } => {
-                    let updated_cursor = self.insert_text_no_log(&text.clone(), &start.clone());
-                    return Some(updated_cursor);
-                }
-                Update::RemoveUpdate { selection, text: _ } => {
-                    let (_removed_text, updated_cursor) =
-                        self.remove_text_no_log(&selection.clone());
-                    return Some(updated_cursor);
-                }
+    /// Applies an update again and returns the updated cursor position
+    fn redo_update(&mut self, update: &Update) -> Option<Cursor> {
+        match update {
+            Update::InsertUpdate {
+                start,
+                end: _,
+                text,
+            } => Some(self.insert_text_no_log(text, start)),
+            Update::RemoveUpdate { selection, text: _ } => {
+                let (_removed_text, updated_cursor) = self.remove_text_no_log(selection);
+                Some(updated_cursor)
             }
+            Update::GroupUpdate { updates } => updates
+                .iter()
+                .fold(None, |_, update| self.redo_update(update)),
         }
-        None
+    }
+
+    /// Undo last change
+    pub fn undo(&mut self) -> Option<Cursor> {
+        self.end_all_groups();
+        self.last_update_time = None;
+        let update = self.history.undo()?;
+        self.undo_update(&update)
+    }
+
+    /// Redo last change
+    pub fn redo(&mut self) -> Option<Cursor> {
+        self.end_all_groups();
+        self.last_update_time = None;
+        let update = self.history.redo()?;
+        self.redo_update(&update)
+    }
+
+    /// Moves to the given node of the undo tree, reverting and applying the
+    /// updates on the way. Returns None if the node does not exist or the
+    /// buffer is already there.
+    pub fn goto_history(&mut self, node: UndoNodeId) -> Option<Cursor> {
+        self.end_all_groups();
+        self.last_update_time = None;
+        let (undo_updates, redo_updates) = self.history.goto(node)?;
+        let cursor = undo_updates
+            .iter()
+            .fold(None, |_, update| self.undo_update(update));
+        redo_updates
+            .iter()
+            .fold(cursor, |_, update| self.redo_update(update))
+    }
+
+    /// Moves back to the state the buffer was in the given time earlier
+    pub fn earlier(&mut self, duration: Duration) -> Option<Cursor> {
+        self.end_all_groups();
+        self.goto_history(self.history.earlier(duration))
+    }
+
+    /// Moves forward to the state the buffer was in the given time later
+    pub fn later(&mut self, duration: Duration) -> Option<Cursor> {
+        self.end_all_groups();
+        self.goto_history(self.history.later(duration))
+    }
+
+    /// Switches to the next or previous branch sharing the parent of the
+    /// current state
+    pub fn switch_branch(&mut self, forward: bool) -> Option<Cursor> {
+        self.end_all_groups();
+        let sibling = self.history.sibling(forward)?;
+        self.goto_history(sibling)
     }
 
     /// Add indentation to the selected lines and returns the updated cursor position
@@ -447,69 +847,486 @@ impl LineTextBuffer {
         let tab = " ".repeat(tab_size);
         updated_selection.start.column += tab_size;
         updated_selection.end.column += tab_size;
+        self.begin_group();
         for i in selection.start.row..=selection.end.row {
-            let current_line = self.lines[i].clone();
-            let mut new_line = String::new();
-            new_line.push_str(&tab);
-            new_line.push_str(&current_line);
-            self.lines[i] = new_line;
+            self.insert_text(tab.clone(), Cursor { row: i, column: 0 });
         }
+        self.end_group();
         updated_selection
     }
 
     /// Remove indentation from the selected lines if present and returns the updated cursor position
     pub fn remove_indentation(&mut self, selection: Selection, tab_size: usize) -> Selection {
         let mut updated_selection = selection.clone();
-        let tab = " ".repeat(tab_size);
+        self.begin_group();
         for i in selection.start.row..=selection.end.row {
-            let current_line = self.lines[i].clone();
-            if current_line.starts_with(&tab) {
-                let (_first, second) = current_line.split_at(tab_size);
-                self.lines[i] = second.to_owned();
+            if self.get_indent_size(i) >= tab_size {
+                self.remove_text(Selection {
+                    start: Cursor { row: i, column: 0 },
+                    end: Cursor {
+                        row: i,
+                        column: tab_size,
+                    },
+                });
 
                 if i == selection.start.row {
-                    updated_selection.start.column -= tab_size;
+                    updated_selection.start.column =
+                        updated_selection.start.column.saturating_sub(tab_size);
                 }
                 if i == selection.end.row {
-                    updated_selection.end.column -= tab_size;
+                    updated_selection.end.column =
+                        updated_selection.end.column.saturating_sub(tab_size);
                 }
             }
         }
+        self.end_group();
         updated_selection
     }
 
     /// Get indent size of the given row
     pub fn get_indent_size(&self, row: usize) -> usize {
-        let current_line = self.lines[row].clone();
-        let indent_size = current_line.chars().take_while(|c| *c == ' ').count();
+        let indent_size = self
+            .rope
+            .line(row)
+            .chars()
+            .take_while(|c| *c == ' ')
+            .count();
         indent_size
     }
 
     /// Get text at selection
     pub fn get_selected_text(&self, selection: Selection) -> String {
-        if selection.start.row == selection.end.row {
-            let current_line = self.lines[selection.start.row].clone();
-            let (first, _second) = current_line.split_at(selection.end.column);
-            let (_first, middle) = first.split_at(selection.start.column);
-            middle.to_owned()
-        } else {
-            let mut buf = String::new();
+        let start = self.cursor_to_char_idx(&selection.start);
+        let end = self.cursor_to_char_idx(&selection.end).max(start);
+        self.rope.slice(start..end).to_string()
+    }
+
+    /// Converts a byte range of the buffer content into a selection
+    fn byte_range_to_selection(&self, range: &Range<usize>) -> Selection {
+        Selection {
+            start: self.char_idx_to_cursor(self.rope.byte_to_char(range.start)),
+            end: self.char_idx_to_cursor(self.rope.byte_to_char(range.end)),
+        }
+    }
+
+    /// Returns the byte ranges of the matches of the regex in the buffer
+    /// and the text replacing each of them
+    fn find_replacements(
+        &self,
+        regex: &Regex,
+        options: &SearchOptions,
+        replacement: &str,
+    ) -> Vec<(Range<usize>, String)> {
+        let content = self.rope.to_string();
+        query::find_replacements(regex, options, &content, replacement)
+            .into_iter()
+            .map(|(range, text)| (range, line_ending::normalize_line_endings(text)))
+            .collect()
+    }
 
-            let current_line = self.lines[selection.end.row].clone();
-            let (first, _second) = current_line.split_at(selection.end.column);
-            buf.insert_str(0, first);
+    /// Replaces sorted, non overlapping byte ranges as a single undo step
+    /// and returns the selection of the first replacement
+    fn replace_ranges(&mut self, replacements: Vec<(Range<usize>, String)>) -> Option<Selection> {
+        let mut first_replacement = None;
+        self.begin_group();
+        // Ranges are replaced from the end so earlier ranges stay valid
+        for (range, text) in replacements.into_iter().rev() {
+            let (_removed_text, start) = self.remove_text(self.byte_range_to_selection(&range));
+            let end = if text.is_empty() {
+                start.clone()
+            } else {
+                self.insert_text(text, start.clone())
+            };
+            first_replacement = Some(Selection { start, end });
+        }
+        self.end_group();
+        first_replacement
+    }
+
+    /// Returns the selections of every non empty match of the query
+    pub fn find_all(
+        &self,
+        query: &str,
+        options: &SearchOptions,
+    ) -> Result<Vec<Selection>, PapyrusError> {
+        let regex = options.to_regex(query)?;
+        let content = self.rope.to_string();
+        Ok(regex
+            .find_iter(&content)
+            .filter(|found| found.start() != found.end())
+            .map(|found| self.byte_range_to_selection(&found.range()))
+            .collect())
+    }
 
-            for i in (selection.start.row + 1..selection.end.row).rev() {
-                let current_line = self.lines[i].clone();
-                buf.insert(0, '\n');
-                buf.insert_str(0, &current_line);
+    /// Returns the first match starting at or after the cursor, wrapping
+    /// around to the start of the buffer
+    pub fn find_next(
+        &self,
+        query: &str,
+        options: &SearchOptions,
+        cursor: &Cursor,
+    ) -> Result<Option<Selection>, PapyrusError> {
+        let matches = self.find_all(query, options)?;
+        Ok(matches
+            .iter()
+            .find(|selection| selection.start >= *cursor)
+            .or_else(|| matches.first())
+            .cloned())
+    }
+
+    /// Returns the last match starting before the cursor, wrapping around
+    /// to the end of the buffer
+    pub fn find_prev(
+        &self,
+        query: &str,
+        options: &SearchOptions,
+        cursor: &Cursor,
+    ) -> Result<Option<Selection>, PapyrusError> {
+        let matches = self.find_all(query, options)?;
+        Ok(matches
+            .iter()
+            .rev()
+            .find(|selection| selection.start < *cursor)
+            .or_else(|| matches.last())
+            .cloned())
+    }
+
+    /// Replaces the next match at or after the cursor and returns the
+    /// selection of the replacement
+    pub fn replace(
+        &mut self,
+        query: &str,
+        options: &SearchOptions,
+        replacement: &str,
+        cursor: &Cursor,
+    ) -> Result<Option<Selection>, PapyrusError> {
+        let regex = options.to_regex(query)?;
+        let cursor_byte = self.rope.char_to_byte(self.cursor_to_char_idx(cursor));
+        let mut replacements = self.find_replacements(&regex, options, replacement);
+        let next_idx = replacements
+            .iter()
+            .position(|(range, _)| range.start >= cursor_byte)
+            .unwrap_or(0);
+        if replacements.is_empty() {
+            return Ok(None);
+        }
+        Ok(self.replace_ranges(vec![replacements.swap_remove(next_idx)]))
+    }
+
+    /// Replaces every match as a single undo step and returns the number
+    /// of replacements
+    pub fn replace_all(
+        &mut self,
+        query: &str,
+        options: &SearchOptions,
+        replacement: &str,
+    ) -> Result<usize, PapyrusError> {
+        let regex = options.to_regex(query)?;
+        let replacements = self.find_replacements(&regex, options, replacement);
+        let count = replacements.len();
+        self.replace_ranges(replacements);
+        Ok(count)
+    }
+
+    /// Converts selections into sorted char ranges, merging the ones that
+    /// overlap
+    fn selections_to_char_ranges(&self, selections: &[Selection]) -> Vec<Range<usize>> {
+        let mut ranges: Vec<Range<usize>> = selections
+            .iter()
+            .map(|selection| {
+                self.cursor_to_char_idx(&selection.start)..self.cursor_to_char_idx(&selection.end)
+            })
+            .collect();
+        ranges.sort_by_key(|range| range.start);
+
+        let mut merged: Vec<Range<usize>> = vec![];
+        for range in ranges {
+            match merged.last_mut() {
+                Some(last) if range.start < last.end || range == *last => {
+                    last.end = last.end.max(range.end);
+                }
+                _ => merged.push(range),
             }
+        }
+        merged
+    }
 
-            let current_line = self.lines[selection.start.row].clone();
-            let (_first, middle) = current_line.split_at(selection.start.column);
-            buf.insert(0, '\n');
-            buf.insert_str(0, middle);
-            buf
+    /// Replaces sorted, non overlapping char ranges as a single undo step
+    /// and returns the selections of the replacements in the updated buffer
+    fn replace_char_ranges(&mut self, replacements: Vec<(Range<usize>, String)>) -> Vec<Selection> {
+        self.begin_group();
+        // Ranges are replaced from the end so earlier ranges stay valid
+        for (range, text) in replacements.iter().rev() {
+            let start = self.char_idx_to_cursor(range.start);
+            if range.start < range.end {
+                self.remove_text(Selection {
+                    start: start.clone(),
+                    end: self.char_idx_to_cursor(range.end),
+                });
+            }
+            if !text.is_empty() {
+                self.insert_text(text.clone(), start);
+            }
+        }
+        self.end_group();
+
+        // Each replacement moves the ones after it by its change in length
+        let mut shift = 0isize;
+        replacements
+            .iter()
+            .map(|(range, text)| {
+                let start = (range.start as isize + shift) as usize;
+                let length = text.chars().count();
+                shift += length as isize - range.len() as isize;
+                Selection {
+                    start: self.char_idx_to_cursor(start),
+                    end: self.char_idx_to_cursor(start + length),
+                }
+            })
+            .collect()
+    }
+
+    /// Replaces every selection with the text as a single undo step and
+    /// returns the cursors after each inserted text
+    pub fn insert_text_at_selections(
+        &mut self,
+        text: String,
+        selections: &[Selection],
+    ) -> Vec<Selection> {
+        let text = line_ending::normalize_line_endings(text);
+        let replacements = self
+            .selections_to_char_ranges(selections)
+            .into_iter()
+            .map(|range| (range, text.clone()))
+            .collect();
+        self.replace_char_ranges(replacements)
+            .into_iter()
+            .map(|selection| Selection {
+                start: selection.end.clone(),
+                end: selection.end,
+            })
+            .collect()
+    }
+
+    /// Removes the text of every selection as a single undo step. Empty
+    /// selections remove the character before them, or after them when
+    /// `forward` is set. Returns the cursors where the text was removed.
+    pub fn remove_text_at_selections(
+        &mut self,
+        selections: &[Selection],
+        forward: bool,
+    ) -> Vec<Selection> {
+        let len_chars = self.rope.len_chars();
+        let expanded: Vec<Selection> = selections
+            .iter()
+            .map(|selection| {
+                if selection.start != selection.end {
+                    return selection.clone();
+                }
+                let char_idx = self.cursor_to_char_idx(&selection.start);
+                let range = if forward {
+                    char_idx..(char_idx + 1).min(len_chars)
+                } else {
+                    char_idx.saturating_sub(1)..char_idx
+                };
+                Selection {
+                    start: self.char_idx_to_cursor(range.start),
+                    end: self.char_idx_to_cursor(range.end),
+                }
+            })
+            .collect();
+        let replacements = self
+            .selections_to_char_ranges(&expanded)
+            .into_iter()
+            .filter(|range| !range.is_empty())
+            .map(|range| (range, String::new()))
+            .collect();
+        let mut cursors = self.replace_char_ranges(replacements);
+        cursors.dedup();
+        if cursors.is_empty() {
+            // Nothing was removed, the cursors stay where they were
+            let mut cursors: Vec<Selection> = selections.to_vec();
+            cursors.dedup();
+            return cursors;
+        }
+        cursors
+    }
+
+    /// Selects the word under or right before the cursor, using the syntax
+    /// tree when there is one
+    fn select_word_under_cursor(&self, cursor: &Cursor) -> Option<Selection> {
+        if let Some(selection) = self.select_token_under_cursor(cursor.clone()) {
+            return Some(selection);
+        }
+
+        let is_word_char = |c: char| c.is_alphanumeric() || c == '_';
+        let char_idx = self.cursor_to_char_idx(cursor);
+        let line_start = self.rope.line_to_char(cursor.row);
+        let line_end = line_start + self.rope.line(cursor.row).len_chars();
+        let mut start = char_idx;
+        while start > line_start && is_word_char(self.rope.char(start - 1)) {
+            start -= 1;
+        }
+        let mut end = char_idx;
+        while end < line_end && is_word_char(self.rope.char(end)) {
+            end += 1;
+        }
+        if start == end {
+            return None;
         }
+        Some(Selection {
+            start: self.char_idx_to_cursor(start),
+            end: self.char_idx_to_cursor(end),
+        })
+    }
+
+    /// Adds the next occurrence of the text selected by the last selection,
+    /// wrapping around the buffer. An empty last selection is expanded to
+    /// the word under the cursor instead.
+    pub fn add_next_occurrence(&self, selections: &[Selection]) -> Vec<Selection> {
+        let mut selections = selections.to_vec();
+        let last = match selections.pop() {
+            Some(last) => last,
+            None => return selections,
+        };
+        if last.start == last.end {
+            selections.push(self.select_word_under_cursor(&last.start).unwrap_or(last));
+            return selections;
+        }
+
+        let text = self.get_selected_text(last.clone());
+        let content = self.rope.to_string();
+        let after = self.rope.char_to_byte(self.cursor_to_char_idx(&last.end));
+        let occurrences = content[after..]
+            .match_indices(&text)
+            .map(|(idx, _)| after + idx)
+            .chain(content[..after].match_indices(&text).map(|(idx, _)| idx));
+        selections.push(last);
+        for byte_idx in occurrences {
+            let occurrence = self.byte_range_to_selection(&(byte_idx..byte_idx + text.len()));
+            if !selections.contains(&occurrence) {
+                selections.push(occurrence);
+                break;
+            }
+        }
+        selections
+    }
+
+    /// Returns a selection on each row between the anchor and the head,
+    /// spanning their columns. Columns past the end of a row are clamped.
+    pub fn box_selection(&self, anchor: &Cursor, head: &Cursor) -> Vec<Selection> {
+        let rows = anchor.row.min(head.row)..=anchor.row.max(head.row);
+        let start_column = anchor.column.min(head.column);
+        let end_column = anchor.column.max(head.column);
+        rows.map(|row| {
+            let row_length = self.get_row_length(row);
+            let cursor = |column: usize| {
+                let cursor = Cursor {
+                    row,
+                    column: column.min(row_length),
+                };
+                // Round columns inside a surrogate pair down
+                self.char_idx_to_cursor(self.cursor_to_char_idx(&cursor))
+            };
+            Selection {
+                start: cursor(start_column),
+                end: cursor(end_column),
+            }
+        })
+        .collect()
+    }
+
+    /// Splits selections spanning several rows into a selection per row.
+    /// The last row is skipped when the selection ends at its start.
+    pub fn split_selection_into_lines(&self, selections: &[Selection]) -> Vec<Selection> {
+        let mut lines = vec![];
+        for selection in selections {
+            if selection.start.row == selection.end.row {
+                lines.push(selection.clone());
+                continue;
+            }
+            for row in selection.start.row..=selection.end.row {
+                let start = if row == selection.start.row {
+                    selection.start.column
+                } else {
+                    0
+                };
+                let end = if row == selection.end.row {
+                    if selection.end.column == 0 {
+                        break;
+                    }
+                    selection.end.column
+                } else {
+                    self.get_row_length(row)
+                };
+                lines.push(Selection {
+                    start: Cursor { row, column: start },
+                    end: Cursor { row, column: end },
+                });
+            }
+        }
+        lines
+    }
+}
+
+/// Merges an update into the previous one when both type or delete text on
+/// the same row without starting a new word. Returns whether it was merged.
+fn coalesce(previous: &mut Update, update: &Update) -> bool {
+    match (previous, update) {
+        (
+            Update::InsertUpdate { end, text, .. },
+            Update::InsertUpdate {
+                start: new_start,
+                end: new_end,
+                text: new_text,
+            },
+        ) => {
+            if end != new_start
+                || text.contains('\n')
+                || new_text.contains('\n')
+                || starts_new_word(text, new_text)
+            {
+                return false;
+            }
+            text.push_str(new_text);
+            *end = new_end.clone();
+            true
+        }
+        (
+            Update::RemoveUpdate { selection, text },
+            Update::RemoveUpdate {
+                selection: new_selection,
+                text: new_text,
+            },
+        ) => {
+            if text.contains('\n')
+                || new_text.contains('\n')
+                || new_selection.start.row != selection.start.row
+            {
+                return false;
+            }
+            if new_selection.end == selection.start && !starts_new_word(new_text, text) {
+                // Deleting backwards
+                text.insert_str(0, new_text);
+                selection.start = new_selection.start.clone();
+                true
+            } else if new_selection.start == selection.start && !starts_new_word(text, new_text) {
+                // Deleting forwards
+                text.push_str(new_text);
+                selection.end.column += new_selection.end.column - new_selection.start.column;
+                true
+            } else {
+                false
+            }
+        }
+        _ => false,
+    }
+}
+
+/// Returns whether the text starts a new word after the previous text
+fn starts_new_word(previous: &str, text: &str) -> bool {
+    match (previous.chars().last(), text.chars().next()) {
+        (Some(last), Some(first)) => last.is_whitespace() && !first.is_whitespace(),
+        _ => false,
     }
 }
@@ -0,0 +1,329 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use crate::editor::highlight::{HighlightType, HIGHLIGHT_SCOPES};
+
+/// Theme used when no theme file has been loaded
+const DEFAULT_THEME: &str = include_str!("../../themes/one-dark.toml");
+
+/// TextMate scopes matched against the selectors of a .tmTheme file for
+/// each highlight type, in order of preference
+const TEXTMATE_SCOPES: &[(HighlightType, &[&str])] = &[
+    (HighlightType::Attribute, &["entity.other.attribute-name"]),
+    (HighlightType::Boolean, &["constant.language.boolean"]),
+    (HighlightType::Comment, &["comment"]),
+    (
+        HighlightType::Constant,
+        &["constant", "variable.other.constant"],
+    ),
+    (HighlightType::ConstantBuiltin, &["constant.language"]),
+    (
+        HighlightType::Constructor,
+        &["entity.name.function.constructor", "entity.name.class"],
+    ),
+    (HighlightType::Embedded, &["meta.embedded"]),
+    (HighlightType::Escape, &["constant.character.escape"]),
+    (HighlightType::Function, &["entity.name.function"]),
+    (HighlightType::FunctionBuiltin, &["support.function"]),
+    (
+        HighlightType::FunctionMacro,
+        &["entity.name.function.macro", "entity.name.function"],
+    ),
+    (
+        HighlightType::FunctionMethod,
+        &["entity.name.function.method", "entity.name.function"],
+    ),
+    (HighlightType::Keyword, &["keyword", "storage"]),
+    (HighlightType::Label, &["entity.name.label"]),
+    (HighlightType::Number, &["constant.numeric"]),
+    (HighlightType::Operator, &["keyword.operator"]),
+    (
+        HighlightType::Property,
+        &["variable.other.property", "support.type.property-name"],
+    ),
+    (HighlightType::Punctuation, &["punctuation"]),
+    (
+        HighlightType::PunctuationBracket,
+        &["punctuation.section", "punctuation"],
+    ),
+    (
+        HighlightType::PunctuationDelimiter,
+        &["punctuation.separator", "punctuation"],
+    ),
+    (
+        HighlightType::PunctuationSpecial,
+        &["punctuation.definition", "punctuation"],
+    ),
+    (HighlightType::String, &["string"]),
+    (HighlightType::StringEscape, &["constant.character.escape"]),
+    (HighlightType::StringSpecial, &["string.regexp", "string"]),
+    (HighlightType::Tag, &["entity.name.tag"]),
+    (HighlightType::TextEmphasis, &["markup.italic"]),
+    (
+        HighlightType::TextLiteral,
+        &["markup.raw", "markup.inline.raw"],
+    ),
+    (HighlightType::TextReference, &["markup.underline.link"]),
+    (HighlightType::TextStrong, &["markup.bold"]),
+    (
+        HighlightType::TextTitle,
+        &["markup.heading", "entity.name.section"],
+    ),
+    (HighlightType::TextUri, &["markup.underline.link"]),
+    (HighlightType::Type, &["entity.name.type", "storage.type"]),
+    (
+        HighlightType::TypeBuiltin,
+        &["support.type", "storage.type"],
+    ),
+    (HighlightType::Variable, &["variable"]),
+    (HighlightType::VariableBuiltin, &["variable.language"]),
+    (HighlightType::VariableParameter, &["variable.parameter"]),
+];
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+/// Style of a highlight scope, colours are CSS colour strings
+pub struct Style {
+    pub foreground: Option<String>,
+    pub background: Option<String>,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+/// Style of a scope in a theme file, either a full style or just the
+/// foreground colour
+enum ScopeStyle {
+    Foreground(String),
+    Style(Style),
+}
+
+#[derive(serde::Deserialize)]
+/// Layout of TOML and JSON theme files
+struct ThemeFile {
+    name: String,
+    foreground: Option<String>,
+    background: Option<String>,
+    #[serde(default)]
+    scopes: HashMap<String, ScopeStyle>,
+}
+
+#[derive(serde::Deserialize)]
+/// Layout of TextMate .tmTheme files
+struct TmTheme {
+    name: Option<String>,
+    settings: Vec<TmThemeRule>,
+}
+
+#[derive(serde::Deserialize)]
+struct TmThemeRule {
+    scope: Option<String>,
+    settings: TmThemeSettings,
+}
+
+#[derive(serde::Deserialize)]
+struct TmThemeSettings {
+    foreground: Option<String>,
+    background: Option<String>,
+    #[serde(rename = "fontStyle")]
+    font_style: Option<String>,
+}
+
+impl TmThemeSettings {
+    fn to_style(&self) -> Style {
+        let font_style = self.font_style.clone().unwrap_or_default();
+        Style {
+            foreground: self.foreground.clone(),
+            background: self.background.clone(),
+            bold: font_style.contains("bold"),
+            italic: font_style.contains("italic"),
+            underline: font_style.contains("underline"),
+        }
+    }
+}
+
+/// Returns the length of the selector if it matches the TextMate scope,
+/// that is if it is equal to the scope or one of its dotted prefixes
+fn match_selector(selector: &str, scope: &str) -> Option<usize> {
+    if scope == selector
+        || (scope.starts_with(selector) && scope[selector.len()..].starts_with('.'))
+    {
+        Some(selector.len())
+    } else {
+        None
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Colour theme mapping highlight scopes to styles
+pub struct Theme {
+    pub name: String,
+    pub foreground: Option<String>,
+    pub background: Option<String>,
+    pub scopes: HashMap<HighlightType, Style>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+/// Theme with a resolved style for every highlight scope
+pub struct ResolvedTheme {
+    pub name: String,
+    pub foreground: Option<String>,
+    pub background: Option<String>,
+    pub styles: HashMap<HighlightType, Style>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::from_toml(DEFAULT_THEME).expect("Invalid default theme")
+    }
+}
+
+impl Theme {
+    fn from_theme_file(theme_file: ThemeFile) -> Self {
+        let mut scopes = HashMap::new();
+        for (scope, style) in theme_file.scopes {
+            // Unknown scopes are ignored instead of falling back to their
+            // parent so they do not override its style
+            if let Some((highlight_type, _)) = HIGHLIGHT_SCOPES
+                .iter()
+                .find(|(_, highlight_scope)| *highlight_scope == scope)
+            {
+                let style = match style {
+                    ScopeStyle::Foreground(foreground) => Style {
+                        foreground: Some(foreground),
+                        ..Default::default()
+                    },
+                    ScopeStyle::Style(style) => style,
+                };
+                scopes.insert(*highlight_type, style);
+            }
+        }
+
+        Self {
+            name: theme_file.name,
+            foreground: theme_file.foreground,
+            background: theme_file.background,
+            scopes,
+        }
+    }
+
+    /// Parses a theme from the content of a TOML theme file
+    pub fn from_toml(content: &str) -> Result<Self, Box<dyn Error>> {
+        Ok(Self::from_theme_file(toml::from_str(content)?))
+    }
+
+    /// Parses a theme from the content of a JSON theme file
+    pub fn from_json(content: &str) -> Result<Self, Box<dyn Error>> {
+        Ok(Self::from_theme_file(serde_json::from_str(content)?))
+    }
+
+    /// Parses a theme from the content of a TextMate .tmTheme file.
+    /// Each highlight scope uses the rule with the longest selector
+    /// matching one of its TextMate scopes, later rules winning ties.
+    /// Descendant and exclusion selectors are not supported and skipped.
+    pub fn from_tm_theme(content: &str) -> Result<Self, Box<dyn Error>> {
+        let tm_theme: TmTheme = plist::from_bytes(content.as_bytes())?;
+
+        let mut foreground = None;
+        let mut background = None;
+        let mut rules = vec![];
+        for rule in &tm_theme.settings {
+            match &rule.scope {
+                None => {
+                    foreground = rule.settings.foreground.clone();
+                    background = rule.settings.background.clone();
+                }
+                Some(scope) => {
+                    for selector in scope.split(',') {
+                        let selector = selector.trim();
+                        if !selector.is_empty() && !selector.contains(' ') {
+                            rules.push((selector, &rule.settings));
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut scopes = HashMap::new();
+        for (highlight_type, textmate_scopes) in TEXTMATE_SCOPES {
+            for textmate_scope in textmate_scopes.iter() {
+                let mut best_match: Option<(usize, &TmThemeSettings)> = None;
+                for (selector, settings) in &rules {
+                    if let Some(length) = match_selector(selector, textmate_scope) {
+                        if !matches!(best_match, Some((best_length, _)) if length < best_length) {
+                            best_match = Some((length, settings));
+                        }
+                    }
+                }
+                if let Some((_, settings)) = best_match {
+                    scopes.insert(*highlight_type, settings.to_style());
+                    break;
+                }
+            }
+        }
+
+        Ok(Self {
+            name: tm_theme.name.unwrap_or_else(|| "Untitled".into()),
+            foreground,
+            background,
+            scopes,
+        })
+    }
+
+    /// Loads a theme file, the format is chosen from the file extension
+    /// (.toml, .json or .tmTheme)
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        let content = fs::read_to_string(path)?;
+        let extension = Path::new(path)
+            .extension()
+            .unwrap_or_default()
+            .to_str()
+            .unwrap_or_default()
+            .to_lowercase();
+
+        match extension.as_str() {
+            "toml" => Self::from_toml(&content),
+            "json" => Self::from_json(&content),
+            "tmtheme" => Self::from_tm_theme(&content),
+            _ => Err(format!("Unsupported theme format: {}", path).into()),
+        }
+    }
+
+    /// Returns the style of a highlight scope, falling back to its parent
+    /// scopes and finally to the theme foreground
+    pub fn resolve(&self, highlight_type: HighlightType) -> Style {
+        let mut current = Some(highlight_type);
+        while let Some(highlight_type) = current {
+            if let Some(style) = self.scopes.get(&highlight_type) {
+                let mut style = style.clone();
+                if style.foreground.is_none() {
+                    style.foreground = self.foreground.clone();
+                }
+                return style;
+            }
+            current = highlight_type.parent();
+        }
+
+        Style {
+            foreground: self.foreground.clone(),
+            ..Default::default()
+        }
+    }
+
+    /// Resolves the style of every highlight scope
+    pub fn resolve_all(&self) -> ResolvedTheme {
+        ResolvedTheme {
+            name: self.name.clone(),
+            foreground: self.foreground.clone(),
+            background: self.background.clone(),
+            styles: HIGHLIGHT_SCOPES
+                .iter()
+                .map(|(highlight_type, _)| (*highlight_type, self.resolve(*highlight_type)))
+                .collect(),
+        }
+    }
+}
@@ -0,0 +1,258 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::editor::text_buffer::Update;
+
+/// Longest text shown in the description of a history node, in chars
+const MAX_DESCRIPTION_LENGTH: usize = 40;
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(transparent)]
+/// Identifier of a node of the undo tree. Nodes are numbered in the order
+/// they were created and the root, the state the buffer was loaded in, is 0.
+pub struct UndoNodeId(pub usize);
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+struct UndoNode {
+    /// Update leading to this node from its parent, None for the root
+    update: Option<Update>,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    /// Child followed when redoing, the one most recently left or created
+    redo_child: Option<usize>,
+    /// Milliseconds since the unix epoch of the last update of the node
+    timestamp: u64,
+}
+
+#[derive(serde::Serialize, Debug, Clone, PartialEq, Eq)]
+/// Node of the undo tree listed in the history panel
+pub struct HistoryNode {
+    pub id: UndoNodeId,
+    pub parent: Option<UndoNodeId>,
+    pub timestamp: u64,
+    pub is_current: bool,
+    pub description: String,
+}
+
+/// History of the updates of a buffer. Updates made after undoing start a
+/// new branch, so no state of the buffer is ever lost.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct UndoTree {
+    nodes: Vec<UndoNode>,
+    current: usize,
+}
+
+impl Default for UndoTree {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl UndoTree {
+    pub fn new() -> Self {
+        Self {
+            nodes: vec![UndoNode {
+                update: None,
+                parent: None,
+                children: vec![],
+                redo_child: None,
+                timestamp: now(),
+            }],
+            current: 0,
+        }
+    }
+
+    /// Number of updates in the tree, across every branch
+    pub fn len(&self) -> usize {
+        self.nodes.len() - 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Checks that the nodes link to each other, with parents and children
+    /// pointing back at each other, for trees that were read from disk
+    pub fn is_valid(&self) -> bool {
+        let len = self.nodes.len();
+        self.current < len
+            && self.nodes.iter().enumerate().all(|(id, node)| {
+                (id == 0) == node.parent.is_none()
+                    && node.parent.map_or(true, |parent| {
+                        parent < id && self.nodes[parent].children.contains(&id)
+                    })
+                    && node.children.iter().all(|child| {
+                        *child > id && *child < len && self.nodes[*child].parent == Some(id)
+                    })
+                    && node
+                        .redo_child
+                        .map_or(true, |child| node.children.contains(&child))
+            })
+    }
+
+    pub fn current(&self) -> UndoNodeId {
+        UndoNodeId(self.current)
+    }
+
+    /// Adds an update as a child of the current node and moves to it
+    pub fn push(&mut self, update: Update) {
+        let id = self.nodes.len();
+        self.nodes.push(UndoNode {
+            update: Some(update),
+            parent: Some(self.current),
+            children: vec![],
+            redo_child: None,
+            timestamp: now(),
+        });
+        let parent = &mut self.nodes[self.current];
+        parent.children.push(id);
+        parent.redo_child = Some(id);
+        self.current = id;
+    }
+
+    /// Calls `merge` with the update of the current node if it is the tip
+    /// of its branch. Returns whether the update was merged.
+    pub fn merge_with_current(&mut self, merge: impl FnOnce(&mut Update) -> bool) -> bool {
+        let node = &mut self.nodes[self.current];
+        if !node.children.is_empty() {
+            return false;
+        }
+        let merged = node.update.as_mut().map_or(false, merge);
+        if merged {
+            node.timestamp = now();
+        }
+        merged
+    }
+
+    /// Moves to the parent of the current node, returning the update to
+    /// revert
+    pub fn undo(&mut self) -> Option<Update> {
+        let node = &self.nodes[self.current];
+        let parent = node.parent?;
+        let update = node.update.clone();
+        self.nodes[parent].redo_child = Some(self.current);
+        self.current = parent;
+        update
+    }
+
+    /// Moves to the child last visited, returning the update to apply
+    pub fn redo(&mut self) -> Option<Update> {
+        let child = self.nodes[self.current].redo_child?;
+        self.current = child;
+        self.nodes[child].update.clone()
+    }
+
+    /// Moves to the given node, returning the updates to revert to reach the
+    /// common ancestor of both nodes, then the updates to apply from there
+    pub fn goto(&mut self, target: UndoNodeId) -> Option<(Vec<Update>, Vec<Update>)> {
+        let target = target.0;
+        if target >= self.nodes.len() {
+            return None;
+        }
+
+        let target_ancestors = self.ancestors(target);
+        let mut undo_updates = vec![];
+        while !target_ancestors.contains(&self.current) {
+            undo_updates.extend(self.undo());
+        }
+
+        let ancestor_idx = target_ancestors
+            .iter()
+            .position(|node| *node == self.current)?;
+        let mut redo_updates = vec![];
+        for &node in target_ancestors[..ancestor_idx].iter().rev() {
+            self.nodes[self.current].redo_child = Some(node);
+            redo_updates.extend(self.redo());
+        }
+        Some((undo_updates, redo_updates))
+    }
+
+    /// Returns the latest node whose last update was made at or before the
+    /// given time, or the root if there is none
+    pub fn node_at(&self, timestamp: u64) -> UndoNodeId {
+        let id = self
+            .nodes
+            .iter()
+            .rposition(|node| node.timestamp <= timestamp)
+            .unwrap_or(0);
+        UndoNodeId(id)
+    }
+
+    /// Returns the node the buffer was in the given time before the current
+    /// node was last updated
+    pub fn earlier(&self, duration: Duration) -> UndoNodeId {
+        let timestamp = self.nodes[self.current].timestamp;
+        self.node_at(timestamp.saturating_sub(duration.as_millis() as u64))
+    }
+
+    /// Returns the node the buffer was in the given time after the current
+    /// node was last updated
+    pub fn later(&self, duration: Duration) -> UndoNodeId {
+        let timestamp = self.nodes[self.current].timestamp;
+        self.node_at(timestamp.saturating_add(duration.as_millis() as u64))
+    }
+
+    /// Returns the next or previous sibling of the current node, wrapping
+    /// around, or None if the current node has no siblings
+    pub fn sibling(&self, forward: bool) -> Option<UndoNodeId> {
+        let siblings = &self.nodes[self.nodes[self.current].parent?].children;
+        if siblings.len() < 2 {
+            return None;
+        }
+        let idx = siblings.iter().position(|node| *node == self.current)?;
+        let sibling_idx = if forward {
+            (idx + 1) % siblings.len()
+        } else {
+            (idx + siblings.len() - 1) % siblings.len()
+        };
+        Some(UndoNodeId(siblings[sibling_idx]))
+    }
+
+    /// Lists every node in the order they were created
+    pub fn history(&self) -> Vec<HistoryNode> {
+        self.nodes
+            .iter()
+            .enumerate()
+            .map(|(id, node)| HistoryNode {
+                id: UndoNodeId(id),
+                parent: node.parent.map(UndoNodeId),
+                timestamp: node.timestamp,
+                is_current: id == self.current,
+                description: node
+                    .update
+                    .as_ref()
+                    .map_or_else(|| "Original".to_string(), describe),
+            })
+            .collect()
+    }
+
+    /// Returns the node followed by its ancestors up to the root
+    fn ancestors(&self, mut node: usize) -> Vec<usize> {
+        let mut ancestors = vec![node];
+        while let Some(parent) = self.nodes[node].parent {
+            ancestors.push(parent);
+            node = parent;
+        }
+        ancestors
+    }
+}
+
+/// Short description of an update for the history panel
+fn describe(update: &Update) -> String {
+    let (action, text) = match update {
+        Update::InsertUpdate { text, .. } => ("Insert", text),
+        Update::RemoveUpdate { text, .. } => ("Delete", text),
+        Update::GroupUpdate { updates } => return format!("Edit ({} changes)", updates.len()),
+    };
+    let mut shown: String = text.chars().take(MAX_DESCRIPTION_LENGTH).collect();
+    if shown.len() < text.len() {
+        shown.push('…');
+    }
+    format!("{} {:?}", action, shown)
+}
+
+/// Milliseconds since the unix epoch
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_millis() as u64)
+}
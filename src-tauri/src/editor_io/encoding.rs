@@ -0,0 +1,184 @@
+use std::error::Error;
+
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+
+use crate::error::PapyrusError;
+
+/// Encodings offered when reopening or saving a file with a chosen encoding
+pub const COMMON_ENCODINGS: &[&str] = &[
+    "UTF-8",
+    "UTF-16LE",
+    "UTF-16BE",
+    "windows-1252",
+    "ISO-8859-2",
+    "ISO-8859-15",
+    "windows-1251",
+    "KOI8-R",
+    "Shift_JIS",
+    "EUC-JP",
+    "EUC-KR",
+    "GBK",
+    "gb18030",
+    "Big5",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Encoding of a file on disk and whether it starts with a byte order mark
+pub struct FileEncoding {
+    pub encoding: &'static Encoding,
+    pub bom: bool,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct SerializedFileEncoding {
+    name: String,
+    bom: bool,
+}
+
+impl Default for FileEncoding {
+    fn default() -> Self {
+        Self {
+            encoding: UTF_8,
+            bom: false,
+        }
+    }
+}
+
+impl FileEncoding {
+    /// Looks up an encoding by its name or any of its WHATWG labels
+    pub fn from_label(label: &str, bom: bool) -> Result<Self, Box<dyn Error>> {
+        let encoding = Encoding::for_label(label.trim().as_bytes())
+            .ok_or_else(|| PapyrusError::Encoding(format!("Unknown encoding {}", label)))?;
+        Ok(Self { encoding, bom })
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.encoding.name()
+    }
+}
+
+impl serde::Serialize for FileEncoding {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        SerializedFileEncoding {
+            name: self.name().to_string(),
+            bom: self.bom,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for FileEncoding {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let encoding = SerializedFileEncoding::deserialize(deserializer)?;
+        FileEncoding::from_label(&encoding.name, encoding.bom).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Guesses whether BOM-less text is UTF-16 from the position of its zero
+/// bytes, which are common in the high byte of mostly ASCII UTF-16 text
+fn detect_utf16_without_bom(bytes: &[u8]) -> Option<&'static Encoding> {
+    if bytes.len() < 2 || bytes.len() & 1 == 1 {
+        return None;
+    }
+    let pairs = bytes.len() / 2;
+    let even_zeros = bytes.iter().step_by(2).filter(|byte| **byte == 0).count();
+    let odd_zeros = bytes
+        .iter()
+        .skip(1)
+        .step_by(2)
+        .filter(|byte| **byte == 0)
+        .count();
+
+    if odd_zeros * 10 > pairs * 4 && even_zeros * 10 < pairs {
+        Some(UTF_16LE)
+    } else if even_zeros * 10 > pairs * 4 && odd_zeros * 10 < pairs {
+        Some(UTF_16BE)
+    } else {
+        None
+    }
+}
+
+/// Detects the encoding of file content from its byte order mark, falling
+/// back to BOM-less UTF-16, then UTF-8 when the content is valid UTF-8 and
+/// finally to a statistical guess. UTF-16 is checked first since mostly
+/// ASCII UTF-16 text is also valid UTF-8.
+pub fn detect_encoding(bytes: &[u8]) -> FileEncoding {
+    if let Some((encoding, _)) = Encoding::for_bom(bytes) {
+        return FileEncoding {
+            encoding,
+            bom: true,
+        };
+    }
+
+    if let Some(encoding) = detect_utf16_without_bom(bytes) {
+        return FileEncoding {
+            encoding,
+            bom: false,
+        };
+    }
+
+    if std::str::from_utf8(bytes).is_ok() {
+        return FileEncoding::default();
+    }
+
+    let mut detector = chardetng::EncodingDetector::new();
+    detector.feed(bytes, true);
+    FileEncoding {
+        encoding: detector.guess(None, true),
+        bom: false,
+    }
+}
+
+/// Returns whether the content starts with the byte order mark of the encoding
+pub fn has_bom(bytes: &[u8], encoding: &FileEncoding) -> bool {
+    matches!(Encoding::for_bom(bytes), Some((bom_encoding, _)) if bom_encoding == encoding.encoding)
+}
+
+/// Decodes file content with the given encoding, skipping its byte order
+/// mark. Malformed sequences are replaced with U+FFFD, and reported by the
+/// returned flag since saving the text would not restore them.
+pub fn decode(bytes: &[u8], encoding: &FileEncoding) -> (String, bool) {
+    let bytes = match Encoding::for_bom(bytes) {
+        Some((bom_encoding, bom_length)) if bom_encoding == encoding.encoding => {
+            &bytes[bom_length..]
+        }
+        _ => bytes,
+    };
+    let (text, had_errors) = encoding.encoding.decode_without_bom_handling(bytes);
+    (text.into_owned(), had_errors)
+}
+
+/// Encodes text with the given encoding, writing a byte order mark if the
+/// encoding has one. Characters the encoding cannot represent are an error
+/// instead of being silently replaced.
+pub fn encode(text: &str, encoding: &FileEncoding) -> Result<Vec<u8>, Box<dyn Error>> {
+    // encoding_rs only decodes UTF-16, so it is encoded by hand
+    if encoding.encoding == UTF_16LE || encoding.encoding == UTF_16BE {
+        let little_endian = encoding.encoding == UTF_16LE;
+        let mut bytes = Vec::with_capacity(text.len() * 2 + 2);
+        let bom = if encoding.bom { Some(0xFEFF) } else { None };
+        for unit in bom.into_iter().chain(text.encode_utf16()) {
+            if little_endian {
+                bytes.extend_from_slice(&unit.to_le_bytes());
+            } else {
+                bytes.extend_from_slice(&unit.to_be_bytes());
+            }
+        }
+        return Ok(bytes);
+    }
+
+    let mut bytes = vec![];
+    if encoding.bom && encoding.encoding == UTF_8 {
+        bytes.extend_from_slice(b"\xEF\xBB\xBF");
+    }
+    let (encoded, _, had_unmappable) = encoding.encoding.encode(text);
+    if had_unmappable {
+        return Err(PapyrusError::Encoding(format!(
+            "The text contains characters that cannot be encoded as {}",
+            encoding.name()
+        ))
+        .into());
+    }
+    bytes.extend_from_slice(&encoded);
+    Ok(bytes)
+}
@@ -1,11 +1,26 @@
 use std::{
     cmp::Ordering,
     error::Error,
-    fs::{self, DirBuilder, File},
-    io::{Read, Write},
+    ffi::OsString,
+    fs::{self, DirBuilder, File, OpenOptions},
+    io::{self, Read, Write},
     path::{Path, PathBuf},
+    time::SystemTime,
 };
 
+use crate::editor_io::encoding::{self, FileEncoding};
+use crate::editor_io::trash;
+use crate::error::PapyrusError;
+
+/// Suffix of the temporary files written by `write_file_atomically`
+pub const TEMP_FILE_SUFFIX: &str = ".papyrus-tmp";
+
+/// Most symlinks followed to reach a file, like the limit of the OS
+const MAX_SYMLINK_DEPTH: usize = 40;
+
+/// Most names tried for the temporary file of an atomic write
+const MAX_TEMP_FILE_ATTEMPTS: usize = 100;
+
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct FolderEntry {
     path: String,
@@ -37,6 +52,37 @@ impl Ord for FolderEntry {
     }
 }
 
+impl FolderEntry {
+    pub fn new(path: &Path, is_dir: bool) -> Self {
+        Self {
+            path: path.to_string_lossy().to_string(),
+            is_dir,
+            name: path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string(),
+            extension: path
+                .extension()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string(),
+        }
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.is_dir
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
 impl FileEntry {
     pub fn new(path_str: String) -> Self {
         let path = Path::new(&path_str);
@@ -46,14 +92,12 @@ impl FileEntry {
             name: path
                 .file_name()
                 .unwrap_or_default()
-                .to_str()
-                .unwrap()
+                .to_string_lossy()
                 .to_string(),
             extension: path
                 .extension()
                 .unwrap_or_default()
-                .to_str()
-                .unwrap()
+                .to_string_lossy()
                 .to_string(),
         }
     }
@@ -70,67 +114,350 @@ pub fn create_folder(path: &str) -> Result<(), Box<dyn Error>> {
 }
 
 pub fn read_file_content(path: &str) -> Result<String, Box<dyn Error>> {
+    let (buf, _encoding, _had_errors) = read_file_content_with_encoding(path, None)?;
+    Ok(buf)
+}
+
+/// Reads and decodes a file with the given encoding, or with the encoding
+/// detected from its content if none is given. Also returns whether the
+/// file had bytes that are malformed in the encoding.
+pub fn read_file_content_with_encoding(
+    path: &str,
+    encoding: Option<FileEncoding>,
+) -> Result<(String, FileEncoding, bool), Box<dyn Error>> {
     let mut f = File::open(path)?;
-    let mut buf = String::new();
+    let mut bytes = vec![];
 
-    let _ = f.read_to_string(&mut buf)?;
+    let _ = f.read_to_end(&mut bytes)?;
 
-    Ok(buf)
+    let encoding = match encoding {
+        Some(encoding) => FileEncoding {
+            encoding: encoding.encoding,
+            bom: encoding::has_bom(&bytes, &encoding),
+        },
+        None => encoding::detect_encoding(&bytes),
+    };
+    let (text, had_errors) = encoding::decode(&bytes, &encoding);
+    Ok((text, encoding, had_errors))
+}
+
+/// Encodes the text and replaces the content of the file atomically
+pub fn override_file_content(
+    path: &str,
+    buf: String,
+    encoding: &FileEncoding,
+) -> Result<(), Box<dyn Error>> {
+    let bytes = encoding::encode(&buf, encoding)?;
+    write_file_atomically(path, &bytes)
+}
+
+/// Returns the last modification time of the file, or None if it does not
+/// exist
+pub fn modified_time(path: &str) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// Returns an error if the file was modified since the given time, when it
+/// was loaded or last saved. Files that no longer exist can be written.
+pub fn check_unchanged(path: &str, modified: Option<SystemTime>) -> Result<(), Box<dyn Error>> {
+    match modified_time(path) {
+        Some(current) if Some(current) != modified => {
+            Err(Box::new(PapyrusError::FileChanged(path.to_string())))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Replaces the content of the file by writing a temporary file in the
+/// same folder, syncing it to disk and renaming it over the file, so the
+/// file is never left partially written. Symlinks are followed so the file
+/// they point to is replaced rather than the link.
+pub fn write_file_atomically(path: &str, bytes: &[u8]) -> Result<(), Box<dyn Error>> {
+    let path = resolve_symlinks(Path::new(path))?;
+    let (temp_path, temp_file) = create_temp_file(&path)?;
+
+    let result = write_and_rename(temp_file, &temp_path, &path, bytes);
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result
+}
+
+/// Creates the temporary file written in place of the file. Only a new
+/// file is opened, never a file or symlink already at the name, trying
+/// another name when it is taken.
+fn create_temp_file(path: &Path) -> Result<(PathBuf, File), Box<dyn Error>> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| format!("{} is not a file", path.display()))?;
+    for attempt in 0..MAX_TEMP_FILE_ATTEMPTS {
+        let mut temp_name = OsString::from(".");
+        temp_name.push(file_name);
+        if attempt > 0 {
+            temp_name.push(format!(".{}", attempt));
+        }
+        temp_name.push(TEMP_FILE_SUFFIX);
+        let temp_path = path.with_file_name(temp_name);
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&temp_path)
+        {
+            Ok(file) => return Ok((temp_path, file)),
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Err(format!("No temporary file could be created for {}", path.display()).into())
 }
 
-pub fn override_file_content(path: &str, buf: String) -> Result<(), Box<dyn Error>> {
-    let mut f = File::create(path)?;
-    f.write_all(buf.as_bytes())?;
+/// Returns the file the path points to after following symlinks. The
+/// target of a dangling symlink is returned so it gets created.
+fn resolve_symlinks(path: &Path) -> Result<PathBuf, Box<dyn Error>> {
+    let mut path = path.to_path_buf();
+    for _ in 0..MAX_SYMLINK_DEPTH {
+        match fs::symlink_metadata(&path) {
+            Ok(metadata) if metadata.file_type().is_symlink() => {
+                // Relative targets are relative to the folder of the link
+                let target = fs::read_link(&path)?;
+                path = match path.parent() {
+                    Some(parent) => parent.join(target),
+                    None => target,
+                };
+            }
+            _ => return Ok(path),
+        }
+    }
+    Err(format!("Too many levels of symbolic links in {}", path.display()).into())
+}
 
+/// Writes the temporary file, keeping the permissions and ownership of the
+/// file it replaces, and renames it over the file
+fn write_and_rename(
+    mut f: File,
+    temp_path: &Path,
+    path: &Path,
+    bytes: &[u8],
+) -> Result<(), Box<dyn Error>> {
+    f.write_all(bytes)?;
+    if let Ok(metadata) = fs::metadata(path) {
+        // Changing the owner clears the setuid bits, so the permissions
+        // are set after
+        copy_ownership(&f, &metadata);
+        f.set_permissions(metadata.permissions())?;
+    }
+    f.sync_all()?;
+    fs::rename(temp_path, path)?;
+    sync_folder(path);
     Ok(())
 }
 
+/// Gives the file the owner and group of the file it replaces. Only root
+/// can change the owner, otherwise only the group is kept if the user
+/// belongs to it.
+#[cfg(unix)]
+fn copy_ownership(file: &File, metadata: &fs::Metadata) {
+    use std::os::unix::fs::MetadataExt;
+    use std::os::unix::io::AsRawFd;
+
+    let fd = file.as_raw_fd();
+    // SAFETY: the descriptor is owned by `file`, which outlives the calls
+    unsafe {
+        if libc::fchown(fd, metadata.uid(), metadata.gid()) != 0 {
+            libc::fchown(fd, libc::uid_t::MAX, metadata.gid());
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn copy_ownership(_file: &File, _metadata: &fs::Metadata) {}
+
+/// Syncs the folder of the file so the rename survives a crash
+#[cfg(unix)]
+fn sync_folder(path: &Path) {
+    if let Some(Ok(folder)) = path.parent().map(File::open) {
+        let _ = folder.sync_all();
+    }
+}
+
+#[cfg(not(unix))]
+fn sync_folder(_path: &Path) {}
+
 pub fn get_folder_content(path: &str) -> Result<Vec<FolderEntry>, Box<dyn Error>> {
     let path = Path::new(path);
-    assert!(path.is_dir());
+    if !path.is_dir() {
+        return Err(format!("{} is not a directory", path.display()).into());
+    }
 
     let mut entries: Vec<FolderEntry> = vec![];
     let entry_iter = fs::read_dir(path)?;
     for entry in entry_iter {
         let entry = entry?;
-        let folder_entry = FolderEntry {
-            path: entry.path().to_str().unwrap().to_string(),
-            is_dir: entry.metadata()?.is_dir(),
-            name: entry
-                .path()
-                .file_name()
-                .unwrap_or_default()
-                .to_str()
-                .unwrap()
-                .to_string(),
-            extension: entry
-                .path()
-                .extension()
-                .unwrap_or_default()
-                .to_str()
-                .unwrap()
-                .to_string(),
-        };
-        entries.push(folder_entry);
+        entries.push(FolderEntry::new(&entry.path(), entry.metadata()?.is_dir()));
     }
     entries.sort();
 
     Ok(entries)
 }
 
+/// Returns the metadata of a file or folder without following symlinks,
+/// or an error if there is none at the path
+fn check_exists(path: &Path) -> Result<fs::Metadata, Box<dyn Error>> {
+    fs::symlink_metadata(path).map_err(|err| -> Box<dyn Error> {
+        if err.kind() == io::ErrorKind::NotFound {
+            Box::new(PapyrusError::PathNotFound(path.display().to_string()))
+        } else {
+            err.into()
+        }
+    })
+}
+
+/// Returns an error if something exists at the destination or the
+/// destination is inside the source
+fn check_destination(from: &Path, to: &Path) -> Result<(), Box<dyn Error>> {
+    if fs::symlink_metadata(to).is_ok() {
+        return Err(Box::new(PapyrusError::PathExists(to.display().to_string())));
+    }
+    if to.starts_with(from) {
+        return Err(format!("{} cannot be moved or copied into itself", from.display()).into());
+    }
+    Ok(())
+}
+
+/// Returns whether the paths only differ by case and name the same file,
+/// as they do on case insensitive filesystems
+fn is_case_only_rename(from: &Path, to: &Path) -> bool {
+    from.to_string_lossy().to_lowercase() == to.to_string_lossy().to_lowercase()
+        && is_same_file(from, to)
+}
+
+#[cfg(unix)]
+fn is_same_file(a: &Path, b: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    match (fs::symlink_metadata(a), fs::symlink_metadata(b)) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+        _ => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn is_same_file(a: &Path, b: &Path) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// Renames or moves a file or folder without overwriting anything. Items
+/// moved to another filesystem are copied, then deleted. Changing only the
+/// case of the name is allowed, even where the new name already names the
+/// same file.
+pub fn rename_path(from: &str, to: &str) -> Result<(), Box<dyn Error>> {
+    let (from, to) = (Path::new(from), Path::new(to));
+    check_exists(from)?;
+    if !is_case_only_rename(from, to) {
+        check_destination(from, to)?;
+    }
+    match fs::rename(from, to) {
+        Err(err) if is_cross_device(&err) => {
+            copy_recursively(from, to)?;
+            remove_recursively(from)
+        }
+        result => Ok(result?),
+    }
+}
+
+#[cfg(unix)]
+fn is_cross_device(err: &io::Error) -> bool {
+    err.raw_os_error() == Some(libc::EXDEV)
+}
+
+#[cfg(not(unix))]
+fn is_cross_device(_err: &io::Error) -> bool {
+    false
+}
+
+/// Copies a file, or a folder with everything in it, without overwriting
+/// anything
+pub fn copy_path(from: &str, to: &str) -> Result<(), Box<dyn Error>> {
+    let (from, to) = (Path::new(from), Path::new(to));
+    check_exists(from)?;
+    check_destination(from, to)?;
+    copy_recursively(from, to)
+}
+
+/// Copies files with their permissions. Symlinks are copied as links.
+fn copy_recursively(from: &Path, to: &Path) -> Result<(), Box<dyn Error>> {
+    let metadata = fs::symlink_metadata(from)?;
+    let file_type = metadata.file_type();
+    if file_type.is_dir() {
+        fs::create_dir(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            copy_recursively(&entry.path(), &to.join(entry.file_name()))?;
+        }
+        // Set last, in case the folder is read only
+        fs::set_permissions(to, metadata.permissions())?;
+    } else if file_type.is_symlink() {
+        copy_symlink(from, to)?;
+    } else {
+        fs::copy(from, to)?;
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn copy_symlink(from: &Path, to: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(fs::read_link(from)?, to)
+}
+
+#[cfg(not(unix))]
+fn copy_symlink(from: &Path, to: &Path) -> io::Result<()> {
+    fs::copy(from, to).map(|_| ())
+}
+
+/// Permanently deletes a file, or a folder with everything in it
+pub fn remove_path(path: &str) -> Result<(), Box<dyn Error>> {
+    let path = Path::new(path);
+    check_exists(path)?;
+    remove_recursively(path)
+}
+
+fn remove_recursively(path: &Path) -> Result<(), Box<dyn Error>> {
+    if fs::symlink_metadata(path)?.is_dir() {
+        fs::remove_dir_all(path)?;
+    } else {
+        fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+/// Moves a file or folder to the trash
+pub fn trash_path(path: &str) -> Result<(), Box<dyn Error>> {
+    let path = Path::new(path);
+    check_exists(path)?;
+    trash::move_to_trash(path)
+}
+
 pub fn get_relative_path(from: &str, to: &str) -> Result<String, Box<dyn Error>> {
     let path = Path::new(to);
     let path = path.strip_prefix(from)?;
-    Ok(path.to_str().unwrap().to_string())
+    Ok(path.to_string_lossy().to_string())
 }
 
 pub fn get_parent(path: &str) -> Result<String, Box<dyn Error>> {
     let path = Path::new(path);
-    Ok(path.parent().unwrap().to_str().unwrap().to_string())
+    let parent = path
+        .parent()
+        .ok_or_else(|| format!("{} has no parent", path.display()))?;
+    Ok(parent.to_string_lossy().to_string())
 }
 
 pub fn join_paths(start: &str, end: &str) -> Result<String, Box<dyn Error>> {
     let mut path = PathBuf::from(start);
     path.push(end);
-    Ok(path.to_str().unwrap().to_string())
+    Ok(path.to_string_lossy().to_string())
 }
@@ -0,0 +1,89 @@
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+/// Line break sequence written when saving a buffer
+pub enum LineEnding {
+    #[serde(rename = "LF")]
+    Lf,
+    #[serde(rename = "CRLF")]
+    Crlf,
+    #[serde(rename = "CR")]
+    Cr,
+}
+
+impl Default for LineEnding {
+    fn default() -> Self {
+        LineEnding::Lf
+    }
+}
+
+impl LineEnding {
+    pub fn sequence(&self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::Crlf => "\r\n",
+            LineEnding::Cr => "\r",
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+/// Line ending of a buffer and whether the file it was loaded from mixed
+/// several line endings
+pub struct LineEndings {
+    pub line_ending: LineEnding,
+    pub mixed: bool,
+}
+
+/// Detects the line ending of the text. Text mixing several line endings
+/// uses the most common one, preferring LF on ties, and text without
+/// line breaks defaults to LF.
+pub fn detect_line_endings(text: &str) -> LineEndings {
+    let (mut lf, mut crlf, mut cr) = (0, 0, 0);
+    for line_break in detect_line_breaks(text) {
+        match line_break {
+            LineEnding::Lf => lf += 1,
+            LineEnding::Crlf => crlf += 1,
+            LineEnding::Cr => cr += 1,
+        }
+    }
+
+    let line_ending = if crlf > lf && crlf >= cr {
+        LineEnding::Crlf
+    } else if cr > lf && cr > crlf {
+        LineEnding::Cr
+    } else {
+        LineEnding::Lf
+    };
+    let kinds = [lf, crlf, cr].iter().filter(|count| **count > 0).count();
+
+    LineEndings {
+        line_ending,
+        mixed: kinds > 1,
+    }
+}
+
+/// Returns the line break ending each line of the text, in order
+pub fn detect_line_breaks(text: &str) -> Vec<LineEnding> {
+    let mut line_breaks = vec![];
+    let mut bytes = text.bytes().peekable();
+    while let Some(byte) = bytes.next() {
+        match byte {
+            b'\n' => line_breaks.push(LineEnding::Lf),
+            b'\r' if bytes.peek() == Some(&b'\n') => {
+                bytes.next();
+                line_breaks.push(LineEnding::Crlf);
+            }
+            b'\r' => line_breaks.push(LineEnding::Cr),
+            _ => {}
+        }
+    }
+    line_breaks
+}
+
+/// Replaces CRLF and CR line breaks with LF, the only line break stored in
+/// the buffer
+pub fn normalize_line_endings(text: String) -> String {
+    if !text.contains('\r') {
+        return text;
+    }
+    text.replace("\r\n", "\n").replace('\r', "\n")
+}
@@ -1 +1,7 @@
+pub mod encoding;
 pub mod file_handling;
+pub mod line_ending;
+pub mod recovery;
+pub mod trash;
+pub mod undo_history;
+pub mod watcher;
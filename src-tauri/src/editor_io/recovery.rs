@@ -0,0 +1,338 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::editor::language::Language;
+use crate::editor::state::BufferId;
+use crate::editor::text_buffer::{Cursor, LineTextBuffer};
+use crate::editor::undo_tree::{UndoNodeId, UndoTree};
+use crate::editor_io::encoding::FileEncoding;
+use crate::editor_io::file_handling;
+use crate::editor_io::line_ending::{LineEnding, LineEndings};
+
+/// Folder of the app data dir holding the snapshots of unsaved buffers
+const RECOVERY_FOLDER: &str = "recovery";
+
+/// Time between two snapshots of the unsaved buffers
+pub const SNAPSHOT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Time after which a session whose lock file was not touched is assumed
+/// to have crashed
+const SESSION_TIMEOUT: Duration = Duration::from_secs(90);
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+/// Unsaved state of a buffer, enough to open it again as it was
+pub struct BufferSnapshot {
+    pub file_path: Option<String>,
+    pub content: String,
+    pub cursor: Cursor,
+    pub history: UndoTree,
+    pub language: Language,
+    pub encoding: FileEncoding,
+    pub line_endings: LineEndings,
+    #[serde(default)]
+    pub line_breaks: Vec<LineEnding>,
+    #[serde(default)]
+    pub saved_node: Option<UndoNodeId>,
+    pub saved_content_hash: u64,
+    #[serde(default)]
+    pub saved_line_endings: LineEndings,
+    #[serde(default)]
+    pub had_decoding_errors: bool,
+    /// Modification time of the linked file when it was loaded or last
+    /// saved, so saving the restored buffer still detects external changes
+    #[serde(default)]
+    pub file_modified_time: Option<SystemTime>,
+}
+
+impl BufferSnapshot {
+    pub fn new(buffer: &LineTextBuffer) -> Self {
+        Self {
+            file_path: buffer.file_path.clone(),
+            content: buffer.get_content("\n".into()),
+            cursor: buffer.cursor.clone(),
+            history: buffer.history.clone(),
+            language: buffer.language,
+            encoding: buffer.encoding,
+            line_endings: buffer.line_endings,
+            line_breaks: buffer.line_breaks.clone(),
+            saved_node: buffer.saved_node,
+            saved_content_hash: buffer.saved_content_hash,
+            saved_line_endings: buffer.saved_line_endings,
+            had_decoding_errors: buffer.had_decoding_errors,
+            file_modified_time: buffer.file_modified_time,
+        }
+    }
+
+    /// Creates a buffer with the content, undo history and settings of the
+    /// snapshot
+    pub fn restore(self) -> LineTextBuffer {
+        let mut buffer = LineTextBuffer::new(self.content);
+        buffer.file_path = self.file_path;
+        buffer.set_language(self.language);
+        buffer.encoding = self.encoding;
+        buffer.line_endings = self.line_endings;
+        buffer.line_breaks = self.line_breaks;
+        buffer.saved_content_hash = self.saved_content_hash;
+        buffer.saved_line_endings = self.saved_line_endings;
+        buffer.had_decoding_errors = self.had_decoding_errors;
+        buffer.file_modified_time = self.file_modified_time;
+        if self.history.is_valid() {
+            buffer.history = self.history;
+            buffer.saved_node = self.saved_node;
+        } else {
+            buffer.saved_node = None;
+        }
+        if buffer.is_valid_cursor(&self.cursor) {
+            buffer.cursor = self.cursor;
+        }
+        // The frontend opens restored buffers as dirty
+        buffer.take_dirty_change();
+        buffer
+    }
+}
+
+#[derive(serde::Serialize, Debug, Clone, PartialEq, Eq)]
+/// Snapshot left by a previous session that can be restored
+pub struct RecoverableBuffer {
+    pub id: String,
+    pub file_path: Option<String>,
+    /// Milliseconds since the unix epoch of the snapshot
+    pub timestamp: u64,
+}
+
+#[derive(serde::Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct RestoredBuffer {
+    pub buffer_id: BufferId,
+    pub file_path: Option<String>,
+    pub cursor: Cursor,
+}
+
+/// Change to the snapshot files, written without holding the editor state
+pub enum SnapshotWrite {
+    Write(PathBuf, BufferSnapshot),
+    Remove(PathBuf),
+}
+
+/// Snapshots of the unsaved buffers of this session. Snapshots are named
+/// after the session so the ones left by a crashed session are not
+/// overwritten before they are restored. A running session keeps a lock
+/// file touched so other instances leave its snapshots alone.
+pub struct Recovery {
+    dir: PathBuf,
+    session: String,
+    /// Content hash of each buffer when its snapshot was last taken
+    snapshot_hashes: HashMap<BufferId, u64>,
+}
+
+impl Recovery {
+    pub fn new(data_dir: &Path) -> Self {
+        let started = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_millis());
+        Self {
+            dir: data_dir.join(RECOVERY_FOLDER),
+            session: format!("{}-{}", started, std::process::id()),
+            snapshot_hashes: HashMap::new(),
+        }
+    }
+
+    /// Touches the lock file of the session, marking it as running
+    pub fn lock_session(&self) -> Result<(), Box<dyn Error>> {
+        fs::create_dir_all(&self.dir)?;
+        fs::write(self.lock_file(&self.session), b"")?;
+        Ok(())
+    }
+
+    /// Removes the lock file of the session, so its snapshots are offered
+    /// right away by the next session
+    pub fn unlock_session(&self) -> Result<(), Box<dyn Error>> {
+        let lock_file = self.lock_file(&self.session);
+        if lock_file.exists() {
+            fs::remove_file(lock_file)?;
+        }
+        Ok(())
+    }
+
+    fn lock_file(&self, session: &str) -> PathBuf {
+        self.dir.join(format!("{}.lock", session))
+    }
+
+    /// Returns whether the session that took the snapshot is still running,
+    /// because its lock file was touched recently and, where it can be
+    /// checked, its process is alive
+    fn is_running(&self, snapshot_id: &str) -> bool {
+        let session = match snapshot_id.rsplit_once('-') {
+            Some((session, _)) => session,
+            None => return false,
+        };
+        let is_locked = fs::metadata(self.lock_file(session))
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok())
+            .map_or(false, |elapsed| elapsed < SESSION_TIMEOUT);
+        is_locked && is_process_alive(session)
+    }
+
+    fn snapshot_file(&self, buffer_id: BufferId) -> PathBuf {
+        self.dir
+            .join(format!("{}-{}.json", self.session, buffer_id))
+    }
+
+    /// Returns the snapshots to take of the buffers modified since their
+    /// last snapshot, and the snapshots to remove of the buffers that were
+    /// saved or closed since
+    pub fn pending_writes(
+        &mut self,
+        buffers: &HashMap<BufferId, LineTextBuffer>,
+    ) -> Vec<SnapshotWrite> {
+        let mut writes = vec![];
+        let snapshotted: Vec<BufferId> = self.snapshot_hashes.keys().copied().collect();
+        for buffer_id in snapshotted {
+            let is_dirty = buffers
+                .get(&buffer_id)
+                .map_or(false, |buffer| buffer.is_dirty());
+            if !is_dirty {
+                self.snapshot_hashes.remove(&buffer_id);
+                writes.push(SnapshotWrite::Remove(self.snapshot_file(buffer_id)));
+            }
+        }
+
+        for (buffer_id, buffer) in buffers {
+            if !buffer.is_dirty() {
+                continue;
+            }
+            let content_hash = buffer.content_hash();
+            if self.snapshot_hashes.get(buffer_id) != Some(&content_hash) {
+                self.snapshot_hashes.insert(*buffer_id, content_hash);
+                writes.push(SnapshotWrite::Write(
+                    self.snapshot_file(*buffer_id),
+                    BufferSnapshot::new(buffer),
+                ));
+            }
+        }
+        writes
+    }
+
+    /// Lists the snapshots left by other sessions that are no longer
+    /// running, oldest first. Snapshots that cannot be read are skipped.
+    pub fn recoverable(&self) -> Vec<RecoverableBuffer> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(_) => return vec![],
+        };
+        let mut recoverable: Vec<RecoverableBuffer> = entries
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                let id = path.file_stem()?.to_string_lossy().to_string();
+                if path.extension()? != "json"
+                    || id.starts_with(&self.session)
+                    || self.is_running(&id)
+                {
+                    return None;
+                }
+                let snapshot = read_snapshot(&path).ok()?;
+                let timestamp = fs::metadata(&path)
+                    .and_then(|metadata| metadata.modified())
+                    .ok()?
+                    .duration_since(UNIX_EPOCH)
+                    .ok()?
+                    .as_millis() as u64;
+                Some(RecoverableBuffer {
+                    id,
+                    file_path: snapshot.file_path,
+                    timestamp,
+                })
+            })
+            .collect();
+        recoverable.sort_by_key(|buffer| buffer.timestamp);
+        recoverable
+    }
+
+    /// Reads the snapshot of a recoverable buffer
+    pub fn read(&self, id: &str) -> Result<BufferSnapshot, Box<dyn Error>> {
+        read_snapshot(&self.recoverable_file(id)?)
+    }
+
+    /// Removes the snapshot of a recoverable buffer, once restored buffers
+    /// are snapshotted by this session instead
+    pub fn discard(&self, id: &str) -> Result<(), Box<dyn Error>> {
+        fs::remove_file(self.recoverable_file(id)?)?;
+        Ok(())
+    }
+
+    /// Returns the file of a snapshot left by another session that is no
+    /// longer running, refusing ids that are not plain file names
+    fn recoverable_file(&self, id: &str) -> Result<PathBuf, Box<dyn Error>> {
+        if id.is_empty()
+            || id.contains(['/', '\\', '.'])
+            || id.starts_with(&self.session)
+            || self.is_running(id)
+        {
+            return Err(format!("{} is not a recoverable buffer", id).into());
+        }
+        Ok(self.dir.join(format!("{}.json", id)))
+    }
+}
+
+fn read_snapshot(path: &Path) -> Result<BufferSnapshot, Box<dyn Error>> {
+    Ok(serde_json::from_slice(&fs::read(path)?)?)
+}
+
+/// Applies the changes to the snapshot files. A change that fails is
+/// logged without stopping the others.
+pub fn write_snapshots(writes: Vec<SnapshotWrite>) {
+    for write in writes {
+        let (path, result) = match write {
+            SnapshotWrite::Write(path, snapshot) => {
+                let result = write_snapshot(&path, &snapshot);
+                (path, result)
+            }
+            SnapshotWrite::Remove(path) => {
+                let result = if path.exists() {
+                    fs::remove_file(&path).map_err(|err| err.into())
+                } else {
+                    Ok(())
+                };
+                (path, result)
+            }
+        };
+        if let Err(err) = result {
+            eprintln!("Failed to update snapshot {}: {}", path.display(), err);
+        }
+    }
+}
+
+fn write_snapshot(path: &Path, snapshot: &BufferSnapshot) -> Result<(), Box<dyn Error>> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    file_handling::write_file_atomically(&path.to_string_lossy(), &serde_json::to_vec(snapshot)?)
+}
+
+/// Returns whether the process of the session is alive. Sessions are
+/// named after the start time and the id of their process. Processes can
+/// only be checked on unix, elsewhere the lock file alone is trusted.
+fn is_process_alive(session: &str) -> bool {
+    let pid = match session
+        .rsplit('-')
+        .next()
+        .and_then(|pid| pid.parse::<i32>().ok())
+    {
+        Some(pid) => pid,
+        None => return false,
+    };
+    #[cfg(unix)]
+    {
+        // Signal 0 only checks that the process exists and can be signaled
+        let signaled = unsafe { libc::kill(pid, 0) } == 0;
+        signaled || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = pid;
+        true
+    }
+}
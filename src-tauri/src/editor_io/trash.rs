@@ -0,0 +1,179 @@
+use std::error::Error;
+use std::path::Path;
+
+#[cfg(target_os = "linux")]
+use std::{
+    env,
+    fs::{self, DirBuilder, OpenOptions},
+    io::{self, Write},
+    os::unix::ffi::OsStrExt,
+    os::unix::fs::{DirBuilderExt, MetadataExt},
+    path::PathBuf,
+};
+
+/// Moves a file or folder to the trash, as described by the freedesktop.org
+/// trash specification. Items on the filesystem of the home folder go to
+/// the home trash, others to a trash at the top of their filesystem so they
+/// are not copied.
+#[cfg(target_os = "linux")]
+pub fn move_to_trash(path: &Path) -> Result<(), Box<dyn Error>> {
+    let path = absolute_path(path)?;
+    let home_trash = home_trash_dir()?;
+    create_trash_dir(&home_trash)?;
+
+    let device = fs::symlink_metadata(&path)?.dev();
+    let trash_dir = if fs::metadata(&home_trash)?.dev() == device {
+        home_trash
+    } else {
+        // SAFETY: getuid has no preconditions and cannot fail
+        let uid = unsafe { libc::getuid() };
+        let trash_dir = mount_point(&path)?.join(format!(".Trash-{}", uid));
+        create_trash_dir(&trash_dir)?;
+        trash_dir
+    };
+    move_to_trash_dir(&path, &trash_dir)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn move_to_trash(_path: &Path) -> Result<(), Box<dyn Error>> {
+    Err("Moving to the trash is not supported on this platform".into())
+}
+
+/// Moves a file or folder into the `files` folder of a trash, along with
+/// a `.trashinfo` file in its `info` folder recording where it came from.
+/// The item is renamed if the trash already has an item with its name.
+#[cfg(target_os = "linux")]
+pub fn move_to_trash_dir(path: &Path, trash_dir: &Path) -> Result<(), Box<dyn Error>> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| format!("{} cannot be moved to the trash", path.display()))?
+        .to_string_lossy()
+        .to_string();
+
+    // Names are reserved by creating their info file, as other programs
+    // may be trashing items at the same time
+    let mut copy = 1;
+    let (trashed_name, info_path, mut info_file) = loop {
+        let trashed_name = if copy == 1 {
+            name.clone()
+        } else {
+            format!("{}.{}", name, copy)
+        };
+        copy += 1;
+        if fs::symlink_metadata(trash_dir.join("files").join(&trashed_name)).is_ok() {
+            continue;
+        }
+        let info_path = trash_dir
+            .join("info")
+            .join(format!("{}.trashinfo", trashed_name));
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&info_path)
+        {
+            Ok(info_file) => break (trashed_name, info_path, info_file),
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(err.into()),
+        }
+    };
+
+    let result = write!(
+        info_file,
+        "[Trash Info]\nPath={}\nDeletionDate={}\n",
+        encode_path(path),
+        deletion_date()
+    )
+    .and_then(|_| fs::rename(path, trash_dir.join("files").join(trashed_name)));
+    if result.is_err() {
+        let _ = fs::remove_file(info_path);
+    }
+    Ok(result?)
+}
+
+/// Returns `$XDG_DATA_HOME/Trash`, which defaults to
+/// `~/.local/share/Trash`
+#[cfg(target_os = "linux")]
+fn home_trash_dir() -> Result<PathBuf, Box<dyn Error>> {
+    match env::var_os("XDG_DATA_HOME") {
+        Some(data_home) if Path::new(&data_home).is_absolute() => {
+            Ok(Path::new(&data_home).join("Trash"))
+        }
+        _ => {
+            let home = env::var_os("HOME").ok_or("The home folder is not set")?;
+            Ok(Path::new(&home).join(".local/share/Trash"))
+        }
+    }
+}
+
+/// Creates the `files` and `info` folders of a trash, only readable by the
+/// user
+#[cfg(target_os = "linux")]
+fn create_trash_dir(trash_dir: &Path) -> io::Result<()> {
+    let mut builder = DirBuilder::new();
+    builder.recursive(true).mode(0o700);
+    builder.create(trash_dir.join("files"))?;
+    builder.create(trash_dir.join("info"))
+}
+
+/// Returns the path made absolute, with the symlinks of its parent
+/// resolved. The item itself is not resolved since a trashed symlink is
+/// trashed as a link.
+#[cfg(target_os = "linux")]
+fn absolute_path(path: &Path) -> Result<PathBuf, Box<dyn Error>> {
+    let path = env::current_dir()?.join(path);
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) => Ok(fs::canonicalize(parent)?.join(name)),
+        _ => Err(format!("{} cannot be moved to the trash", path.display()).into()),
+    }
+}
+
+/// Returns the top folder of the filesystem holding the path
+#[cfg(target_os = "linux")]
+fn mount_point(path: &Path) -> io::Result<PathBuf> {
+    let device = fs::symlink_metadata(path)?.dev();
+    let mut mount_point = path;
+    while let Some(parent) = mount_point.parent() {
+        if fs::metadata(parent)?.dev() != device {
+            break;
+        }
+        mount_point = parent;
+    }
+    Ok(mount_point.to_path_buf())
+}
+
+/// Percent-encodes the path like a URL path, as the trash info requires
+#[cfg(target_os = "linux")]
+fn encode_path(path: &Path) -> String {
+    path.as_os_str()
+        .as_bytes()
+        .iter()
+        .map(|&byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// Returns the current local time formatted as `YYYY-MM-DDThh:mm:ss`
+#[cfg(target_os = "linux")]
+fn deletion_date() -> String {
+    // SAFETY: time accepts a null pointer and localtime_r only writes to
+    // the given tm
+    let tm = unsafe {
+        let now = libc::time(std::ptr::null_mut());
+        let mut tm: libc::tm = std::mem::zeroed();
+        libc::localtime_r(&now, &mut tm);
+        tm
+    };
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        tm.tm_year + 1900,
+        tm.tm_mon + 1,
+        tm.tm_mday,
+        tm.tm_hour,
+        tm.tm_min,
+        tm.tm_sec
+    )
+}
@@ -0,0 +1,110 @@
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::editor::text_buffer::LineTextBuffer;
+use crate::editor::undo_tree::{UndoNodeId, UndoTree};
+use crate::editor_io::file_handling;
+
+/// Folder of the app data dir holding the stored undo histories
+const HISTORY_FOLDER: &str = "undo_history";
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+/// Undo history of a file along with the content saved to the file and
+/// the node of the history leading to it
+struct StoredHistory {
+    path: String,
+    content_hash: u64,
+    /// Node of the saved content, the current node of histories stored
+    /// before it was recorded
+    #[serde(default)]
+    saved_node: Option<UndoNodeId>,
+    history: UndoTree,
+}
+
+/// 64 bit FNV-1a hash, stable across builds unlike the std hashers
+pub fn fnv1a_hash(bytes: impl IntoIterator<Item = u8>) -> u64 {
+    bytes.into_iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Returns the file the undo history of a path is stored in
+pub fn history_file(data_dir: &Path, path: &str) -> PathBuf {
+    data_dir
+        .join(HISTORY_FOLDER)
+        .join(format!("{:016x}.json", fnv1a_hash(path.bytes())))
+}
+
+/// Stores the undo history of a buffer linked to a file, along with the
+/// content last saved, even if the buffer has unsaved changes. The history
+/// is only restored if the file still has that content when reopened.
+/// Histories whose saved state is no longer in them are not stored.
+pub fn store(data_dir: &Path, buffer: &LineTextBuffer) -> Result<(), Box<dyn Error>> {
+    let path = match &buffer.file_path {
+        Some(path) => path,
+        None => return Ok(()),
+    };
+    let file = history_file(data_dir, path);
+    let saved_node = match buffer.saved_node {
+        Some(saved_node) if !buffer.history.is_empty() => saved_node,
+        _ => {
+            if file.exists() {
+                fs::remove_file(file)?;
+            }
+            return Ok(());
+        }
+    };
+
+    fs::create_dir_all(data_dir.join(HISTORY_FOLDER))?;
+    let stored = StoredHistory {
+        path: path.clone(),
+        content_hash: buffer.saved_content_hash,
+        saved_node: Some(saved_node),
+        history: buffer.history.clone(),
+    };
+    file_handling::write_file_atomically(&file.to_string_lossy(), &serde_json::to_vec(&stored)?)?;
+    Ok(())
+}
+
+/// Restores the stored undo history of a buffer linked to a file, moving
+/// to the node of the saved content. A history that cannot be read or
+/// does not lead to the content of the buffer, because the file changed
+/// since it was stored, is deleted instead. Returns whether the history
+/// was restored.
+pub fn restore(data_dir: &Path, buffer: &mut LineTextBuffer) -> bool {
+    let path = match &buffer.file_path {
+        Some(path) => path,
+        None => return false,
+    };
+    let file = history_file(data_dir, path);
+    let stored = match fs::read(&file) {
+        Ok(content) => serde_json::from_slice::<StoredHistory>(&content).ok(),
+        Err(_) => return false,
+    };
+
+    match stored {
+        Some(mut stored)
+            if &stored.path == path
+                && stored.content_hash == buffer.content_hash()
+                && stored.history.is_valid()
+                && stored
+                    .saved_node
+                    .map_or(true, |node| node.0 <= stored.history.len()) =>
+        {
+            // The updates are not applied, the buffer already has the saved
+            // content
+            let saved_node = stored
+                .saved_node
+                .unwrap_or_else(|| stored.history.current());
+            stored.history.goto(saved_node);
+            buffer.history = stored.history;
+            buffer.mark_saved();
+            true
+        }
+        _ => {
+            let _ = fs::remove_file(file);
+            false
+        }
+    }
+}
@@ -0,0 +1,248 @@
+use std::error::Error;
+use std::path::{Component, Path};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+use notify::event::{EventKind, ModifyKind, RenameMode};
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::editor_io::file_handling;
+
+/// Time without file system events after which the pending events are
+/// emitted
+pub const DEBOUNCE_DELAY: Duration = Duration::from_millis(200);
+
+/// Longest time events are held back while files keep changing
+pub const MAX_DEBOUNCE_DELAY: Duration = Duration::from_secs(1);
+
+/// Folders whose content changes too often to be worth reporting
+const IGNORED_FOLDERS: [&str; 1] = [".git"];
+
+#[derive(serde::Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "kind")]
+/// Change made to a file or folder of the workspace
+pub enum FileEvent {
+    Created { path: String },
+    Modified { path: String },
+    Removed { path: String },
+    Renamed { from: String, to: String },
+}
+
+impl FileEvent {
+    /// Returns the path of the file after the event
+    fn path(&self) -> &str {
+        match self {
+            FileEvent::Created { path }
+            | FileEvent::Modified { path }
+            | FileEvent::Removed { path } => path,
+            FileEvent::Renamed { to, .. } => to,
+        }
+    }
+}
+
+/// Collects file system events until the workspace is quiet, merging the
+/// events of the same file. A file created and removed in between is not
+/// reported at all.
+#[derive(Default)]
+pub struct Debouncer {
+    events: Vec<FileEvent>,
+    first_event_time: Option<Instant>,
+    last_event_time: Option<Instant>,
+}
+
+impl Debouncer {
+    /// Adds the changes reported by a file system event. Events of ignored
+    /// folders and temporary files are skipped.
+    pub fn add(&mut self, event: Event, now: Instant) {
+        let mut paths = event.paths.iter().map(|path| {
+            let is_temp_file = path
+                .to_string_lossy()
+                .ends_with(file_handling::TEMP_FILE_SUFFIX);
+            (
+                path.to_string_lossy().to_string(),
+                is_temp_file || is_ignored(path),
+            )
+        });
+        let first = paths.next();
+        let second = paths.next();
+
+        let file_events = match (event.kind, first, second) {
+            (EventKind::Modify(ModifyKind::Name(RenameMode::Both)), Some(from), Some(to)) => {
+                match (from, to) {
+                    (_, (_, true)) => vec![],
+                    // Files written atomically are renamed from a temporary
+                    // file
+                    ((_, true), (to, false)) => vec![FileEvent::Modified { path: to }],
+                    ((from, false), (to, false)) => vec![FileEvent::Renamed { from, to }],
+                }
+            }
+            (kind, first, second) => first
+                .into_iter()
+                .chain(second)
+                .chain(paths)
+                .filter(|(_, skipped)| !skipped)
+                .filter_map(|(path, _)| match kind {
+                    EventKind::Create(_) | EventKind::Modify(ModifyKind::Name(RenameMode::To)) => {
+                        Some(FileEvent::Created { path })
+                    }
+                    EventKind::Remove(_)
+                    | EventKind::Modify(ModifyKind::Name(RenameMode::From)) => {
+                        Some(FileEvent::Removed { path })
+                    }
+                    // Some platforms do not tell which side of a rename
+                    // the path is on
+                    EventKind::Modify(ModifyKind::Name(_)) => {
+                        if Path::new(&path).exists() {
+                            Some(FileEvent::Created { path })
+                        } else {
+                            Some(FileEvent::Removed { path })
+                        }
+                    }
+                    EventKind::Modify(_) => Some(FileEvent::Modified { path }),
+                    _ => None,
+                })
+                .collect(),
+        };
+
+        for file_event in file_events {
+            self.push(file_event);
+            self.first_event_time.get_or_insert(now);
+            self.last_event_time = Some(now);
+        }
+    }
+
+    /// Adds an event, merging it with the pending events of the same file
+    fn push(&mut self, event: FileEvent) {
+        match event {
+            FileEvent::Created { path } => match self.last_event_at(&path) {
+                Some(idx) if matches!(self.events[idx], FileEvent::Removed { .. }) => {
+                    self.events[idx] = FileEvent::Modified { path };
+                }
+                _ => self.events.push(FileEvent::Created { path }),
+            },
+            FileEvent::Modified { path } => match self.last_event_at(&path) {
+                Some(idx)
+                    if matches!(
+                        self.events[idx],
+                        FileEvent::Created { .. } | FileEvent::Modified { .. }
+                    ) => {}
+                _ => self.events.push(FileEvent::Modified { path }),
+            },
+            FileEvent::Removed { path } => {
+                if let Some(idx) = self.last_event_at(&path) {
+                    match self.events[idx] {
+                        FileEvent::Created { .. } => {
+                            self.events.remove(idx);
+                            return;
+                        }
+                        FileEvent::Modified { .. } => {
+                            self.events.remove(idx);
+                        }
+                        _ => {}
+                    }
+                }
+                self.events.push(FileEvent::Removed { path });
+            }
+            FileEvent::Renamed { from, to } => {
+                // Both sides of a rename may already have been reported on
+                // their own
+                if let Some(idx) = self.last_event_at(&to) {
+                    if matches!(self.events[idx], FileEvent::Created { .. }) {
+                        self.events.remove(idx);
+                    }
+                }
+                if let Some(idx) = self.last_event_at(&from) {
+                    match self.events[idx] {
+                        FileEvent::Removed { .. } => {
+                            self.events.remove(idx);
+                        }
+                        FileEvent::Created { .. } => {
+                            self.events[idx] = FileEvent::Created { path: to };
+                            return;
+                        }
+                        _ => {}
+                    }
+                }
+                self.events.push(FileEvent::Renamed { from, to });
+            }
+        }
+    }
+
+    /// Returns the index of the last pending event leaving a file at the
+    /// given path
+    fn last_event_at(&self, path: &str) -> Option<usize> {
+        self.events.iter().rposition(|event| event.path() == path)
+    }
+
+    /// Returns the time left until the pending events are ready, or None if
+    /// there are none
+    pub fn timeout(&self, now: Instant) -> Option<Duration> {
+        let ready_time = (self.last_event_time? + DEBOUNCE_DELAY)
+            .min(self.first_event_time? + MAX_DEBOUNCE_DELAY);
+        Some(ready_time.saturating_duration_since(now))
+    }
+
+    /// Returns the pending events once they are ready
+    pub fn take_ready(&mut self, now: Instant) -> Vec<FileEvent> {
+        match self.timeout(now) {
+            Some(timeout) if timeout.is_zero() => {
+                self.first_event_time = None;
+                self.last_event_time = None;
+                std::mem::take(&mut self.events)
+            }
+            _ => vec![],
+        }
+    }
+}
+
+/// Returns whether the path is inside an ignored folder
+fn is_ignored(path: &Path) -> bool {
+    path.components().any(|component| match component {
+        Component::Normal(name) => IGNORED_FOLDERS.iter().any(|folder| name == *folder),
+        _ => false,
+    })
+}
+
+/// Watches a folder and its subfolders. Watching stops when it is dropped.
+pub struct WorkspaceWatcher {
+    _watcher: RecommendedWatcher,
+}
+
+impl WorkspaceWatcher {
+    /// Starts watching the folder, calling `on_events` from a background
+    /// thread with the debounced events
+    pub fn new(
+        root: &str,
+        on_events: impl FnMut(Vec<FileEvent>) + Send + 'static,
+    ) -> Result<Self, Box<dyn Error>> {
+        let (sender, receiver) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(sender)?;
+        watcher.watch(Path::new(root), RecursiveMode::Recursive)?;
+        std::thread::spawn(move || debounce_events(receiver, on_events));
+        Ok(Self { _watcher: watcher })
+    }
+}
+
+/// Debounces the events received from the watcher until it is dropped
+fn debounce_events(
+    receiver: Receiver<notify::Result<Event>>,
+    mut on_events: impl FnMut(Vec<FileEvent>),
+) {
+    let mut debouncer = Debouncer::default();
+    loop {
+        let received = match debouncer.timeout(Instant::now()) {
+            Some(timeout) => receiver.recv_timeout(timeout),
+            None => receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        match received {
+            Ok(Ok(event)) => debouncer.add(event, Instant::now()),
+            Ok(Err(_)) | Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+
+        let events = debouncer.take_ready(Instant::now());
+        if !events.is_empty() {
+            on_events(events);
+        }
+    }
+}
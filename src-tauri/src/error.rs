@@ -0,0 +1,139 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use crate::editor::state::BufferId;
+use crate::editor::text_buffer::{Cursor, Selection};
+
+#[derive(Debug)]
+/// Errors returned by the Tauri commands. They are serialised as
+/// `{ kind, message }` so the frontend can match on the kind.
+pub enum PapyrusError {
+    BufferNotFound(BufferId),
+    Io(io::Error),
+    Encoding(String),
+    NoFilePath,
+    FileChanged(String),
+    MalformedContent(String),
+    UnsavedChanges(String),
+    PathExists(String),
+    PathNotFound(String),
+    PtyNotInitialized,
+    Pty(String),
+    InvalidCursor(Cursor),
+    InvalidSelection(Selection),
+    InvalidRow(usize),
+    Theme(String),
+    Search(String),
+    Other(String),
+}
+
+impl PapyrusError {
+    /// Returns the name of the error kind sent to the frontend
+    pub fn kind(&self) -> &'static str {
+        match self {
+            PapyrusError::BufferNotFound(_) => "BufferNotFound",
+            PapyrusError::Io(_) => "Io",
+            PapyrusError::Encoding(_) => "Encoding",
+            PapyrusError::NoFilePath => "NoFilePath",
+            PapyrusError::FileChanged(_) => "FileChanged",
+            PapyrusError::MalformedContent(_) => "MalformedContent",
+            PapyrusError::UnsavedChanges(_) => "UnsavedChanges",
+            PapyrusError::PathExists(_) => "PathExists",
+            PapyrusError::PathNotFound(_) => "PathNotFound",
+            PapyrusError::PtyNotInitialized => "PtyNotInitialized",
+            PapyrusError::Pty(_) => "Pty",
+            PapyrusError::InvalidCursor(_) => "InvalidCursor",
+            PapyrusError::InvalidSelection(_) => "InvalidSelection",
+            PapyrusError::InvalidRow(_) => "InvalidRow",
+            PapyrusError::Theme(_) => "Theme",
+            PapyrusError::Search(_) => "Search",
+            PapyrusError::Other(_) => "Other",
+        }
+    }
+}
+
+impl fmt::Display for PapyrusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PapyrusError::BufferNotFound(buffer_id) => {
+                write!(f, "Buffer {:?} not found", buffer_id)
+            }
+            PapyrusError::Io(err) => write!(f, "{}", err),
+            PapyrusError::Encoding(message) => write!(f, "Encoding error: {}", message),
+            PapyrusError::NoFilePath => write!(f, "Buffer has no file path"),
+            PapyrusError::FileChanged(path) => {
+                write!(f, "{} changed on disk since it was loaded or saved", path)
+            }
+            PapyrusError::MalformedContent(path) => write!(
+                f,
+                "{} has bytes that are not valid in its encoding, saving replaces them",
+                path
+            ),
+            PapyrusError::UnsavedChanges(path) => write!(f, "{} has unsaved changes", path),
+            PapyrusError::PathExists(path) => write!(f, "{} already exists", path),
+            PapyrusError::PathNotFound(path) => write!(f, "{} does not exist", path),
+            PapyrusError::PtyNotInitialized => write!(f, "Terminal has not been initialized"),
+            PapyrusError::Pty(message) => write!(f, "Terminal error: {}", message),
+            PapyrusError::InvalidCursor(cursor) => {
+                write!(f, "Invalid cursor {}:{}", cursor.row, cursor.column)
+            }
+            PapyrusError::InvalidSelection(selection) => write!(
+                f,
+                "Invalid selection {}:{} to {}:{}",
+                selection.start.row,
+                selection.start.column,
+                selection.end.row,
+                selection.end.column
+            ),
+            PapyrusError::InvalidRow(row) => write!(f, "Invalid row {}", row),
+            PapyrusError::Theme(message) => write!(f, "Theme error: {}", message),
+            PapyrusError::Search(message) => write!(f, "Search error: {}", message),
+            PapyrusError::Other(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl Error for PapyrusError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            PapyrusError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for PapyrusError {
+    fn from(err: io::Error) -> Self {
+        // Reading a file that is not valid UTF-8 into a string fails with
+        // invalid data
+        if err.kind() == io::ErrorKind::InvalidData {
+            PapyrusError::Encoding(err.to_string())
+        } else {
+            PapyrusError::Io(err)
+        }
+    }
+}
+
+impl From<Box<dyn Error>> for PapyrusError {
+    fn from(err: Box<dyn Error>) -> Self {
+        match err.downcast::<io::Error>() {
+            Ok(err) => PapyrusError::from(*err),
+            Err(err) => match err.downcast::<PapyrusError>() {
+                Ok(err) => *err,
+                Err(err) => PapyrusError::Other(err.to_string()),
+            },
+        }
+    }
+}
+
+impl serde::Serialize for PapyrusError {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut state = serializer.serialize_struct("PapyrusError", 2)?;
+        state.serialize_field("kind", self.kind())?;
+        state.serialize_field("message", &self.to_string())?;
+        state.end()
+    }
+}
@@ -9,6 +9,8 @@ use tauri::Manager;
 pub mod commands;
 pub mod editor;
 pub mod editor_io;
+pub mod error;
+pub mod search;
 pub mod terminal;
 
 #[cfg(test)]
@@ -22,6 +24,14 @@ async fn main() {
 
     tauri::Builder::default()
         .setup(|app| {
+            if let Some(data_dir) = app.path_resolver().app_data_dir() {
+                commands::lock_editor_state().set_data_dir(data_dir);
+            }
+            commands::snapshot_unsaved_buffers();
+            std::thread::spawn(|| loop {
+                std::thread::sleep(editor_io::recovery::SNAPSHOT_INTERVAL);
+                commands::snapshot_unsaved_buffers();
+            });
             #[cfg(debug_assertions)]
             {
                 let window = app.get_window("main").unwrap();
@@ -34,11 +44,33 @@ async fn main() {
             commands::send_to_pty,
             commands::resize_pty,
             commands::get_folder_content,
+            commands::watch_workspace,
+            commands::index_workspace,
+            commands::query_workspace_index,
+            commands::quick_open,
             commands::create_buffer_from_file_path,
             commands::delete_buffer,
+            commands::get_language,
+            commands::get_theme,
+            commands::load_theme,
+            commands::get_encoding,
+            commands::get_encodings,
+            commands::reopen_with_encoding,
+            commands::save_with_encoding,
+            commands::reload_buffer,
+            commands::is_dirty,
+            commands::set_compare_content_hash,
+            commands::get_line_endings,
+            commands::convert_line_endings,
             commands::get_highlighted_text,
+            commands::get_highlighted_rows,
             commands::insert_text,
             commands::remove_text,
+            commands::insert_text_at_selections,
+            commands::remove_text_at_selections,
+            commands::add_next_occurrence,
+            commands::box_selection,
+            commands::split_selection_into_lines,
             commands::get_row_length,
             commands::get_lines_length,
             commands::select_token_under_cursor,
@@ -47,16 +79,45 @@ async fn main() {
             commands::get_selected_text,
             commands::undo,
             commands::redo,
+            commands::get_undo_history,
+            commands::goto_history,
+            commands::undo_earlier,
+            commands::redo_later,
+            commands::switch_undo_branch,
+            commands::begin_undo_group,
+            commands::end_undo_group,
+            commands::find_all,
+            commands::find_next,
+            commands::find_prev,
+            commands::replace,
+            commands::replace_all,
             commands::add_indentation,
             commands::remove_indentation,
             commands::get_indent_size,
+            commands::find_in_files,
+            commands::cancel_search,
+            commands::preview_replace_in_files,
+            commands::replace_in_files,
+            commands::set_cursor,
+            commands::get_recoverable_buffers,
+            commands::restore_buffers,
+            commands::discard_recoverable_buffers,
             commands::get_file_info,
             commands::get_relative_path,
             commands::get_parent,
             commands::join_paths,
             commands::create_file,
             commands::create_folder,
+            commands::rename_path,
+            commands::copy_path,
+            commands::delete_path,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|_app, event| {
+            // Unsaved buffers are kept on exit and offered on the next start
+            if let tauri::RunEvent::Exit = event {
+                commands::end_recovery_session();
+            }
+        });
 }
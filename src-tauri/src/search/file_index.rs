@@ -0,0 +1,265 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf, MAIN_SEPARATOR};
+
+use ignore::overrides::{Override, OverrideBuilder};
+use ignore::{DirEntry, WalkBuilder};
+
+use crate::editor_io::file_handling::FolderEntry;
+use crate::editor_io::watcher::FileEvent;
+use crate::error::PapyrusError;
+
+/// Files whose changes can change which files are ignored
+const IGNORE_FILES: [&str; 2] = [".gitignore", ".ignore"];
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct IndexOptions {
+    /// Globs of the files and folders to leave out, on top of the ones
+    /// ignored by `.gitignore` and `.ignore` files
+    pub exclude: Vec<String>,
+    /// Whether to leave out files and folders starting with a dot
+    pub exclude_hidden: bool,
+}
+
+/// Files and folders of the workspace, skipping ignored and excluded ones.
+/// Entries are keyed by their path, so the entries inside a folder follow
+/// it.
+pub struct FileIndex {
+    root: PathBuf,
+    options: IndexOptions,
+    overrides: Override,
+    entries: BTreeMap<String, FolderEntry>,
+}
+
+impl FileIndex {
+    /// Creates an empty index, reporting invalid globs before the
+    /// workspace is walked
+    pub fn new(root: &str, options: IndexOptions) -> Result<Self, PapyrusError> {
+        let root = PathBuf::from(root);
+        if !root.is_dir() {
+            return Err(PapyrusError::Search(format!(
+                "{} is not a directory",
+                root.display()
+            )));
+        }
+
+        let mut overrides = OverrideBuilder::new(&root);
+        for glob in &options.exclude {
+            overrides
+                .add(&format!("!{}", glob))
+                .map_err(|err| PapyrusError::Search(err.to_string()))?;
+        }
+        let overrides = overrides
+            .build()
+            .map_err(|err| PapyrusError::Search(err.to_string()))?;
+
+        Ok(Self {
+            root,
+            options,
+            overrides,
+            entries: BTreeMap::new(),
+        })
+    }
+
+    /// Walks the whole workspace again. Entries that cannot be read are
+    /// skipped.
+    pub fn build(&mut self) {
+        self.entries.clear();
+        let root = self.root.clone();
+        self.add_tree(&root);
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Number of files and folders in the index
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn contains(&self, path: &str) -> bool {
+        self.entries.contains_key(path)
+    }
+
+    /// Returns every indexed file, sorted by path
+    pub fn files(&self) -> impl Iterator<Item = &FolderEntry> {
+        self.entries.values().filter(|entry| !entry.is_dir())
+    }
+
+    /// Returns the indexed files matching any of the globs, relative to the
+    /// root, or every file if there are no globs
+    pub fn query(&self, globs: &[String]) -> Result<Vec<&FolderEntry>, PapyrusError> {
+        if globs.is_empty() {
+            return Ok(self.files().collect());
+        }
+        let mut matcher = OverrideBuilder::new(&self.root);
+        for glob in globs {
+            matcher
+                .add(glob)
+                .map_err(|err| PapyrusError::Search(err.to_string()))?;
+        }
+        let matcher = matcher
+            .build()
+            .map_err(|err| PapyrusError::Search(err.to_string()))?;
+        Ok(self
+            .files()
+            .filter(|entry| matcher.matched(entry.path(), false).is_whitelist())
+            .collect())
+    }
+
+    /// Returns the indexed files and folders directly inside a folder,
+    /// sorted like `get_folder_content`
+    pub fn folder_content(&self, folder: &str) -> Vec<FolderEntry> {
+        let prefix = format!(
+            "{}{}",
+            folder.trim_end_matches(MAIN_SEPARATOR),
+            MAIN_SEPARATOR
+        );
+        let mut content: Vec<FolderEntry> = self
+            .entries_under(&prefix)
+            .filter(|(path, _)| !path[prefix.len()..].contains(MAIN_SEPARATOR))
+            .map(|(_, entry)| entry.clone())
+            .collect();
+        content.sort();
+        content
+    }
+
+    /// Updates the index after files changed on disk. The whole workspace
+    /// is walked again when an ignore file changed.
+    pub fn apply(&mut self, events: &[FileEvent]) {
+        let changes_ignore_files = events.iter().any(|event| match event {
+            FileEvent::Created { path }
+            | FileEvent::Modified { path }
+            | FileEvent::Removed { path } => is_ignore_file(path),
+            FileEvent::Renamed { from, to } => is_ignore_file(from) || is_ignore_file(to),
+        });
+        if changes_ignore_files {
+            self.build();
+            return;
+        }
+
+        let mut created = vec![];
+        for event in events {
+            match event {
+                FileEvent::Created { path } => created.push(PathBuf::from(path)),
+                FileEvent::Modified { .. } => {}
+                FileEvent::Removed { path } => self.remove_tree(path),
+                FileEvent::Renamed { from, to } => {
+                    self.remove_tree(from);
+                    created.push(PathBuf::from(to));
+                }
+            }
+        }
+        self.add_created(created);
+    }
+
+    /// Returns a walker skipping the ignored and excluded entries
+    fn walker(&self, path: &Path) -> WalkBuilder {
+        let mut walker = WalkBuilder::new(path);
+        walker
+            .overrides(self.overrides.clone())
+            .hidden(self.options.exclude_hidden)
+            .require_git(false)
+            .filter_entry(|entry| entry.file_name() != ".git");
+        walker
+    }
+
+    fn insert(&mut self, entry: &DirEntry) {
+        let is_dir = entry
+            .file_type()
+            .map_or(false, |file_type| file_type.is_dir());
+        let folder_entry = FolderEntry::new(entry.path(), is_dir);
+        self.entries
+            .insert(folder_entry.path().to_string(), folder_entry);
+    }
+
+    /// Adds a file, or a folder with everything in it
+    fn add_tree(&mut self, path: &Path) {
+        let entries: Vec<DirEntry> = self
+            .walker(path)
+            .build()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path() != self.root)
+            .collect();
+        for entry in &entries {
+            self.insert(entry);
+        }
+    }
+
+    /// Adds the created files and folders that are not ignored or excluded.
+    /// The ignore rules only apply to the entries found while walking a
+    /// folder, so created entries are found by walking their parent.
+    fn add_created(&mut self, paths: Vec<PathBuf>) {
+        let mut created_by_parent: HashMap<PathBuf, HashSet<PathBuf>> = HashMap::new();
+        for path in paths {
+            // Files saved by the editor are created again
+            if self.contains(&path.to_string_lossy()) || !path.starts_with(&self.root) {
+                continue;
+            }
+            if let Some(parent) = path.parent() {
+                created_by_parent
+                    .entry(parent.to_path_buf())
+                    .or_default()
+                    .insert(path);
+            }
+        }
+
+        for (parent, created) in created_by_parent {
+            // Entries of an excluded folder stay excluded
+            if parent != self.root && !self.contains(&parent.to_string_lossy()) {
+                continue;
+            }
+            let children: Vec<DirEntry> = self
+                .walker(&parent)
+                .max_depth(Some(1))
+                .build()
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.depth() == 1 && created.contains(entry.path()))
+                .collect();
+            for child in children {
+                if child
+                    .file_type()
+                    .map_or(false, |file_type| file_type.is_dir())
+                {
+                    self.add_tree(child.path());
+                } else {
+                    self.insert(&child);
+                }
+            }
+        }
+    }
+
+    /// Removes a file, or a folder with everything in it
+    fn remove_tree(&mut self, path: &str) {
+        self.entries.remove(path);
+        let prefix = format!("{}{}", path, MAIN_SEPARATOR);
+        let nested: Vec<String> = self
+            .entries_under(&prefix)
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in nested {
+            self.entries.remove(&path);
+        }
+    }
+
+    /// Returns the entries whose path starts with the prefix
+    fn entries_under<'a>(
+        &'a self,
+        prefix: &'a str,
+    ) -> impl Iterator<Item = (&'a String, &'a FolderEntry)> {
+        self.entries
+            .range(prefix.to_string()..)
+            .take_while(move |(path, _)| path.starts_with(prefix))
+    }
+}
+
+fn is_ignore_file(path: &str) -> bool {
+    let name = Path::new(path).file_name().unwrap_or_default();
+    IGNORE_FILES.iter().any(|file| name == OsStr::new(file))
+}
@@ -0,0 +1,259 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use ignore::overrides::{Override, OverrideBuilder};
+use ignore::WalkBuilder;
+use regex::Regex;
+
+use crate::editor::state::SearchId;
+use crate::editor_io::encoding::{self, FileEncoding};
+use crate::editor_io::line_ending;
+use crate::error::PapyrusError;
+use crate::search::query::{self, SearchOptions};
+
+/// Longest preview of a matched line sent to the frontend, in chars
+const MAX_PREVIEW_LENGTH: usize = 250;
+
+/// Number of bytes checked for a NUL byte to decide if a file is binary
+const BINARY_CHECK_LENGTH: usize = 8000;
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct FindInFilesOptions {
+    pub query: String,
+    #[serde(flatten)]
+    pub search: SearchOptions,
+    /// Globs of the files to search, every file is searched if empty
+    pub include: Vec<String>,
+    /// Globs of the files to skip
+    pub exclude: Vec<String>,
+}
+
+#[derive(serde::Serialize, Debug, Clone, PartialEq, Eq)]
+/// Match found in a file. Columns are UTF-16 code units, like `Cursor`.
+pub struct FileMatch {
+    pub path: String,
+    pub row: usize,
+    pub start_column: usize,
+    pub end_column: usize,
+    pub preview: String,
+}
+
+#[derive(serde::Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchSummary {
+    pub files_searched: usize,
+    pub match_count: usize,
+    pub cancelled: bool,
+}
+
+#[derive(Clone, serde::Serialize)]
+struct SearchResultPayload {
+    search_id: SearchId,
+    matches: Vec<FileMatch>,
+}
+
+#[derive(Clone, serde::Serialize)]
+struct SearchFinishedPayload {
+    search_id: SearchId,
+    summary: SearchSummary,
+}
+
+/// Search of the files in a folder, skipping the files ignored by
+/// `.gitignore` and `.ignore` files and hidden files
+pub struct FileSearch {
+    root: PathBuf,
+    regex: Regex,
+    overrides: Override,
+}
+
+impl FileSearch {
+    /// Compiles the query and globs so invalid ones are reported before
+    /// the search starts
+    pub fn new(root: &str, options: &FindInFilesOptions) -> Result<Self, PapyrusError> {
+        let root = PathBuf::from(root);
+        if !root.is_dir() {
+            return Err(PapyrusError::Search(format!(
+                "{} is not a directory",
+                root.display()
+            )));
+        }
+
+        let mut overrides = OverrideBuilder::new(&root);
+        let globs = options
+            .include
+            .iter()
+            .cloned()
+            .chain(options.exclude.iter().map(|glob| format!("!{}", glob)));
+        for glob in globs {
+            overrides
+                .add(&glob)
+                .map_err(|err| PapyrusError::Search(err.to_string()))?;
+        }
+        let overrides = overrides
+            .build()
+            .map_err(|err| PapyrusError::Search(err.to_string()))?;
+
+        Ok(Self {
+            regex: options.search.to_regex(&options.query)?,
+            root,
+            overrides,
+        })
+    }
+
+    pub fn regex(&self) -> &Regex {
+        &self.regex
+    }
+
+    /// Returns the paths of the files to search. Entries that cannot be
+    /// read are skipped.
+    pub fn files(&self) -> impl Iterator<Item = PathBuf> {
+        WalkBuilder::new(&self.root)
+            .overrides(self.overrides.clone())
+            .require_git(false)
+            .build()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| {
+                entry
+                    .file_type()
+                    .map_or(false, |file_type| file_type.is_file())
+            })
+            .map(|entry| entry.into_path())
+    }
+
+    /// Searches every file, calling `on_matches` with the matches of each
+    /// file that has any. `open_buffers` maps the paths of open files to
+    /// their content, which is searched instead of the file. Files that
+    /// cannot be read are skipped and the search stops early once
+    /// `cancelled` is set.
+    pub fn run(
+        &self,
+        open_buffers: &HashMap<String, String>,
+        cancelled: &AtomicBool,
+        mut on_matches: impl FnMut(Vec<FileMatch>),
+    ) -> SearchSummary {
+        let mut summary = SearchSummary::default();
+        for path in self.files() {
+            if cancelled.load(Ordering::Relaxed) {
+                summary.cancelled = true;
+                break;
+            }
+
+            let path_string = path.to_string_lossy().to_string();
+            let matches = match open_buffers.get(&path_string) {
+                Some(text) => search_text(&path_string, text, &self.regex, cancelled),
+                None => match search_file(&path, &self.regex, cancelled) {
+                    Ok(matches) => matches,
+                    Err(_) => continue,
+                },
+            };
+            if cancelled.load(Ordering::Relaxed) {
+                summary.cancelled = true;
+                break;
+            }
+
+            summary.files_searched += 1;
+            if !matches.is_empty() {
+                summary.match_count += matches.len();
+                on_matches(matches);
+            }
+        }
+
+        summary
+    }
+}
+
+/// Decoded content of a text file
+pub struct TextFile {
+    pub text: String,
+    pub encoding: FileEncoding,
+    /// Whether the file had bytes that are malformed in its encoding,
+    /// which were replaced with U+FFFD
+    pub had_errors: bool,
+}
+
+/// Reads and decodes a text file with its detected encoding. Returns None
+/// for binary files.
+pub fn read_text_file(path: &Path) -> Result<Option<TextFile>, Box<dyn Error>> {
+    let bytes = fs::read(path)?;
+    let file_encoding = encoding::detect_encoding(&bytes);
+    let is_utf16 = file_encoding.encoding == encoding_rs::UTF_16LE
+        || file_encoding.encoding == encoding_rs::UTF_16BE;
+    if !is_utf16 && bytes[..bytes.len().min(BINARY_CHECK_LENGTH)].contains(&0) {
+        return Ok(None);
+    }
+    let (text, had_errors) = encoding::decode(&bytes, &file_encoding);
+    Ok(Some(TextFile {
+        text,
+        encoding: file_encoding,
+        had_errors,
+    }))
+}
+
+/// Returns the matches of the regex in the file. Binary files have no
+/// matches.
+pub fn search_file(
+    path: &Path,
+    regex: &Regex,
+    cancelled: &AtomicBool,
+) -> Result<Vec<FileMatch>, Box<dyn Error>> {
+    let text = match read_text_file(path)? {
+        Some(file) => line_ending::normalize_line_endings(file.text),
+        None => return Ok(vec![]),
+    };
+    Ok(search_text(
+        &path.to_string_lossy(),
+        &text,
+        regex,
+        cancelled,
+    ))
+}
+
+/// Returns the matches of the regex in text with `\n` line endings. Stops
+/// early once `cancelled` is set.
+pub fn search_text(
+    path: &str,
+    text: &str,
+    regex: &Regex,
+    cancelled: &AtomicBool,
+) -> Vec<FileMatch> {
+    let mut matches = vec![];
+    for (row, line) in text.split('\n').enumerate() {
+        if cancelled.load(Ordering::Relaxed) {
+            break;
+        }
+        for found in regex.find_iter(line) {
+            if found.start() == found.end() {
+                continue;
+            }
+            matches.push(FileMatch {
+                path: path.to_string(),
+                row,
+                start_column: query::utf16_column(line, found.start()),
+                end_column: query::utf16_column(line, found.end()),
+                preview: line.chars().take(MAX_PREVIEW_LENGTH).collect(),
+            });
+        }
+    }
+    matches
+}
+
+/// Runs the search, emitting the matches of each file as a
+/// `search_result` event and a `search_finished` event at the end
+pub fn emit_search_results(
+    window: &tauri::Window,
+    search_id: SearchId,
+    search: &FileSearch,
+    open_buffers: &HashMap<String, String>,
+    cancelled: &AtomicBool,
+) {
+    let summary = search.run(open_buffers, cancelled, |matches| {
+        let _ = window.emit("search_result", SearchResultPayload { search_id, matches });
+    });
+    let _ = window.emit(
+        "search_finished",
+        SearchFinishedPayload { search_id, summary },
+    );
+}
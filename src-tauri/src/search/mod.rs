@@ -0,0 +1,5 @@
+pub mod file_index;
+pub mod find_in_files;
+pub mod query;
+pub mod quick_open;
+pub mod replace_in_files;
@@ -0,0 +1,71 @@
+use std::ops::Range;
+
+use regex::{Regex, RegexBuilder};
+
+use crate::error::PapyrusError;
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+/// How a search query is matched
+pub struct SearchOptions {
+    pub is_regex: bool,
+    pub case_sensitive: bool,
+    pub whole_word: bool,
+}
+
+impl SearchOptions {
+    /// Compiles the query into a regex. Literal queries are escaped and
+    /// whole word queries only match between word boundaries.
+    pub fn to_regex(&self, query: &str) -> Result<Regex, PapyrusError> {
+        if query.is_empty() {
+            return Err(PapyrusError::Search("Search query is empty".into()));
+        }
+
+        let mut pattern = if self.is_regex {
+            query.to_string()
+        } else {
+            regex::escape(query)
+        };
+        if self.whole_word {
+            pattern = format!(r"\b(?:{})\b", pattern);
+        }
+
+        RegexBuilder::new(&pattern)
+            .case_insensitive(!self.case_sensitive)
+            .multi_line(true)
+            .build()
+            .map_err(|err| PapyrusError::Search(err.to_string()))
+    }
+}
+
+/// Converts a byte offset into a line into a column in UTF-16 code units
+pub fn utf16_column(line: &str, byte_idx: usize) -> usize {
+    line[..byte_idx].encode_utf16().count()
+}
+
+/// Returns the byte ranges of the non empty matches of the regex in the
+/// text and the text replacing each of them. Capture groups such as `$1`
+/// or `${name}` are expanded in regex replacements.
+pub fn find_replacements(
+    regex: &Regex,
+    options: &SearchOptions,
+    text: &str,
+    replacement: &str,
+) -> Vec<(Range<usize>, String)> {
+    regex
+        .captures_iter(text)
+        .filter_map(|captures| {
+            let found = captures.get(0)?;
+            if found.start() == found.end() {
+                return None;
+            }
+            let mut replaced = String::new();
+            if options.is_regex {
+                captures.expand(replacement, &mut replaced);
+            } else {
+                replaced.push_str(replacement);
+            }
+            Some((found.range(), replaced))
+        })
+        .collect()
+}
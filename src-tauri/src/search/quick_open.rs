@@ -0,0 +1,299 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap, VecDeque};
+use std::path::{Path, MAIN_SEPARATOR};
+
+use crate::editor_io::file_handling::FolderEntry;
+
+/// Number of recently opened files ranked higher
+const MAX_RECENT_FILES: usize = 50;
+
+const SCORE_MATCH: i64 = 16;
+const SCORE_GAP_START: i64 = -3;
+const SCORE_GAP_EXTENSION: i64 = -1;
+/// Bonus of a character starting a folder or file name
+const BONUS_BOUNDARY: i64 = 10;
+/// Bonus of a character following `_`, `-`, `.` or a space
+const BONUS_WORD: i64 = 8;
+const BONUS_CAMEL_CASE: i64 = 7;
+const BONUS_CONSECUTIVE: i64 = 8;
+/// Bonus of a match found entirely in the file name
+const BONUS_FILE_NAME: i64 = 16;
+/// Bonus of the most recently opened file, decreasing for older ones
+const BONUS_RECENT: i64 = 64;
+
+/// Files opened recently, most recent first
+#[derive(Debug, Default)]
+pub struct RecentFiles {
+    paths: VecDeque<String>,
+}
+
+impl RecentFiles {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Moves the path to the front, forgetting the oldest file if there are
+    /// too many
+    pub fn open(&mut self, path: &str) {
+        self.paths.retain(|recent| recent != path);
+        self.paths.push_front(path.to_string());
+        self.paths.truncate(MAX_RECENT_FILES);
+    }
+
+    /// Replaces the paths of a renamed file or of the files of a renamed
+    /// folder
+    pub fn rename(&mut self, from: &str, to: &str) {
+        for path in self.paths.iter_mut() {
+            if let Ok(rest) = Path::new(path.as_str()).strip_prefix(from) {
+                *path = if rest.as_os_str().is_empty() {
+                    to.to_string()
+                } else {
+                    Path::new(to).join(rest).to_string_lossy().to_string()
+                };
+            }
+        }
+    }
+
+    pub fn paths(&self) -> impl Iterator<Item = &String> {
+        self.paths.iter()
+    }
+
+    fn bonus(&self) -> HashMap<&str, i64> {
+        self.paths
+            .iter()
+            .enumerate()
+            .map(|(rank, path)| {
+                let bonus =
+                    BONUS_RECENT * (MAX_RECENT_FILES - rank) as i64 / MAX_RECENT_FILES as i64;
+                (path.as_str(), bonus)
+            })
+            .collect()
+    }
+}
+
+#[derive(serde::Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct QuickOpenMatch {
+    pub entry: FolderEntry,
+    /// Path relative to the workspace folder
+    pub relative_path: String,
+    /// Indices of the matched characters of the relative path, counted in
+    /// unicode code points
+    pub positions: Vec<usize>,
+    pub score: i64,
+}
+
+/// Candidate kept while looking for the best matches, ordered from the
+/// worst to the best match
+struct Ranked<'a> {
+    score: i64,
+    relative_path: &'a str,
+    entry: &'a FolderEntry,
+    positions: Vec<usize>,
+}
+
+impl Ranked<'_> {
+    fn key(&self) -> (i64, Reverse<usize>, Reverse<&str>) {
+        // Shorter paths, then paths sorting first, win ties
+        (
+            self.score,
+            Reverse(self.relative_path.len()),
+            Reverse(self.relative_path),
+        )
+    }
+}
+
+impl PartialEq for Ranked<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for Ranked<'_> {}
+
+impl PartialOrd for Ranked<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Ranked<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+/// Returns the files best matching the query, best first. The characters
+/// of the query must appear in order in the path of a file relative to the
+/// root, ignoring case unless the query has uppercase characters. Spaces
+/// in the query are ignored. Recently opened files are ranked higher.
+pub fn find_files<'a>(
+    root: &Path,
+    files: impl IntoIterator<Item = &'a FolderEntry>,
+    query: &str,
+    recent_files: &RecentFiles,
+    limit: usize,
+) -> Vec<QuickOpenMatch> {
+    if limit == 0 {
+        return vec![];
+    }
+    let case_sensitive = query.chars().any(char::is_uppercase);
+    let query: Vec<char> = query
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| fold_case(c, case_sensitive))
+        .collect();
+    let ascii_query = query.iter().all(char::is_ascii);
+    let root = root.to_string_lossy();
+    let recent_bonus = recent_files.bonus();
+
+    // Min heap of the best matches found so far
+    let mut best: BinaryHeap<Reverse<Ranked>> = BinaryHeap::with_capacity(limit + 1);
+    let mut chars = vec![];
+    let mut positions = vec![];
+    for entry in files {
+        let relative_path = match entry.path().strip_prefix(root.as_ref()) {
+            Some(rest) => rest.trim_start_matches(MAIN_SEPARATOR),
+            None => entry.path(),
+        };
+        // ASCII queries are matched against the bytes of the path, as
+        // bytes of other characters cannot match them
+        let mut score = if ascii_query {
+            let bytes = relative_path.as_bytes();
+            if !match_positions(&query, bytes, case_sensitive, &mut positions) {
+                continue;
+            }
+            score(bytes, &positions)
+        } else {
+            chars.clear();
+            chars.extend(relative_path.chars());
+            if !match_positions(&query, &chars, case_sensitive, &mut positions) {
+                continue;
+            }
+            score(&chars, &positions)
+        };
+
+        // Most files cannot make it into a full list of matches, even when
+        // opened recently
+        let worst = best.peek().filter(|_| best.len() == limit);
+        if worst.map_or(false, |Reverse(worst)| score + BONUS_RECENT < worst.score) {
+            continue;
+        }
+        score += recent_bonus.get(entry.path()).copied().unwrap_or_default();
+        let ranked = Ranked {
+            score,
+            relative_path,
+            entry,
+            positions: vec![],
+        };
+
+        if worst.map_or(true, |Reverse(worst)| ranked > *worst) {
+            if best.len() == limit {
+                best.pop();
+            }
+            best.push(Reverse(Ranked {
+                positions: positions.clone(),
+                ..ranked
+            }));
+        }
+    }
+
+    best.into_sorted_vec()
+        .into_iter()
+        .map(|Reverse(ranked)| {
+            let mut positions = ranked.positions;
+            if ascii_query && !ranked.relative_path.is_ascii() {
+                for position in positions.iter_mut() {
+                    *position = ranked.relative_path[..*position].chars().count();
+                }
+            }
+            QuickOpenMatch {
+                entry: ranked.entry.clone(),
+                relative_path: ranked.relative_path.to_string(),
+                positions,
+                score: ranked.score,
+            }
+        })
+        .collect()
+}
+
+fn fold_case(c: char, case_sensitive: bool) -> char {
+    if case_sensitive {
+        c
+    } else if c.is_ascii() {
+        c.to_ascii_lowercase()
+    } else {
+        c.to_lowercase().next().unwrap_or(c)
+    }
+}
+
+/// Finds the positions of the match found by matching the query
+/// backwards from the end of the path, which favors matches in the file
+/// name over matches in the folders holding it. Returns whether the
+/// characters of the query appear in order in the path.
+fn match_positions<C: Copy + Into<char>>(
+    query: &[char],
+    path: &[C],
+    case_sensitive: bool,
+    positions: &mut Vec<usize>,
+) -> bool {
+    positions.clear();
+    positions.resize(query.len(), 0);
+    let mut remaining = query.len();
+    for (i, &c) in path.iter().enumerate().rev() {
+        if remaining == 0 {
+            break;
+        }
+        if fold_case(c.into(), case_sensitive) == query[remaining - 1] {
+            remaining -= 1;
+            positions[remaining] = i;
+        }
+    }
+    remaining == 0
+}
+
+fn score<C: Copy + Into<char>>(path: &[C], positions: &[usize]) -> i64 {
+    let mut score = 0;
+    let mut previous: Option<usize> = None;
+    for &position in positions {
+        score += SCORE_MATCH + bonus(path, position);
+        match previous {
+            Some(previous) if previous + 1 == position => score += BONUS_CONSECUTIVE,
+            Some(previous) => {
+                score += SCORE_GAP_START + SCORE_GAP_EXTENSION * (position - previous - 2) as i64
+            }
+            None => {}
+        }
+        previous = Some(position);
+    }
+
+    let name_start = path
+        .iter()
+        .rposition(|&c| c.into() == MAIN_SEPARATOR)
+        .map_or(0, |separator| separator + 1);
+    if positions
+        .first()
+        .map_or(false, |&first| first >= name_start)
+    {
+        score += BONUS_FILE_NAME;
+    }
+    score
+}
+
+/// Bonus of matching the character at the position, for characters
+/// starting a name or a word
+fn bonus<C: Copy + Into<char>>(path: &[C], position: usize) -> i64 {
+    let previous: char = match position.checked_sub(1) {
+        Some(previous) => path[previous].into(),
+        None => return BONUS_BOUNDARY,
+    };
+    let current: char = path[position].into();
+    if previous == MAIN_SEPARATOR || previous == '/' {
+        BONUS_BOUNDARY
+    } else if matches!(previous, '_' | '-' | '.' | ' ') {
+        BONUS_WORD
+    } else if previous.is_lowercase() && current.is_uppercase() {
+        BONUS_CAMEL_CASE
+    } else {
+        0
+    }
+}
@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::Path;
+
+use regex::Regex;
+use similar::TextDiff;
+
+use crate::editor_io::encoding;
+use crate::editor_io::file_handling;
+use crate::error::PapyrusError;
+use crate::search::find_in_files::{self, FileSearch};
+use crate::search::query::{self, SearchOptions};
+
+/// Unchanged lines shown around each change in a diff preview
+const DIFF_CONTEXT_LINES: usize = 2;
+
+#[derive(serde::Serialize, Debug, Clone, PartialEq, Eq)]
+/// Replacements planned in a file, with a unified diff of the change
+pub struct FileReplacementPreview {
+    pub path: String,
+    pub replacement_count: usize,
+    pub diff: String,
+    /// Whether the file is open, in which case the buffer content is used
+    /// instead of the file on disk
+    pub is_open: bool,
+}
+
+#[derive(serde::Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct FileReplacementError {
+    pub path: String,
+    pub message: String,
+}
+
+#[derive(serde::Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReplaceInFilesSummary {
+    pub replacement_count: usize,
+    pub changed_files: Vec<String>,
+    pub errors: Vec<FileReplacementError>,
+}
+
+/// Returns the text with every match of the regex replaced and the number
+/// of replacements
+pub fn replace_text(
+    regex: &Regex,
+    options: &SearchOptions,
+    text: &str,
+    replacement: &str,
+) -> (String, usize) {
+    let replacements = query::find_replacements(regex, options, text, replacement);
+    let mut replaced = String::with_capacity(text.len());
+    let mut last_end = 0;
+    for (range, replacement_text) in &replacements {
+        replaced.push_str(&text[last_end..range.start]);
+        replaced.push_str(replacement_text);
+        last_end = range.end;
+    }
+    replaced.push_str(&text[last_end..]);
+    (replaced, replacements.len())
+}
+
+/// Computes the replacements in every file of the search. `open_buffers`
+/// maps the paths of open buffers to their content.
+pub fn preview_replacements(
+    search: &FileSearch,
+    options: &SearchOptions,
+    replacement: &str,
+    open_buffers: &HashMap<String, String>,
+) -> Vec<FileReplacementPreview> {
+    search
+        .files()
+        .filter_map(|path| {
+            let path = path.to_string_lossy().to_string();
+            let (text, is_open) = match open_buffers.get(&path) {
+                Some(content) => (content.clone(), true),
+                None => (
+                    find_in_files::read_text_file(Path::new(&path)).ok()??.text,
+                    false,
+                ),
+            };
+
+            let (replaced, replacement_count) =
+                replace_text(search.regex(), options, &text, replacement);
+            if replacement_count == 0 {
+                return None;
+            }
+            let diff = TextDiff::from_lines(&text, &replaced)
+                .unified_diff()
+                .context_radius(DIFF_CONTEXT_LINES)
+                .header(&path, &path)
+                .to_string();
+
+            Some(FileReplacementPreview {
+                path,
+                replacement_count,
+                diff,
+                is_open,
+            })
+        })
+        .collect()
+}
+
+/// Replaces every match in a file on disk and rewrites it atomically in
+/// its encoding. Returns the number of replacements. Files with bytes that
+/// are malformed in their encoding are left unchanged, as rewriting them
+/// would replace those bytes.
+pub fn replace_in_file(
+    path: &str,
+    regex: &Regex,
+    options: &SearchOptions,
+    replacement: &str,
+) -> Result<usize, Box<dyn Error>> {
+    let file = match find_in_files::read_text_file(Path::new(path))? {
+        Some(file) => file,
+        None => return Ok(0),
+    };
+    if file.had_errors {
+        return Err(PapyrusError::MalformedContent(path.to_string()).into());
+    }
+
+    let (replaced, replacement_count) = replace_text(regex, options, &file.text, replacement);
+    if replacement_count > 0 {
+        let bytes = encoding::encode(&replaced, &file.encoding)?;
+        file_handling::write_file_atomically(path, &bytes)?;
+    }
+    Ok(replacement_count)
+}
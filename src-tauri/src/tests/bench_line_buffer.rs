@@ -0,0 +1,141 @@
+//! Timing comparison between the rope backed `LineTextBuffer` and the
+//! previous `Vec<String>` storage.
+//!
+//! Run with `cargo test --release bench_ -- --ignored --nocapture`
+
+use std::time::{Duration, Instant};
+
+use crate::editor::text_buffer::{Cursor, LineTextBuffer, Selection};
+
+const LINES: usize = 50_000;
+const ITERATIONS: usize = 200;
+
+/// Previous line based storage, kept only as a baseline for the benchmarks
+struct VecLineBuffer {
+    lines: Vec<String>,
+}
+
+impl VecLineBuffer {
+    fn new(initial_text: &str) -> Self {
+        let mut lines: Vec<String> = initial_text.lines().map(String::from).collect();
+        if initial_text.ends_with('\n') {
+            lines.push("".into());
+        }
+        Self { lines }
+    }
+
+    fn insert_text(&mut self, text: &str, cursor: &Cursor) {
+        let current_line = self.lines[cursor.row].clone();
+        let mut text_iter = text.split('\n');
+        let (s1, s2) = current_line.split_at(cursor.column);
+        let mut s1 = s1.to_string();
+        s1.push_str(text_iter.next().unwrap());
+        self.lines[cursor.row] = s1;
+        let mut row = cursor.row;
+        for i in text_iter {
+            row += 1;
+            self.lines.insert(row, i.to_owned());
+        }
+        self.lines[row].push_str(s2);
+    }
+
+    fn remove_text(&mut self, selection: &Selection) {
+        let second = self.lines[selection.end.row]
+            .split_at(selection.end.column)
+            .1
+            .to_owned();
+        self.lines
+            .drain(selection.start.row + 1..=selection.end.row);
+        self.lines[selection.start.row].truncate(selection.start.column);
+        self.lines[selection.start.row].push_str(&second);
+    }
+}
+
+fn generated_file() -> String {
+    (0..LINES)
+        .map(|i| format!("    value_{} = compute(value_{}, {})\n", i, i, i * 7))
+        .collect()
+}
+
+fn paste_block() -> String {
+    (0..50).map(|i| format!("pasted line {}\n", i)).collect()
+}
+
+fn region(row: usize) -> Selection {
+    Selection {
+        start: Cursor { row, column: 4 },
+        end: Cursor {
+            row: row + 50,
+            column: 4,
+        },
+    }
+}
+
+fn report(name: &str, rope: Duration, vec: Duration) {
+    println!(
+        "{}: rope {:?} / vec {:?} per op ({} lines)",
+        name,
+        rope / ITERATIONS as u32,
+        vec / ITERATIONS as u32,
+        LINES
+    );
+}
+
+#[test]
+#[ignore]
+fn bench_paste_block() {
+    let content = generated_file();
+    let block = paste_block();
+    let mut rope_buffer = LineTextBuffer::new(content.clone());
+    let mut vec_buffer = VecLineBuffer::new(&content);
+
+    let start = Instant::now();
+    for i in 0..ITERATIONS {
+        let _ = rope_buffer.insert_text_no_log(
+            &block,
+            &Cursor {
+                row: i * 10,
+                column: 4,
+            },
+        );
+    }
+    let rope = start.elapsed();
+
+    let start = Instant::now();
+    for i in 0..ITERATIONS {
+        vec_buffer.insert_text(
+            &block,
+            &Cursor {
+                row: i * 10,
+                column: 4,
+            },
+        );
+    }
+    let vec = start.elapsed();
+
+    assert_eq!(rope_buffer.get_lines(), vec_buffer.lines);
+    report("paste block", rope, vec);
+}
+
+#[test]
+#[ignore]
+fn bench_delete_region() {
+    let content = generated_file();
+    let mut rope_buffer = LineTextBuffer::new(content.clone());
+    let mut vec_buffer = VecLineBuffer::new(&content);
+
+    let start = Instant::now();
+    for i in 0..ITERATIONS {
+        let _ = rope_buffer.remove_text_no_log(&region(i * 10));
+    }
+    let rope = start.elapsed();
+
+    let start = Instant::now();
+    for i in 0..ITERATIONS {
+        vec_buffer.remove_text(&region(i * 10));
+    }
+    let vec = start.elapsed();
+
+    assert_eq!(rope_buffer.get_lines(), vec_buffer.lines);
+    report("delete region", rope, vec);
+}
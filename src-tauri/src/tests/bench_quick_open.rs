@@ -0,0 +1,79 @@
+//! Timing of quick open over a large workspace, which must stay under
+//! 10 ms per query in release builds.
+//!
+//! Run with `cargo test --release bench_ -- --ignored --nocapture`
+
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::editor_io::file_handling::FolderEntry;
+use crate::search::quick_open::{find_files, RecentFiles};
+
+const FILES: usize = 100_000;
+const LIMIT: usize = 50;
+/// Runs of each query, the fastest one is checked against the budget
+const RUNS: usize = 5;
+/// Time allowed per query. Debug builds are not optimized, so they only
+/// get a looser budget.
+const BUDGET: Duration = Duration::from_millis(if cfg!(debug_assertions) { 200 } else { 10 });
+
+/// Paths spread over nested folders like the files of a large repository
+fn generated_files(root: &Path) -> Vec<FolderEntry> {
+    const FOLDERS: [&str; 8] = [
+        "src",
+        "lib",
+        "tests",
+        "docs",
+        "components",
+        "utils",
+        "assets",
+        "vendor",
+    ];
+    const EXTENSIONS: [&str; 5] = ["rs", "ts", "vue", "md", "json"];
+    (0..FILES)
+        .map(|i| {
+            let path = root
+                .join(FOLDERS[i % FOLDERS.len()])
+                .join(FOLDERS[i / 7 % FOLDERS.len()])
+                .join(format!("module_{}", i / 100))
+                .join(format!(
+                    "file{}_item.{}",
+                    i,
+                    EXTENSIONS[i % EXTENSIONS.len()]
+                ));
+            FolderEntry::new(&path, false)
+        })
+        .collect()
+}
+
+#[test]
+#[ignore]
+fn bench_quick_open() {
+    let root = Path::new("/workspace");
+    let files = generated_files(root);
+    let mut recent_files = RecentFiles::new();
+    for entry in files.iter().step_by(FILES / 50) {
+        recent_files.open(entry.path());
+    }
+
+    for query in ["f", "src", "comp util", "mod12item", "file99999", "zzz"] {
+        let mut fastest = Duration::MAX;
+        let mut match_count = 0;
+        for _ in 0..RUNS {
+            let start = Instant::now();
+            match_count = find_files(root, &files, query, &recent_files, LIMIT).len();
+            fastest = fastest.min(start.elapsed());
+        }
+        println!("{:<10} {:>3} matches  {:?}", query, match_count, fastest);
+        assert!(
+            fastest < BUDGET,
+            "{:?} took {:?}, over the budget of {:?}",
+            query,
+            fastest,
+            BUDGET
+        );
+    }
+
+    let matches = find_files(root, &files, "file99999", &recent_files, LIMIT);
+    assert_eq!(matches[0].entry.name(), "file99999_item.json");
+}
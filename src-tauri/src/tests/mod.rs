@@ -1,2 +1,48 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+mod bench_line_buffer;
+mod bench_quick_open;
+mod test_dirty;
+mod test_editor_state;
+mod test_encoding;
+mod test_error;
 mod test_file_handling;
+mod test_file_index;
+mod test_file_operations;
+mod test_find_in_files;
+mod test_find_replace;
+mod test_highlight;
+mod test_language;
 mod test_line_buffer;
+mod test_line_ending;
+mod test_multi_cursor;
+mod test_quick_open;
+mod test_recovery;
+mod test_replace_in_files;
+mod test_theme;
+mod test_undo;
+mod test_undo_history;
+mod test_watcher;
+
+/// Creates an empty folder in the temp dir holding the given files. The
+/// folder name is unique to the test process and call, so tests running
+/// in parallel never share a folder.
+pub fn create_temp_dir(name: &str, files: &[(&str, &[u8])]) -> PathBuf {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let root = std::env::temp_dir().join(format!(
+        "{}_{}_{}",
+        name,
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(&root).unwrap();
+    for (path, content) in files {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+    root
+}
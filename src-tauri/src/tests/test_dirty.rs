@@ -0,0 +1,90 @@
+use crate::editor::text_buffer::{Cursor, LineTextBuffer, Selection};
+use crate::editor_io::line_ending::{LineEnding, LineEndings};
+
+fn cursor(row: usize, column: usize) -> Cursor {
+    Cursor { row, column }
+}
+
+#[test]
+fn undoing_to_saved_state_is_clean() {
+    let mut buffer = LineTextBuffer::from_file("abc".into(), "file.txt".into());
+    assert!(!buffer.is_dirty());
+
+    buffer.insert_text("d".into(), cursor(0, 3));
+    assert!(buffer.is_dirty());
+    buffer.undo();
+    assert!(!buffer.is_dirty());
+    buffer.redo();
+    assert!(buffer.is_dirty());
+
+    buffer.mark_saved();
+    assert!(!buffer.is_dirty());
+    buffer.undo();
+    assert!(buffer.is_dirty());
+}
+
+#[test]
+fn typing_after_save_is_not_merged_into_saved_state() {
+    let mut buffer = LineTextBuffer::new("".into());
+    buffer.insert_text("a".into(), cursor(0, 0));
+    buffer.mark_saved();
+    buffer.insert_text("b".into(), cursor(0, 1));
+
+    assert!(buffer.is_dirty());
+    buffer.undo();
+    assert_eq!(buffer.get_content("\n".into()), "a");
+    assert!(!buffer.is_dirty());
+}
+
+#[test]
+fn content_hash_comparison_is_optional() {
+    let mut buffer = LineTextBuffer::new("abc".into());
+    buffer.insert_text("d".into(), cursor(0, 3));
+    buffer.remove_text(Selection {
+        start: cursor(0, 3),
+        end: cursor(0, 4),
+    });
+    assert!(buffer.is_dirty());
+
+    buffer.compare_content_hash = true;
+    assert!(!buffer.is_dirty());
+}
+
+#[test]
+fn changing_line_ending_is_dirty() {
+    let mut buffer = LineTextBuffer::new("a\nb\n".into());
+    buffer.line_endings = LineEndings {
+        line_ending: LineEnding::Crlf,
+        mixed: false,
+    };
+    assert!(buffer.is_dirty());
+
+    buffer.mark_saved();
+    assert!(!buffer.is_dirty());
+}
+
+#[test]
+fn dirty_changes_are_reported_once() {
+    let mut buffer = LineTextBuffer::new("".into());
+    assert_eq!(buffer.take_dirty_change(), None);
+
+    buffer.insert_text("a".into(), cursor(0, 0));
+    assert_eq!(buffer.take_dirty_change(), Some(true));
+    buffer.insert_text("b".into(), cursor(0, 1));
+    assert_eq!(buffer.take_dirty_change(), None);
+
+    buffer.mark_saved();
+    assert_eq!(buffer.take_dirty_change(), Some(false));
+}
+
+#[test]
+fn replacing_a_buffer_reports_it_clean() {
+    let mut buffer = LineTextBuffer::from_file("abc".into(), "file.txt".into());
+    buffer.insert_text("d".into(), cursor(0, 3));
+    assert_eq!(buffer.take_dirty_change(), Some(true));
+
+    buffer.replace_with(LineTextBuffer::from_file("abc".into(), "file.txt".into()));
+    assert_eq!(buffer.get_content("\n".into()), "abc");
+    assert!(buffer.history.is_empty());
+    assert_eq!(buffer.take_dirty_change(), Some(false));
+}
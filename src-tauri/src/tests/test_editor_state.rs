@@ -0,0 +1,46 @@
+use crate::editor::state::EditorState;
+use crate::editor::text_buffer::LineTextBuffer;
+
+#[test]
+fn buffer_ids_survive_closing_other_buffers() {
+    let mut editor_state = EditorState::new();
+    let first = editor_state.add_buffer(LineTextBuffer::new("first".into()));
+    let second = editor_state.add_buffer(LineTextBuffer::new("second".into()));
+    let third = editor_state.add_buffer(LineTextBuffer::new("third".into()));
+
+    assert!(editor_state.remove_buffer(first).is_some());
+    assert!(editor_state.get_buffer(first).is_err());
+    assert_eq!(
+        editor_state.get_buffer(second).unwrap().get_line(0),
+        "second"
+    );
+    assert_eq!(editor_state.get_buffer(third).unwrap().get_line(0), "third");
+    assert!(editor_state.remove_buffer(first).is_none());
+}
+
+#[test]
+fn buffer_ids_are_never_reused() {
+    let mut editor_state = EditorState::new();
+    let first = editor_state.add_buffer(LineTextBuffer::new("first".into()));
+    editor_state.remove_buffer(first);
+    let second = editor_state.add_buffer(LineTextBuffer::new("second".into()));
+
+    assert_ne!(first, second);
+    assert!(editor_state.get_buffer(first).is_err());
+}
+
+#[test]
+fn find_buffer_by_path() {
+    let mut editor_state = EditorState::new();
+    let buffer_id = editor_state.add_buffer(LineTextBuffer::from_file(
+        "content".into(),
+        "/tmp/file.txt".into(),
+    ));
+    editor_state.add_buffer(LineTextBuffer::new("untitled".into()));
+
+    assert_eq!(
+        editor_state.find_buffer_by_path("/tmp/file.txt"),
+        Some(buffer_id)
+    );
+    assert_eq!(editor_state.find_buffer_by_path("/tmp/other.txt"), None);
+}
@@ -0,0 +1,97 @@
+use std::fs;
+
+use crate::editor_io::encoding::{decode, detect_encoding, encode, FileEncoding};
+use crate::editor_io::file_handling::{override_file_content, read_file_content_with_encoding};
+
+fn round_trip(text: &str, label: &str, bom: bool) {
+    let encoding = FileEncoding::from_label(label, bom).unwrap();
+    let bytes = encode(text, &encoding).unwrap();
+    let detected = detect_encoding(&bytes);
+
+    assert_eq!(detected, encoding);
+    assert_eq!(decode(&bytes, &detected), (text.to_string(), false));
+}
+
+#[test]
+fn detects_byte_order_marks() {
+    round_trip("héllo wörld\n", "utf-8", true);
+    round_trip("héllo wörld 😀\n", "utf-16le", true);
+    round_trip("héllo wörld 😀\n", "utf-16be", true);
+}
+
+#[test]
+fn detects_utf16_without_bom() {
+    round_trip("plain ascii text\nwith two lines\n", "utf-16le", false);
+    round_trip("plain ascii text\nwith two lines\n", "utf-16be", false);
+}
+
+#[test]
+fn detects_legacy_encodings() {
+    let text = "Le cœur a ses raisons que la raison ne connaît point. \
+                Déjà vu, à la carte, crème brûlée, naïveté.\n";
+    round_trip(text, "windows-1252", false);
+
+    let text = "吾輩は猫である。名前はまだ無い。どこで生れたかとんと見当がつかぬ。\
+                何でも薄暗いじめじめした所でニャーニャー泣いていた事だけは記憶している。\n";
+    round_trip(text, "shift_jis", false);
+}
+
+#[test]
+fn utf8_is_the_default() {
+    assert_eq!(detect_encoding(b"hello\n"), FileEncoding::default());
+    assert_eq!(detect_encoding(b""), FileEncoding::default());
+}
+
+#[test]
+fn malformed_bytes_are_reported() {
+    let utf8 = FileEncoding::default();
+    assert_eq!(decode(b"ab\xFFc", &utf8), ("ab\u{FFFD}c".to_string(), true));
+
+    // Lone surrogate after a byte order mark
+    let utf16 = FileEncoding::from_label("utf-16le", true).unwrap();
+    let (text, had_errors) = decode(b"\xFF\xFEa\x00\x00\xD8b\x00", &utf16);
+    assert_eq!(text, "a\u{FFFD}b");
+    assert!(had_errors);
+}
+
+#[test]
+fn unencodable_characters_are_an_error() {
+    let encoding = FileEncoding::from_label("windows-1252", false).unwrap();
+
+    assert!(encode("世界", &encoding).is_err());
+}
+
+#[test]
+fn files_are_saved_in_their_encoding() {
+    let path = std::env::temp_dir().join("papyrus_test_encoding.txt");
+    let path = path.to_str().unwrap();
+    let encoding = FileEncoding::from_label("utf-16le", true).unwrap();
+
+    override_file_content(path, "Grüße\n".into(), &encoding).unwrap();
+    assert_eq!(&fs::read(path).unwrap()[..4], b"\xFF\xFEG\x00");
+
+    let (content, detected, _) = read_file_content_with_encoding(path, None).unwrap();
+    assert_eq!(content, "Grüße\n");
+    assert_eq!(detected, encoding);
+
+    // Reopening with another encoding decodes the same bytes differently
+    let latin1 = FileEncoding::from_label("latin1", false).unwrap();
+    let (content, reopened, _) = read_file_content_with_encoding(path, Some(latin1)).unwrap();
+    assert_eq!(reopened.name(), "windows-1252");
+    assert!(!reopened.bom);
+    assert!(content.starts_with("ÿþG"));
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn encodings_serialize_by_name() {
+    let encoding = FileEncoding::from_label("sjis", false).unwrap();
+    let value = serde_json::to_value(encoding).unwrap();
+
+    assert_eq!(value["name"], "Shift_JIS");
+    assert_eq!(
+        serde_json::from_value::<FileEncoding>(value).unwrap(),
+        encoding
+    );
+}
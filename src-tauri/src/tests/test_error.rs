@@ -0,0 +1,69 @@
+use std::io;
+
+use crate::editor::state::EditorState;
+use crate::editor::text_buffer::{Cursor, LineTextBuffer, Selection};
+use crate::editor_io::encoding::FileEncoding;
+use crate::editor_io::file_handling::{get_folder_content, read_file_content};
+use crate::error::PapyrusError;
+
+#[test]
+fn errors_serialize_with_kind_and_message() {
+    let error = serde_json::to_value(PapyrusError::InvalidRow(3)).unwrap();
+
+    assert_eq!(error["kind"], "InvalidRow");
+    assert_eq!(error["message"], "Invalid row 3");
+}
+
+#[test]
+fn missing_buffer_is_an_error() {
+    let mut editor_state = EditorState::new();
+    let buffer_id = editor_state.add_buffer(LineTextBuffer::new("".into()));
+    editor_state.remove_buffer(buffer_id);
+
+    assert!(matches!(
+        editor_state.get_buffer(buffer_id),
+        Err(PapyrusError::BufferNotFound(_))
+    ));
+}
+
+#[test]
+fn io_errors_keep_their_kind() {
+    let error: PapyrusError = read_file_content("./src/tests/missing.txt")
+        .unwrap_err()
+        .into();
+    assert!(matches!(error, PapyrusError::Io(ref err) if err.kind() == io::ErrorKind::NotFound));
+
+    let error: PapyrusError = FileEncoding::from_label("not-an-encoding", false)
+        .unwrap_err()
+        .into();
+    assert_eq!(error.kind(), "Encoding");
+}
+
+#[test]
+fn folder_content_of_a_file_is_an_error() {
+    let error: PapyrusError = get_folder_content("./src/tests/example.txt")
+        .unwrap_err()
+        .into();
+    assert_eq!(error.kind(), "Other");
+}
+
+#[test]
+fn cursors_and_selections_are_validated() {
+    let buffer = LineTextBuffer::new("h😀llo\nworld".into());
+
+    assert!(buffer.is_valid_cursor(&Cursor { row: 1, column: 5 }));
+    assert!(!buffer.is_valid_cursor(&Cursor { row: 1, column: 6 }));
+    assert!(!buffer.is_valid_cursor(&Cursor { row: 2, column: 0 }));
+    // Inside the surrogate pair of the emoji
+    assert!(!buffer.is_valid_cursor(&Cursor { row: 0, column: 2 }));
+    assert!(buffer.is_valid_cursor(&Cursor { row: 0, column: 3 }));
+
+    assert!(buffer.is_valid_selection(&Selection {
+        start: Cursor { row: 0, column: 1 },
+        end: Cursor { row: 1, column: 0 },
+    }));
+    assert!(!buffer.is_valid_selection(&Selection {
+        start: Cursor { row: 1, column: 0 },
+        end: Cursor { row: 0, column: 1 },
+    }));
+}
@@ -1,15 +1,105 @@
-use crate::editor_io::file_handling::read_file_content;
+use std::fs;
+use std::time::{Duration, SystemTime};
+
+use crate::editor_io::file_handling::{
+    check_unchanged, modified_time, read_file_content, read_file_content_with_encoding,
+    write_file_atomically, TEMP_FILE_SUFFIX,
+};
+use crate::error::PapyrusError;
+use crate::tests::create_temp_dir;
 
 #[test]
 fn test_file_exists() {
-    let content = read_file_content("./src/tests/example.txt".into()).unwrap();
+    let content = read_file_content("./src/tests/example.txt").unwrap();
 
     assert_eq!(content, "This\nis\na simple\nfile\n".to_string());
 }
 
 #[test]
 fn test_non_utf8_encoding() {
-    let content = read_file_content("./src/tests/non_standard_encoding.txt".into());
+    let (content, encoding, had_errors) =
+        read_file_content_with_encoding("./src/tests/non_standard_encoding.txt", None).unwrap();
+
+    assert_eq!(encoding.name(), "UTF-16LE");
+    assert!(encoding.bom);
+    assert!(!had_errors);
+    assert!(content.starts_with("This\r\nis\r\na simple\r\nfile"));
+}
+
+#[test]
+fn atomic_writes_replace_the_file() {
+    let root = create_temp_dir("papyrus_test_atomic_replace", &[]);
+    let path = root.join("file.txt");
+    fs::write(&path, "old content that is longer").unwrap();
+
+    write_file_atomically(path.to_str().unwrap(), b"new").unwrap();
+
+    assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+    assert_eq!(fs::read_dir(&root).unwrap().count(), 1);
+}
+
+#[cfg(unix)]
+#[test]
+fn atomic_writes_follow_symlinks() {
+    let root = create_temp_dir("papyrus_test_atomic_symlink", &[]);
+    fs::create_dir(root.join("real")).unwrap();
+    fs::write(root.join("real/file.txt"), "old").unwrap();
+    std::os::unix::fs::symlink("real/file.txt", root.join("link.txt")).unwrap();
+    std::os::unix::fs::symlink("missing.txt", root.join("dangling.txt")).unwrap();
+
+    write_file_atomically(root.join("link.txt").to_str().unwrap(), b"new").unwrap();
+    assert!(fs::symlink_metadata(root.join("link.txt"))
+        .unwrap()
+        .file_type()
+        .is_symlink());
+    assert_eq!(
+        fs::read_to_string(root.join("real/file.txt")).unwrap(),
+        "new"
+    );
+
+    write_file_atomically(root.join("dangling.txt").to_str().unwrap(), b"created").unwrap();
+    assert_eq!(
+        fs::read_to_string(root.join("missing.txt")).unwrap(),
+        "created"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn atomic_writes_never_write_through_existing_temp_files() {
+    let root = create_temp_dir(
+        "papyrus_test_atomic_planted",
+        &[("file.txt", b"old"), ("victim.txt", b"victim")],
+    );
+    let temp_name = format!(".file.txt{}", TEMP_FILE_SUFFIX);
+    std::os::unix::fs::symlink("victim.txt", root.join(temp_name)).unwrap();
+
+    write_file_atomically(root.join("file.txt").to_str().unwrap(), b"new").unwrap();
+
+    assert_eq!(fs::read_to_string(root.join("file.txt")).unwrap(), "new");
+    assert_eq!(
+        fs::read_to_string(root.join("victim.txt")).unwrap(),
+        "victim"
+    );
+}
+
+#[test]
+fn files_changed_since_loaded_are_not_overwritten() {
+    let root = create_temp_dir("papyrus_test_file_changed", &[]);
+    let path = root.join("file.txt");
+    let path = path.to_str().unwrap();
+    fs::write(path, "content").unwrap();
+    let loaded = modified_time(path);
+
+    assert!(check_unchanged(path, loaded).is_ok());
+    let error: PapyrusError = check_unchanged(path, Some(SystemTime::UNIX_EPOCH))
+        .unwrap_err()
+        .into();
+    assert_eq!(error.kind(), "FileChanged");
 
-    assert!(content.is_err());
+    // Buffers without a modification time, like restored ones, are checked
+    // too
+    assert!(check_unchanged(path, None).is_err());
+    fs::remove_file(path).unwrap();
+    assert!(check_unchanged(path, loaded.map(|time| time + Duration::from_secs(1))).is_ok());
 }
@@ -0,0 +1,247 @@
+use std::fs;
+use std::path::Path;
+
+use crate::editor::state::EditorState;
+use crate::editor_io::watcher::FileEvent;
+use crate::search::file_index::{FileIndex, IndexOptions};
+use crate::tests::create_temp_dir;
+
+fn path(root: &Path, path: &str) -> String {
+    root.join(path).to_string_lossy().to_string()
+}
+
+fn build_index(root: &Path, options: IndexOptions) -> FileIndex {
+    let mut file_index = FileIndex::new(&root.to_string_lossy(), options).unwrap();
+    file_index.build();
+    file_index
+}
+
+/// Returns the indexed files relative to the root
+fn indexed_files(file_index: &FileIndex) -> Vec<String> {
+    file_index
+        .files()
+        .map(|entry| {
+            Path::new(entry.path())
+                .strip_prefix(file_index.root())
+                .unwrap()
+                .to_string_lossy()
+                .replace('\\', "/")
+        })
+        .collect()
+}
+
+#[test]
+fn index_respects_ignore_files() {
+    let root = create_temp_dir(
+        "papyrus_test_index_ignore",
+        &[
+            (".gitignore", b"target/\n*.log\n"),
+            ("src/main.rs", b"main"),
+            ("src/.ignore", b"generated.rs\n"),
+            ("src/generated.rs", b"generated"),
+            ("target/debug/app", b"app"),
+            ("build.log", b"log"),
+            (".git/HEAD", b"ref"),
+        ],
+    );
+
+    let file_index = build_index(&root, IndexOptions::default());
+    assert_eq!(
+        indexed_files(&file_index),
+        vec![".gitignore", "src/.ignore", "src/main.rs"]
+    );
+    assert!(!file_index.contains(&path(&root, "target")));
+    assert!(!file_index.contains(&path(&root, ".git")));
+}
+
+#[test]
+fn index_skips_excluded_and_hidden_files() {
+    let root = create_temp_dir(
+        "papyrus_test_index_exclude",
+        &[
+            ("src/main.rs", b"main"),
+            ("node_modules/lib/index.js", b"lib"),
+            ("docs/guide.md", b"guide"),
+            (".env", b"secret"),
+        ],
+    );
+
+    let file_index = build_index(
+        &root,
+        IndexOptions {
+            exclude: vec!["node_modules/".into(), "*.md".into()],
+            exclude_hidden: true,
+        },
+    );
+    assert_eq!(indexed_files(&file_index), vec!["src/main.rs"]);
+    assert!(file_index.contains(&path(&root, "docs")));
+
+    let file_index = build_index(&root, IndexOptions::default());
+    assert_eq!(
+        indexed_files(&file_index),
+        vec![
+            ".env",
+            "docs/guide.md",
+            "node_modules/lib/index.js",
+            "src/main.rs"
+        ]
+    );
+
+    assert!(FileIndex::new(
+        &root.to_string_lossy(),
+        IndexOptions {
+            exclude: vec!["{".into()],
+            ..Default::default()
+        }
+    )
+    .is_err());
+    assert!(FileIndex::new(&path(&root, "missing"), IndexOptions::default()).is_err());
+}
+
+#[test]
+fn index_lists_folder_content() {
+    let root = create_temp_dir(
+        "papyrus_test_index_folder",
+        &[
+            (".gitignore", b"*.log\n"),
+            ("b.txt", b"b"),
+            ("a.log", b"a"),
+            ("src/main.rs", b"main"),
+            ("src/nested/mod.rs", b"mod"),
+        ],
+    );
+
+    let file_index = build_index(&root, IndexOptions::default());
+    let names: Vec<String> = file_index
+        .folder_content(&root.to_string_lossy())
+        .iter()
+        .map(|entry| entry.name().to_string())
+        .collect();
+    assert_eq!(names, vec!["src", ".gitignore", "b.txt"]);
+
+    let names: Vec<String> = file_index
+        .folder_content(&path(&root, "src"))
+        .iter()
+        .map(|entry| entry.name().to_string())
+        .collect();
+    assert_eq!(names, vec!["nested", "main.rs"]);
+}
+
+#[test]
+fn index_is_queried_with_globs() {
+    let root = create_temp_dir(
+        "papyrus_test_index_query",
+        &[
+            ("src/main.rs", b"main"),
+            ("src/lib.rs", b"lib"),
+            ("README.md", b"readme"),
+        ],
+    );
+
+    let file_index = build_index(&root, IndexOptions::default());
+    assert_eq!(file_index.query(&[]).unwrap().len(), 3);
+
+    let files: Vec<&str> = file_index
+        .query(&["*.rs".into()])
+        .unwrap()
+        .iter()
+        .map(|entry| entry.name())
+        .collect();
+    assert_eq!(files, vec!["lib.rs", "main.rs"]);
+
+    assert!(file_index.query(&["{".into()]).is_err());
+}
+
+#[test]
+fn index_applies_file_events() {
+    let root = create_temp_dir(
+        "papyrus_test_index_events",
+        &[
+            (".gitignore", b"*.log\n"),
+            ("a.txt", b"a"),
+            ("src/main.rs", b"main"),
+        ],
+    );
+    let mut file_index = build_index(&root, IndexOptions::default());
+
+    fs::write(root.join("b.txt"), "b").unwrap();
+    fs::write(root.join("b.log"), "b").unwrap();
+    fs::create_dir_all(root.join("lib/nested")).unwrap();
+    fs::write(root.join("lib/nested/mod.rs"), "mod").unwrap();
+    file_index.apply(&[
+        FileEvent::Created {
+            path: path(&root, "b.txt"),
+        },
+        FileEvent::Created {
+            path: path(&root, "b.log"),
+        },
+        FileEvent::Created {
+            path: path(&root, "lib"),
+        },
+    ]);
+    assert_eq!(
+        indexed_files(&file_index),
+        vec![
+            ".gitignore",
+            "a.txt",
+            "b.txt",
+            "lib/nested/mod.rs",
+            "src/main.rs"
+        ]
+    );
+
+    fs::rename(root.join("src"), root.join("app")).unwrap();
+    fs::remove_file(root.join("a.txt")).unwrap();
+    file_index.apply(&[
+        FileEvent::Renamed {
+            from: path(&root, "src"),
+            to: path(&root, "app"),
+        },
+        FileEvent::Removed {
+            path: path(&root, "a.txt"),
+        },
+    ]);
+    assert_eq!(
+        indexed_files(&file_index),
+        vec![".gitignore", "app/main.rs", "b.txt", "lib/nested/mod.rs"]
+    );
+    assert!(!file_index.contains(&path(&root, "src")));
+}
+
+#[test]
+fn index_is_rebuilt_when_ignore_files_change() {
+    let root = create_temp_dir(
+        "papyrus_test_index_rebuild",
+        &[("a.txt", b"a"), ("b.log", b"b")],
+    );
+    let mut file_index = build_index(&root, IndexOptions::default());
+    assert_eq!(indexed_files(&file_index), vec!["a.txt", "b.log"]);
+
+    fs::write(root.join(".gitignore"), "*.log\n").unwrap();
+    file_index.apply(&[FileEvent::Created {
+        path: path(&root, ".gitignore"),
+    }]);
+    assert_eq!(indexed_files(&file_index), vec![".gitignore", "a.txt"]);
+}
+
+#[test]
+fn index_keeps_events_seen_while_building() {
+    let root = create_temp_dir("papyrus_test_index_pending", &[("a.txt", b"a")]);
+    let mut editor_state = EditorState::new();
+
+    let replaced = editor_state.start_indexing();
+    let generation = editor_state.start_indexing();
+    assert!(!editor_state.finish_indexing(replaced, build_index(&root, IndexOptions::default())));
+    assert!(editor_state.file_index.is_none());
+
+    let file_index = build_index(&root, IndexOptions::default());
+    fs::write(root.join("b.txt"), "b").unwrap();
+    editor_state.apply_file_events(&[FileEvent::Created {
+        path: path(&root, "b.txt"),
+    }]);
+    assert!(editor_state.finish_indexing(generation, file_index));
+    assert_eq!(
+        indexed_files(editor_state.file_index.as_ref().unwrap()),
+        vec!["a.txt", "b.txt"]
+    );
+}
@@ -0,0 +1,183 @@
+use std::fs;
+use std::path::Path;
+
+use crate::editor::state::EditorState;
+use crate::editor::text_buffer::LineTextBuffer;
+use crate::editor_io::file_handling::{copy_path, remove_path, rename_path};
+use crate::error::PapyrusError;
+use crate::tests::create_temp_dir;
+
+fn path(root: &Path, path: &str) -> String {
+    root.join(path).to_string_lossy().to_string()
+}
+
+#[test]
+fn renaming_moves_files_and_folders() {
+    let root = create_temp_dir(
+        "papyrus_test_rename",
+        &[("a.txt", b"a"), ("src/main.rs", b"main")],
+    );
+
+    rename_path(&path(&root, "a.txt"), &path(&root, "b.txt")).unwrap();
+    assert!(!root.join("a.txt").exists());
+    assert_eq!(fs::read_to_string(root.join("b.txt")).unwrap(), "a");
+
+    rename_path(&path(&root, "src"), &path(&root, "lib")).unwrap();
+    assert_eq!(
+        fs::read_to_string(root.join("lib/main.rs")).unwrap(),
+        "main"
+    );
+}
+
+#[test]
+fn renaming_does_not_overwrite() {
+    let root = create_temp_dir(
+        "papyrus_test_rename_existing",
+        &[("a.txt", b"a"), ("b.txt", b"b"), ("src/main.rs", b"main")],
+    );
+
+    let error: PapyrusError = rename_path(&path(&root, "a.txt"), &path(&root, "b.txt"))
+        .unwrap_err()
+        .into();
+    assert_eq!(error.kind(), "PathExists");
+    assert_eq!(fs::read_to_string(root.join("b.txt")).unwrap(), "b");
+
+    let error: PapyrusError = rename_path(&path(&root, "c.txt"), &path(&root, "d.txt"))
+        .unwrap_err()
+        .into();
+    assert_eq!(error.kind(), "PathNotFound");
+
+    assert!(rename_path(&path(&root, "src"), &path(&root, "src/nested")).is_err());
+    assert!(root.join("src/main.rs").exists());
+}
+
+// Needs a case sensitive filesystem to create names differing by case
+#[cfg(target_os = "linux")]
+#[test]
+fn renaming_allows_case_only_changes() {
+    let root = create_temp_dir(
+        "papyrus_test_rename_case",
+        &[
+            ("readme.md", b"readme"),
+            ("notes.md", b"notes"),
+            ("NOTES.md", b"other"),
+        ],
+    );
+    // Hard links stand in for a case insensitive filesystem, where both
+    // names find the same file
+    fs::hard_link(root.join("readme.md"), root.join("README.md")).unwrap();
+    fs::hard_link(root.join("readme.md"), root.join("copy.md")).unwrap();
+
+    assert!(rename_path(&path(&root, "readme.md"), &path(&root, "README.md")).is_ok());
+
+    let error: PapyrusError = rename_path(&path(&root, "notes.md"), &path(&root, "NOTES.md"))
+        .unwrap_err()
+        .into();
+    assert_eq!(error.kind(), "PathExists");
+    let error: PapyrusError = rename_path(&path(&root, "README.md"), &path(&root, "copy.md"))
+        .unwrap_err()
+        .into();
+    assert_eq!(error.kind(), "PathExists");
+}
+
+#[test]
+fn folders_are_copied_recursively() {
+    let root = create_temp_dir(
+        "papyrus_test_copy",
+        &[("src/main.rs", b"main"), ("src/nested/lib.rs", b"lib")],
+    );
+    #[cfg(unix)]
+    std::os::unix::fs::symlink("main.rs", root.join("src/link.rs")).unwrap();
+
+    copy_path(&path(&root, "src"), &path(&root, "copy")).unwrap();
+
+    assert_eq!(
+        fs::read_to_string(root.join("copy/main.rs")).unwrap(),
+        "main"
+    );
+    assert_eq!(
+        fs::read_to_string(root.join("copy/nested/lib.rs")).unwrap(),
+        "lib"
+    );
+    assert!(root.join("src/main.rs").exists());
+    #[cfg(unix)]
+    assert_eq!(
+        fs::read_link(root.join("copy/link.rs")).unwrap(),
+        Path::new("main.rs")
+    );
+
+    let error: PapyrusError = copy_path(&path(&root, "src/main.rs"), &path(&root, "copy/main.rs"))
+        .unwrap_err()
+        .into();
+    assert_eq!(error.kind(), "PathExists");
+}
+
+#[test]
+fn removing_deletes_permanently() {
+    let root = create_temp_dir(
+        "papyrus_test_remove",
+        &[("a.txt", b"a"), ("src/main.rs", b"main")],
+    );
+
+    remove_path(&path(&root, "a.txt")).unwrap();
+    remove_path(&path(&root, "src")).unwrap();
+
+    assert_eq!(fs::read_dir(&root).unwrap().count(), 0);
+    assert!(remove_path(&path(&root, "a.txt")).is_err());
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn trashed_items_record_their_origin() {
+    use crate::editor_io::trash::move_to_trash_dir;
+
+    let root = create_temp_dir(
+        "papyrus_test_trash",
+        &[("a b.txt", b"first"), ("other/a b.txt", b"second")],
+    );
+    let trash_dir = root.join("Trash");
+    fs::create_dir_all(trash_dir.join("files")).unwrap();
+    fs::create_dir_all(trash_dir.join("info")).unwrap();
+
+    move_to_trash_dir(&root.join("a b.txt"), &trash_dir).unwrap();
+    move_to_trash_dir(&root.join("other/a b.txt"), &trash_dir).unwrap();
+
+    assert!(!root.join("a b.txt").exists());
+    assert_eq!(
+        fs::read_to_string(trash_dir.join("files/a b.txt")).unwrap(),
+        "first"
+    );
+    assert_eq!(
+        fs::read_to_string(trash_dir.join("files/a b.txt.2")).unwrap(),
+        "second"
+    );
+
+    let info = fs::read_to_string(trash_dir.join("info/a b.txt.2.trashinfo")).unwrap();
+    let mut lines = info.lines();
+    assert_eq!(lines.next(), Some("[Trash Info]"));
+    assert_eq!(
+        lines.next().unwrap(),
+        format!("Path={}/other/a%20b.txt", root.display())
+    );
+    assert!(lines.next().unwrap().starts_with("DeletionDate="));
+}
+
+#[test]
+fn renaming_updates_open_buffers() {
+    let mut editor_state = EditorState::new();
+    let file = editor_state.add_buffer(LineTextBuffer::from_file(
+        "".into(),
+        "/ws/src/main.rs".into(),
+    ));
+    let other = editor_state.add_buffer(LineTextBuffer::from_file(
+        "".into(),
+        "/ws/src2/lib.rs".into(),
+    ));
+
+    let renamed = editor_state.rename_buffer_paths("/ws/src", "/ws/lib");
+    assert_eq!(renamed, vec![(file, "/ws/lib/main.rs".to_string())]);
+
+    let renamed = editor_state.rename_buffer_paths("/ws/src2/lib.rs", "/ws/lib.rs");
+    assert_eq!(renamed, vec![(other, "/ws/lib.rs".to_string())]);
+    assert_eq!(editor_state.find_buffer_by_path("/ws/lib.rs"), Some(other));
+}
@@ -0,0 +1,179 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+
+use crate::search::find_in_files::{search_text, FileMatch, FileSearch, FindInFilesOptions};
+use crate::tests::create_temp_dir;
+
+fn find(root: &Path, options: FindInFilesOptions) -> Vec<FileMatch> {
+    let search = FileSearch::new(root.to_str().unwrap(), &options).unwrap();
+    let mut matches = vec![];
+    search.run(&HashMap::new(), &AtomicBool::new(false), |file_matches| {
+        matches.extend(file_matches)
+    });
+    matches.sort_by(|a, b| (&a.path, a.row).cmp(&(&b.path, b.row)));
+    matches
+}
+
+fn query(query: &str) -> FindInFilesOptions {
+    FindInFilesOptions {
+        query: query.into(),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn finds_literal_matches() {
+    let root = create_temp_dir(
+        "papyrus_test_find_literal",
+        &[
+            ("a.txt", b"foo bar\nbar (foo)\n"),
+            ("b.txt", b"nothing\r\nFoo\r\n"),
+        ],
+    );
+    let matches = find(&root, query("foo"));
+
+    let found: Vec<_> = matches
+        .iter()
+        .map(|found| (found.row, found.start_column, found.end_column))
+        .collect();
+    assert_eq!(found, vec![(0, 0, 3), (1, 5, 8), (1, 0, 3)]);
+    assert_eq!(matches[2].preview, "Foo");
+}
+
+#[test]
+fn regex_case_and_whole_word_options() {
+    let root = create_temp_dir(
+        "papyrus_test_find_options",
+        &[("a.txt", b"fooBar foo food Foo\n")],
+    );
+    let columns = |options: FindInFilesOptions| -> Vec<usize> {
+        find(&root, options)
+            .iter()
+            .map(|found| found.start_column)
+            .collect()
+    };
+
+    let mut options = query("foo");
+    options.search.case_sensitive = true;
+    assert_eq!(columns(options.clone()), vec![0, 7, 11]);
+    options.search.whole_word = true;
+    assert_eq!(columns(options), vec![7]);
+
+    let mut options = query(r"f[aeiou]+d");
+    options.search.is_regex = true;
+    assert_eq!(columns(options), vec![11]);
+}
+
+#[test]
+fn respects_ignore_files_and_globs() {
+    let root = create_temp_dir(
+        "papyrus_test_find_ignored",
+        &[
+            (".gitignore", b"target/\n*.log\n"),
+            ("src/main.rs", b"needle\n"),
+            ("src/lib.rs", b"needle\n"),
+            ("notes.md", b"needle\n"),
+            ("debug.log", b"needle\n"),
+            ("target/out.rs", b"needle\n"),
+        ],
+    );
+    let paths = |include: Vec<String>, exclude: Vec<String>| -> Vec<String> {
+        find(
+            &root,
+            FindInFilesOptions {
+                include,
+                exclude,
+                ..query("needle")
+            },
+        )
+        .into_iter()
+        .map(|found| {
+            PathBuf::from(found.path)
+                .strip_prefix(&root)
+                .unwrap()
+                .to_string_lossy()
+                .replace('\\', "/")
+        })
+        .collect()
+    };
+
+    assert_eq!(
+        paths(vec![], vec![]),
+        vec!["notes.md", "src/lib.rs", "src/main.rs"]
+    );
+    assert_eq!(
+        paths(vec!["*.rs".into()], vec![]),
+        vec!["src/lib.rs", "src/main.rs"]
+    );
+    assert_eq!(
+        paths(vec![], vec!["lib.rs".into()]),
+        vec!["notes.md", "src/main.rs"]
+    );
+}
+
+#[test]
+fn columns_are_utf16_code_units() {
+    let root = create_temp_dir(
+        "papyrus_test_find_utf16",
+        &[("a.txt", "😀é needle\n".as_bytes())],
+    );
+    let matches = find(&root, query("needle"));
+
+    assert_eq!((matches[0].start_column, matches[0].end_column), (4, 10));
+}
+
+#[test]
+fn cancelled_search_stops() {
+    let root = create_temp_dir("papyrus_test_find_cancel", &[("a.txt", b"needle\n")]);
+    let search = FileSearch::new(root.to_str().unwrap(), &query("needle")).unwrap();
+    let summary = search.run(&HashMap::new(), &AtomicBool::new(true), |_| {
+        panic!("search was cancelled")
+    });
+
+    assert!(summary.cancelled);
+    assert_eq!(summary.match_count, 0);
+}
+
+#[test]
+fn cancelled_search_stops_within_a_file() {
+    let regex = regex::Regex::new("needle").unwrap();
+    let text = "needle\n".repeat(1000);
+
+    assert!(search_text("a.txt", &text, &regex, &AtomicBool::new(true)).is_empty());
+}
+
+#[test]
+fn open_buffers_are_searched_instead_of_their_files() {
+    let root = create_temp_dir(
+        "papyrus_test_find_open_buffers",
+        &[("a.txt", b"saved needle\n"), ("b.txt", b"needle\n")],
+    );
+    let a_path = root.join("a.txt").to_string_lossy().to_string();
+    let open_buffers = HashMap::from([(a_path.clone(), "edited\nunsaved needle\n".to_string())]);
+    let search = FileSearch::new(root.to_str().unwrap(), &query("needle")).unwrap();
+    let mut matches = vec![];
+    search.run(&open_buffers, &AtomicBool::new(false), |file_matches| {
+        matches.extend(file_matches)
+    });
+    let a_matches: Vec<_> = matches.iter().filter(|m| m.path == a_path).collect();
+
+    assert_eq!(matches.len(), 2);
+    assert_eq!(a_matches.len(), 1);
+    assert_eq!(
+        (a_matches[0].row, a_matches[0].preview.as_str()),
+        (1, "unsaved needle")
+    );
+}
+
+#[test]
+fn invalid_queries_are_errors() {
+    let root = create_temp_dir("papyrus_test_find_invalid", &[]);
+    fs::create_dir_all(&root).unwrap();
+    let mut options = query("(unclosed");
+    options.search.is_regex = true;
+
+    assert!(FileSearch::new(root.to_str().unwrap(), &options).is_err());
+    assert!(FileSearch::new(root.to_str().unwrap(), &FindInFilesOptions::default()).is_err());
+}
@@ -0,0 +1,153 @@
+use crate::editor::text_buffer::{Cursor, LineTextBuffer, Selection};
+use crate::search::query::SearchOptions;
+
+fn selection(start: (usize, usize), end: (usize, usize)) -> Selection {
+    Selection {
+        start: Cursor {
+            row: start.0,
+            column: start.1,
+        },
+        end: Cursor {
+            row: end.0,
+            column: end.1,
+        },
+    }
+}
+
+fn regex() -> SearchOptions {
+    SearchOptions {
+        is_regex: true,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn find_all_matches() {
+    let buffer = LineTextBuffer::new("foo bar\nFoo 😀 foo\n".into());
+    let matches = buffer.find_all("foo", &SearchOptions::default()).unwrap();
+
+    assert_eq!(
+        matches,
+        vec![
+            selection((0, 0), (0, 3)),
+            selection((1, 0), (1, 3)),
+            selection((1, 7), (1, 10)),
+        ]
+    );
+
+    let options = SearchOptions {
+        case_sensitive: true,
+        ..Default::default()
+    };
+    assert_eq!(buffer.find_all("foo", &options).unwrap().len(), 2);
+}
+
+#[test]
+fn regex_matches_span_rows() {
+    let buffer = LineTextBuffer::new("fn a() {\n}\nfn b() {\n}\n".into());
+    let matches = buffer.find_all(r"\{\n\}", &regex()).unwrap();
+
+    assert_eq!(
+        matches,
+        vec![selection((0, 7), (1, 1)), selection((2, 7), (3, 1))]
+    );
+}
+
+#[test]
+fn find_next_and_prev_wrap_around() {
+    let buffer = LineTextBuffer::new("ab ab\nab\n".into());
+    let options = SearchOptions::default();
+    let next = |row, column| {
+        buffer
+            .find_next("ab", &options, &Cursor { row, column })
+            .unwrap()
+    };
+    let prev = |row, column| {
+        buffer
+            .find_prev("ab", &options, &Cursor { row, column })
+            .unwrap()
+    };
+
+    assert_eq!(next(0, 1), Some(selection((0, 3), (0, 5))));
+    assert_eq!(next(1, 1), Some(selection((0, 0), (0, 2))));
+    assert_eq!(prev(0, 3), Some(selection((0, 0), (0, 2))));
+    assert_eq!(prev(0, 0), Some(selection((1, 0), (1, 2))));
+    assert_eq!(
+        buffer
+            .find_next("cd", &options, &Cursor { row: 0, column: 0 })
+            .unwrap(),
+        None
+    );
+}
+
+#[test]
+fn replace_next_match() {
+    let mut buffer = LineTextBuffer::new("one two one\n".into());
+    let replaced = buffer
+        .replace(
+            "one",
+            &SearchOptions::default(),
+            "three",
+            &Cursor { row: 0, column: 1 },
+        )
+        .unwrap();
+
+    assert_eq!(replaced, Some(selection((0, 8), (0, 13))));
+    assert_eq!(buffer.get_lines(), vec!["one two three", ""]);
+
+    buffer.undo();
+    assert_eq!(buffer.get_lines(), vec!["one two one", ""]);
+}
+
+#[test]
+fn replace_all_expands_capture_groups() {
+    let mut buffer = LineTextBuffer::new("let a = 1;\nlet bc = 22;\n".into());
+    let count = buffer
+        .replace_all(
+            r"let (\w+) = (?P<value>\d+);",
+            &regex(),
+            "const $1: i32 = ${value};",
+        )
+        .unwrap();
+
+    assert_eq!(count, 2);
+    assert_eq!(
+        buffer.get_lines(),
+        vec!["const a: i32 = 1;", "const bc: i32 = 22;", ""]
+    );
+}
+
+#[test]
+fn literal_replacements_are_not_expanded() {
+    let mut buffer = LineTextBuffer::new("price\n".into());
+    buffer
+        .replace_all("price", &SearchOptions::default(), "$1.00")
+        .unwrap();
+
+    assert_eq!(buffer.get_lines(), vec!["$1.00", ""]);
+}
+
+#[test]
+fn replace_all_is_a_single_undo_step() {
+    let initial_code = "a b a\nb a\n";
+    let mut buffer = LineTextBuffer::new(initial_code.into());
+    buffer.insert_text("x".into(), Cursor { row: 0, column: 0 });
+    buffer
+        .replace_all("a", &SearchOptions::default(), "long\ntext")
+        .unwrap();
+    assert_eq!(
+        buffer.get_lines(),
+        vec!["xlong", "text b long", "text", "b long", "text", ""]
+    );
+
+    buffer.undo();
+    assert_eq!(buffer.get_lines(), vec!["xa b a", "b a", ""]);
+    buffer.redo();
+    assert_eq!(
+        buffer.get_lines(),
+        vec!["xlong", "text b long", "text", "b long", "text", ""]
+    );
+    buffer.undo();
+    buffer.undo();
+    assert_eq!(buffer.get_content("\n".into()), initial_code);
+}
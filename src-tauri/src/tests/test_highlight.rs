@@ -0,0 +1,68 @@
+use crate::editor::highlight::HighlightType;
+use crate::editor::text_buffer::{Language, LineTextBuffer};
+
+#[test]
+fn capture_names_fall_back_to_parent() {
+    assert_eq!(HighlightType::from_scope("keyword"), HighlightType::Keyword);
+    assert_eq!(
+        HighlightType::from_scope("function.method"),
+        HighlightType::FunctionMethod
+    );
+    assert_eq!(
+        HighlightType::from_scope("keyword.function"),
+        HighlightType::Keyword
+    );
+    assert_eq!(HighlightType::from_scope("unknown"), HighlightType::None);
+    assert_eq!(
+        HighlightType::FunctionMethod.parent(),
+        Some(HighlightType::Function)
+    );
+    assert_eq!(HighlightType::Function.parent(), None);
+}
+
+#[test]
+fn highlight_types_serialize_as_scopes() {
+    assert_eq!(
+        serde_json::to_string(&HighlightType::FunctionBuiltin).unwrap(),
+        "\"function.builtin\""
+    );
+    assert_eq!(
+        serde_json::from_str::<HighlightType>("\"string\"").unwrap(),
+        HighlightType::String
+    );
+}
+
+#[test]
+fn python_highlights_query() {
+    let initial_code = "def greet(name):\n    print(\"hi\", name)  # greet\n".into();
+
+    let mut buffer = LineTextBuffer::new(initial_code);
+    buffer.set_language(Language::Python);
+    let highlighted_text = buffer.highlight_complete_text();
+    let tokens = &highlighted_text.text[1];
+
+    assert_eq!(
+        highlighted_text.text[0][0],
+        (HighlightType::Keyword, "def".into())
+    );
+    assert_eq!(
+        highlighted_text.text[0][2],
+        (HighlightType::Function, "greet".into())
+    );
+    assert!(tokens.contains(&(HighlightType::FunctionBuiltin, "print".into())));
+    assert!(tokens.contains(&(HighlightType::String, "\"hi\"".into())));
+    assert!(tokens.contains(&(HighlightType::Comment, "# greet".into())));
+}
+
+#[test]
+fn plain_text_is_not_highlighted() {
+    let initial_code = "def greet(name):\n".into();
+
+    let mut buffer = LineTextBuffer::new(initial_code);
+    let highlighted_text = buffer.highlight_complete_text();
+
+    assert_eq!(
+        highlighted_text.text[0],
+        vec![(HighlightType::None, "def greet(name):".to_string())]
+    );
+}
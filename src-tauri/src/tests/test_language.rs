@@ -0,0 +1,82 @@
+use crate::editor::language::{detect_language, get_language_configurations, Language};
+
+#[test]
+fn detect_language_from_extension() {
+    assert_eq!(detect_language("/project/src/main.rs", ""), Language::Rust);
+    assert_eq!(
+        detect_language("/project/index.mjs", ""),
+        Language::JavaScript
+    );
+    assert_eq!(detect_language("/project/App.tsx", ""), Language::Tsx);
+    assert_eq!(
+        detect_language("/project/README.MD", ""),
+        Language::Markdown
+    );
+    assert_eq!(detect_language("/project/main.go", ""), Language::Go);
+}
+
+#[test]
+fn detect_language_from_file_name() {
+    assert_eq!(detect_language("/project/Cargo.lock", ""), Language::Toml);
+    assert_eq!(detect_language("/home/user/.bashrc", ""), Language::Bash);
+}
+
+#[test]
+fn detect_language_from_shebang() {
+    assert_eq!(
+        detect_language("/project/run", "#!/usr/bin/env python3\nprint(1)\n"),
+        Language::Python
+    );
+    assert_eq!(
+        detect_language("/project/build", "#!/bin/bash\necho 1\n"),
+        Language::Bash
+    );
+    assert_eq!(
+        detect_language("/project/serve", "#!/usr/bin/env -S node --no-warnings\n"),
+        Language::JavaScript
+    );
+    assert_eq!(
+        detect_language("/project/test", "#!/usr/local/bin/python3.11\n"),
+        Language::Python
+    );
+}
+
+#[test]
+fn similar_interpreter_names_are_not_matched() {
+    for shebang in [
+        "#!/usr/bin/env shellcheck\n",
+        "#!/usr/bin/env nodemon\n",
+        "#!/usr/bin/pythonista\n",
+        "#!/bin/bashful\n",
+    ] {
+        assert_eq!(
+            detect_language("/project/script", shebang),
+            Language::PlainText,
+            "{:?}",
+            shebang
+        );
+    }
+}
+
+#[test]
+fn unknown_files_are_plain_text() {
+    assert_eq!(
+        detect_language("/project/notes.txt", "hello"),
+        Language::PlainText
+    );
+    assert_eq!(
+        detect_language("/project/Makefile", "all:\n"),
+        Language::PlainText
+    );
+}
+
+#[test]
+fn highlights_queries_compile() {
+    for configuration in get_language_configurations() {
+        assert!(
+            configuration.highlight_configuration().is_some(),
+            "Invalid highlights query for {}",
+            configuration.name
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use crate::editor::text_buffer::{Cursor, LineTextBuffer, Selection};
+use crate::editor::text_buffer::{Cursor, Language, LineTextBuffer, Selection};
 
 #[test]
 fn line_buffer_from_file_content() {
@@ -12,7 +12,7 @@ fn line_buffer_from_file_content() {
         String::from(""),
     ];
 
-    assert_eq!(buffer.lines, lines);
+    assert_eq!(buffer.get_lines(), lines);
 }
 
 #[test]
@@ -28,7 +28,7 @@ fn line_buffer_from_file_content_with_blank_lines() {
         String::from(""),
     ];
 
-    assert_eq!(buffer.lines, lines);
+    assert_eq!(buffer.get_lines(), lines);
 }
 
 #[test]
@@ -44,7 +44,7 @@ fn line_buffer_from_file_content_with_crlf() {
         String::from(""),
     ];
 
-    assert_eq!(buffer.lines, lines);
+    assert_eq!(buffer.get_lines(), lines);
 }
 
 #[test]
@@ -59,7 +59,7 @@ fn line_buffer_from_file_content_with_escape_sequences() {
         String::from("file\\n"),
     ];
 
-    assert_eq!(buffer.lines, lines);
+    assert_eq!(buffer.get_lines(), lines);
 }
 
 #[test]
@@ -93,7 +93,7 @@ fn insert_character_at_start() {
         String::from("file\\n"),
     ];
 
-    assert_eq!(buffer.lines, lines);
+    assert_eq!(buffer.get_lines(), lines);
     assert_eq!(new_pos.row, 0);
     assert_eq!(new_pos.column, 1);
 }
@@ -111,7 +111,7 @@ fn insert_character_at_line_end() {
         String::from("file\\n"),
     ];
 
-    assert_eq!(buffer.lines, lines);
+    assert_eq!(buffer.get_lines(), lines);
     assert_eq!(new_pos.row, 1);
     assert_eq!(new_pos.column, 3);
 }
@@ -129,7 +129,7 @@ fn insert_character_at_end() {
         String::from("file\\n3"),
     ];
 
-    assert_eq!(buffer.lines, lines);
+    assert_eq!(buffer.get_lines(), lines);
     assert_eq!(new_pos.row, 3);
     assert_eq!(new_pos.column, 7);
 }
@@ -147,7 +147,7 @@ fn insert_character_in_between() {
         String::from("file\\n"),
     ];
 
-    assert_eq!(buffer.lines, lines);
+    assert_eq!(buffer.get_lines(), lines);
     assert_eq!(new_pos.row, 0);
     assert_eq!(new_pos.column, 3);
 }
@@ -166,7 +166,7 @@ fn insert_new_line_character_in_between() {
         String::from("file\\n"),
     ];
 
-    assert_eq!(buffer.lines, lines);
+    assert_eq!(buffer.get_lines(), lines);
     assert_eq!(new_pos.row, 3);
     assert_eq!(new_pos.column, 0);
 }
@@ -185,7 +185,7 @@ fn insert_new_line_character_at_line_end() {
         String::from("file\\n"),
     ];
 
-    assert_eq!(buffer.lines, lines);
+    assert_eq!(buffer.get_lines(), lines);
     assert_eq!(new_pos.row, 2);
     assert_eq!(new_pos.column, 0);
 }
@@ -203,7 +203,7 @@ fn insert_string_in_between() {
         String::from("file\\n"),
     ];
 
-    assert_eq!(buffer.lines, lines);
+    assert_eq!(buffer.get_lines(), lines);
     assert_eq!(new_pos.row, 0);
     assert_eq!(new_pos.column, 7);
 }
@@ -225,7 +225,7 @@ fn insert_string_with_new_lines_in_between() {
         String::from("file\\n"),
     ];
 
-    assert_eq!(buffer.lines, lines);
+    assert_eq!(buffer.get_lines(), lines);
     assert_eq!(new_pos.row, 6);
     assert_eq!(new_pos.column, 0);
 }
@@ -247,7 +247,7 @@ fn remove_character_in_between() {
         String::from(""),
     ];
 
-    assert_eq!(buffer.lines, lines);
+    assert_eq!(buffer.get_lines(), lines);
     assert_eq!(text, String::from("a"));
     assert_eq!(updated_cursor.row, 2);
     assert_eq!(updated_cursor.column, 0);
@@ -269,7 +269,7 @@ fn remove_new_line_character_at_line_start() {
         String::from(""),
     ];
 
-    assert_eq!(buffer.lines, lines);
+    assert_eq!(buffer.get_lines(), lines);
     assert_eq!(text, String::from("\n"));
     assert_eq!(updated_cursor.row, 1);
     assert_eq!(updated_cursor.column, 2);
@@ -292,7 +292,7 @@ fn remove_string_in_between() {
         String::from(""),
     ];
 
-    assert_eq!(buffer.lines, lines);
+    assert_eq!(buffer.get_lines(), lines);
     assert_eq!(text, String::from(" simpl"));
     assert_eq!(updated_cursor.row, 2);
     assert_eq!(updated_cursor.column, 1);
@@ -309,8 +309,203 @@ fn remove_multi_line_in_between() {
     });
     let lines = vec![String::from("This"), String::from("iile"), String::from("")];
 
-    assert_eq!(buffer.lines, lines);
+    assert_eq!(buffer.get_lines(), lines);
     assert_eq!(text, String::from("s\na\nsimple\nf"));
     assert_eq!(updated_cursor.row, 1);
     assert_eq!(updated_cursor.column, 1);
 }
+
+fn fresh_syntax_tree(buffer: &LineTextBuffer) -> String {
+    let mut parser = tree_sitter::Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let content = buffer.get_content("\n".into());
+    parser.parse(content, None).unwrap().root_node().to_sexp()
+}
+
+#[test]
+fn syntax_tree_is_reused_across_edits() {
+    let initial_code = "import os\n\ndef main():\n    return os.getcwd()\n".into();
+
+    let mut buffer = LineTextBuffer::new(initial_code);
+    buffer.set_language(Language::Python);
+    buffer.highlight_complete_text();
+    let updated_cursor = buffer.insert_text("\n    print(1)".into(), Cursor { row: 2, column: 11 });
+    buffer.highlight_complete_text();
+    buffer.remove_text(Selection {
+        start: Cursor { row: 0, column: 0 },
+        end: Cursor { row: 1, column: 0 },
+    });
+    buffer.highlight_complete_text();
+
+    assert_eq!(updated_cursor.row, 3);
+    assert_eq!(
+        buffer.syntax_tree.as_ref().unwrap().root_node().to_sexp(),
+        fresh_syntax_tree(&buffer)
+    );
+}
+
+#[test]
+fn syntax_tree_follows_undo_and_redo() {
+    let initial_code = "x = 1\ny = 2\n".into();
+
+    let mut buffer = LineTextBuffer::new(initial_code);
+    buffer.set_language(Language::Python);
+    buffer.highlight_complete_text();
+    buffer.insert_text("def f():\n    pass\n".into(), Cursor { row: 1, column: 0 });
+    buffer.highlight_complete_text();
+    buffer.undo();
+    buffer.highlight_complete_text();
+
+    assert_eq!(
+        buffer.syntax_tree.as_ref().unwrap().root_node().to_sexp(),
+        fresh_syntax_tree(&buffer)
+    );
+
+    buffer.redo();
+    buffer.highlight_complete_text();
+
+    assert_eq!(
+        buffer.syntax_tree.as_ref().unwrap().root_node().to_sexp(),
+        fresh_syntax_tree(&buffer)
+    );
+}
+
+#[test]
+fn highlight_rows_matches_complete_text() {
+    let initial_code =
+        "import os\n\ndef main():\n    \"\"\"Multi\n    line\"\"\"\n    return os.getcwd()\n"
+            .into();
+
+    let mut buffer = LineTextBuffer::new(initial_code);
+    buffer.set_language(Language::Python);
+    let complete_text = buffer.highlight_complete_text();
+    let rows = buffer.highlight_rows(3, 5);
+
+    assert_eq!(rows.text.len(), 2);
+    assert_eq!(
+        format!("{:?}", rows.text),
+        format!("{:?}", &complete_text.text[3..5])
+    );
+}
+
+#[test]
+fn highlight_rows_are_clamped_to_buffer() {
+    let initial_code = "x = 1\ny = 2".into();
+
+    let mut buffer = LineTextBuffer::new(initial_code);
+    buffer.set_language(Language::Python);
+    let rows = buffer.highlight_rows(1, 100);
+
+    assert_eq!(rows.text.len(), 1);
+}
+
+#[test]
+fn line_changes_after_multi_line_insert() {
+    let initial_code = "This\nis\na simple\nfile\n".into();
+
+    let mut buffer = LineTextBuffer::new(initial_code);
+    buffer.insert_text("\nmany\nnew".into(), Cursor { row: 1, column: 1 });
+    let changes = buffer.take_line_changes();
+
+    assert_eq!(changes.start_row, 1);
+    assert_eq!(changes.removed_rows, 1);
+    assert_eq!(changes.inserted_rows, 3);
+    assert_eq!(changes.lines_length, 7);
+}
+
+#[test]
+fn line_changes_are_merged_until_taken() {
+    let initial_code = "This\nis\na\nsimple\nfile\n".into();
+
+    let mut buffer = LineTextBuffer::new(initial_code);
+    buffer.remove_text(Selection {
+        start: Cursor { row: 3, column: 6 },
+        end: Cursor { row: 4, column: 0 },
+    });
+    buffer.insert_text("!".into(), Cursor { row: 0, column: 4 });
+    let changes = buffer.take_line_changes();
+
+    assert_eq!(changes.start_row, 0);
+    assert_eq!(changes.removed_rows, 5);
+    assert_eq!(changes.inserted_rows, 4);
+
+    let changes = buffer.take_line_changes();
+    assert_eq!(changes.removed_rows, 0);
+    assert_eq!(changes.inserted_rows, 0);
+}
+
+#[test]
+fn columns_count_utf16_code_units() {
+    let initial_code = "héllo 😀 世界\n".into();
+    let buffer = LineTextBuffer::new(initial_code);
+
+    // The emoji takes two UTF-16 code units
+    assert_eq!(buffer.get_row_length(0), 11);
+    assert_eq!(
+        buffer.get_selected_text(Selection {
+            start: Cursor { row: 0, column: 6 },
+            end: Cursor { row: 0, column: 8 },
+        }),
+        "😀"
+    );
+}
+
+#[test]
+fn insert_and_remove_non_ascii_text() {
+    let initial_code = "héllo 世界\n".into();
+    let mut buffer = LineTextBuffer::new(initial_code);
+
+    let cursor = buffer.insert_text("😀é".into(), Cursor { row: 0, column: 2 });
+    assert_eq!(buffer.get_line(0), "hé😀éllo 世界");
+    assert_eq!(cursor, Cursor { row: 0, column: 5 });
+
+    let (removed_text, cursor) = buffer.remove_text(Selection {
+        start: Cursor { row: 0, column: 1 },
+        end: Cursor { row: 0, column: 8 },
+    });
+    assert_eq!(removed_text, "é😀éllo");
+    assert_eq!(cursor, Cursor { row: 0, column: 1 });
+    assert_eq!(buffer.get_line(0), "h 世界");
+
+    buffer.undo();
+    buffer.undo();
+    assert_eq!(buffer.get_line(0), "héllo 世界");
+}
+
+#[test]
+fn columns_inside_surrogate_pairs_are_rounded_down() {
+    let initial_code = "a😀b".into();
+    let mut buffer = LineTextBuffer::new(initial_code);
+
+    assert!(!buffer.is_valid_cursor(&Cursor { row: 0, column: 2 }));
+    buffer.insert_text_no_log(&"x".to_string(), &Cursor { row: 0, column: 2 });
+    assert_eq!(buffer.get_line(0), "ax😀b");
+}
+
+#[test]
+fn syntax_tree_uses_byte_columns_for_non_ascii_text() {
+    let initial_code = "s = \"é\"\nname = 1\n".into();
+    let mut buffer = LineTextBuffer::new(initial_code);
+    buffer.set_language(Language::Python);
+    buffer.highlight_complete_text();
+
+    buffer.insert_text("世界".into(), Cursor { row: 0, column: 6 });
+    buffer.highlight_complete_text();
+    assert_eq!(
+        buffer.syntax_tree.as_ref().unwrap().root_node().to_sexp(),
+        fresh_syntax_tree(&buffer)
+    );
+
+    let selection = buffer
+        .select_token_under_cursor(Cursor { row: 1, column: 2 })
+        .unwrap();
+    assert_eq!(selection.start, Cursor { row: 1, column: 0 });
+    assert_eq!(selection.end, Cursor { row: 1, column: 4 });
+
+    buffer.insert_text("é".into(), Cursor { row: 1, column: 0 });
+    buffer.highlight_complete_text();
+    let selection = buffer
+        .select_token_under_cursor(Cursor { row: 1, column: 5 })
+        .unwrap();
+    assert_eq!(selection.end, Cursor { row: 1, column: 5 });
+}
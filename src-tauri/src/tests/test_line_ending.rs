@@ -0,0 +1,89 @@
+use crate::editor::text_buffer::{Cursor, LineTextBuffer, Selection};
+use crate::editor_io::line_ending::{detect_line_endings, LineEnding, LineEndings};
+
+#[test]
+fn detects_line_endings() {
+    let cases = [
+        ("a\nb\n", LineEnding::Lf, false),
+        ("a\r\nb\r\n", LineEnding::Crlf, false),
+        ("a\rb\r", LineEnding::Cr, false),
+        ("a\r\nb\r\nc\n", LineEnding::Crlf, true),
+        ("a\nb\r\n", LineEnding::Lf, true),
+        ("no line break", LineEnding::Lf, false),
+    ];
+    for (text, line_ending, mixed) in cases {
+        assert_eq!(
+            detect_line_endings(text),
+            LineEndings { line_ending, mixed },
+            "{:?}",
+            text
+        );
+    }
+}
+
+#[test]
+fn buffer_keeps_crlf_line_endings() {
+    let buffer = LineTextBuffer::new("This\r\nis\r\na file\r\n".into());
+
+    assert_eq!(buffer.line_endings.line_ending, LineEnding::Crlf);
+    assert_eq!(buffer.get_lines(), vec!["This", "is", "a file", ""]);
+    assert_eq!(
+        buffer.get_content(buffer.line_endings.line_ending.sequence().into()),
+        "This\r\nis\r\na file\r\n"
+    );
+}
+
+#[test]
+fn carriage_returns_split_rows() {
+    let buffer = LineTextBuffer::new("This\ris\r\na file\n".into());
+
+    assert!(buffer.line_endings.mixed);
+    assert_eq!(buffer.get_lines(), vec!["This", "is", "a file", ""]);
+}
+
+#[test]
+fn mixed_line_breaks_are_kept_until_converted() {
+    let mut buffer = LineTextBuffer::new("a\r\nb\nc\rd".into());
+    assert_eq!(
+        buffer.line_breaks,
+        vec![LineEnding::Crlf, LineEnding::Lf, LineEnding::Cr]
+    );
+
+    buffer.insert_text("x\ny".into(), Cursor { row: 1, column: 1 });
+    assert_eq!(buffer.get_file_content(), "a\r\nbx\ny\nc\rd");
+
+    buffer.remove_text(Selection {
+        start: Cursor { row: 0, column: 1 },
+        end: Cursor { row: 2, column: 0 },
+    });
+    assert_eq!(buffer.get_file_content(), "ay\nc\rd");
+
+    let mut buffer = LineTextBuffer::new("a\nb\r\nc\n".into());
+    assert!(!buffer.is_dirty());
+    buffer.line_endings.mixed = false;
+    buffer.line_breaks.clear();
+    assert!(buffer.is_dirty());
+    assert_eq!(buffer.get_file_content(), "a\nb\nc\n");
+}
+
+#[test]
+fn inserted_line_breaks_are_normalized() {
+    let mut buffer = LineTextBuffer::new("ab".into());
+    let cursor = buffer.insert_text("x\r\ny\rz".into(), Cursor { row: 0, column: 1 });
+
+    assert_eq!(buffer.get_lines(), vec!["ax", "y", "zb"]);
+    assert_eq!(cursor, Cursor { row: 2, column: 1 });
+}
+
+#[test]
+fn line_endings_serialize_by_name() {
+    let line_endings = LineEndings {
+        line_ending: LineEnding::Crlf,
+        mixed: true,
+    };
+
+    assert_eq!(
+        serde_json::to_string(&line_endings).unwrap(),
+        r#"{"line_ending":"CRLF","mixed":true}"#
+    );
+}
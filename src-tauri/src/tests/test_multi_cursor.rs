@@ -0,0 +1,135 @@
+use crate::editor::language::Language;
+use crate::editor::text_buffer::{Cursor, LineTextBuffer, Selection};
+
+fn selection(start: (usize, usize), end: (usize, usize)) -> Selection {
+    Selection {
+        start: Cursor {
+            row: start.0,
+            column: start.1,
+        },
+        end: Cursor {
+            row: end.0,
+            column: end.1,
+        },
+    }
+}
+
+fn cursor(row: usize, column: usize) -> Selection {
+    selection((row, column), (row, column))
+}
+
+fn content(buffer: &LineTextBuffer) -> String {
+    buffer.get_content("\n".into())
+}
+
+#[test]
+fn inserts_at_every_selection() {
+    let mut buffer = LineTextBuffer::new("ab\ncd\nef".into());
+    let cursors = buffer.insert_text_at_selections(
+        "x\n".into(),
+        &[cursor(2, 1), cursor(0, 1), selection((1, 0), (1, 2))],
+    );
+
+    assert_eq!(content(&buffer), "ax\nb\nx\n\nex\nf");
+    assert_eq!(cursors, vec![cursor(1, 0), cursor(3, 0), cursor(5, 0)]);
+
+    buffer.undo();
+    assert_eq!(content(&buffer), "ab\ncd\nef");
+    buffer.redo();
+    assert_eq!(content(&buffer), "ax\nb\nx\n\nex\nf");
+}
+
+#[test]
+fn removes_at_every_selection() {
+    let mut buffer = LineTextBuffer::new("abc\ndef".into());
+    let cursors = buffer.remove_text_at_selections(&[cursor(0, 2), cursor(1, 0)], false);
+    assert_eq!(content(&buffer), "acdef");
+    assert_eq!(cursors, vec![cursor(0, 1), cursor(0, 2)]);
+
+    let cursors = buffer.remove_text_at_selections(&[cursor(0, 0), cursor(0, 1)], true);
+    assert_eq!(content(&buffer), "def");
+    assert_eq!(cursors, vec![cursor(0, 0)]);
+
+    buffer.undo();
+    assert_eq!(content(&buffer), "acdef");
+    buffer.undo();
+    assert_eq!(content(&buffer), "abc\ndef");
+}
+
+#[test]
+fn overlapping_selections_are_merged() {
+    let mut buffer = LineTextBuffer::new("abcdef".into());
+    let cursors = buffer.insert_text_at_selections(
+        "-".into(),
+        &[
+            selection((0, 1), (0, 3)),
+            selection((0, 2), (0, 4)),
+            cursor(0, 5),
+            cursor(0, 5),
+        ],
+    );
+
+    assert_eq!(content(&buffer), "a-e-f");
+    assert_eq!(cursors, vec![cursor(0, 2), cursor(0, 4)]);
+}
+
+#[test]
+fn adds_next_occurrence() {
+    let buffer = LineTextBuffer::new("foo bar\nfoo_x foo".into());
+
+    let selections = buffer.add_next_occurrence(&[cursor(0, 1)]);
+    assert_eq!(selections, vec![selection((0, 0), (0, 3))]);
+
+    let selections = buffer.add_next_occurrence(&selections);
+    assert_eq!(
+        selections,
+        vec![selection((0, 0), (0, 3)), selection((1, 0), (1, 3))]
+    );
+
+    // Wraps around to the first occurrence not already selected
+    let selections =
+        buffer.add_next_occurrence(&[selection((1, 6), (1, 9)), selection((1, 0), (1, 3))]);
+    assert_eq!(selections[2], selection((0, 0), (0, 3)));
+}
+
+#[test]
+fn adds_token_under_cursor() {
+    let mut buffer = LineTextBuffer::new("let value = 1;\n".into());
+    buffer.set_language(Language::Rust);
+
+    let selections = buffer.add_next_occurrence(&[cursor(0, 6)]);
+    assert_eq!(selections, vec![selection((0, 4), (0, 9))]);
+}
+
+#[test]
+fn selects_box() {
+    let buffer = LineTextBuffer::new("abcdef\nab\nabcdef".into());
+    let selections =
+        buffer.box_selection(&Cursor { row: 2, column: 4 }, &Cursor { row: 0, column: 1 });
+
+    assert_eq!(
+        selections,
+        vec![
+            selection((0, 1), (0, 4)),
+            selection((1, 1), (1, 2)),
+            selection((2, 1), (2, 4)),
+        ]
+    );
+}
+
+#[test]
+fn splits_selection_into_lines() {
+    let buffer = LineTextBuffer::new("abc\nde\nfgh\n".into());
+    let lines =
+        buffer.split_selection_into_lines(&[selection((0, 1), (2, 2)), selection((2, 0), (3, 0))]);
+
+    assert_eq!(
+        lines,
+        vec![
+            selection((0, 1), (0, 3)),
+            selection((1, 0), (1, 2)),
+            selection((2, 0), (2, 2)),
+            selection((2, 0), (2, 3)),
+        ]
+    );
+}
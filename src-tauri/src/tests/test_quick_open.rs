@@ -0,0 +1,135 @@
+use std::path::{Path, PathBuf};
+
+use crate::editor_io::file_handling::FolderEntry;
+use crate::search::quick_open::{find_files, QuickOpenMatch, RecentFiles};
+
+fn root() -> PathBuf {
+    std::env::temp_dir().join("papyrus_test_quick_open")
+}
+
+fn entries(files: &[&str]) -> Vec<FolderEntry> {
+    files
+        .iter()
+        .map(|file| FolderEntry::new(&root().join(file), false))
+        .collect()
+}
+
+fn path(file: &str) -> String {
+    root().join(file).to_string_lossy().to_string()
+}
+
+/// Returns the relative paths of the matches with `/` separators
+fn relative_paths(matches: &[QuickOpenMatch]) -> Vec<String> {
+    matches
+        .iter()
+        .map(|found| found.relative_path.replace('\\', "/"))
+        .collect()
+}
+
+#[test]
+fn quick_open_ranks_file_name_matches_first() {
+    let files = entries(&[
+        "src/main.rs",
+        "src/commands.rs",
+        "src/editor/mod.rs",
+        "docs/maintenance.md",
+        "mock/admin.rs",
+    ]);
+
+    let matches = find_files(&root(), &files, "main", &RecentFiles::new(), 10);
+    assert_eq!(
+        relative_paths(&matches),
+        vec!["src/main.rs", "docs/maintenance.md", "mock/admin.rs"]
+    );
+    assert_eq!(matches[0].positions, vec![4, 5, 6, 7]);
+    assert_eq!(matches[0].entry, files[0]);
+
+    let matches = find_files(&root(), &files, "edmod", &RecentFiles::new(), 10);
+    assert_eq!(relative_paths(&matches), vec!["src/editor/mod.rs"]);
+    assert_eq!(matches[0].positions, vec![4, 5, 11, 12, 13]);
+}
+
+#[test]
+fn quick_open_matches_case_and_spaces() {
+    let files = entries(&["src/TextBuffer.ts", "src/textbuffer.rs"]);
+
+    let matches = find_files(&root(), &files, "tb", &RecentFiles::new(), 10);
+    assert_eq!(matches.len(), 2);
+    assert!(matches[0].score > matches[1].score);
+    assert_eq!(relative_paths(&matches)[0], "src/TextBuffer.ts");
+
+    let matches = find_files(&root(), &files, "TB", &RecentFiles::new(), 10);
+    assert_eq!(relative_paths(&matches), vec!["src/TextBuffer.ts"]);
+
+    let matches = find_files(&root(), &files, "text .rs", &RecentFiles::new(), 10);
+    assert_eq!(relative_paths(&matches), vec!["src/textbuffer.rs"]);
+
+    let files = entries(&["café/menü.rs"]);
+    let matches = find_files(&root(), &files, "fmr", &RecentFiles::new(), 10);
+    assert_eq!(matches[0].positions, vec![2, 5, 10]);
+    let matches = find_files(&root(), &files, "ÉMÜ", &RecentFiles::new(), 10);
+    assert!(matches.is_empty());
+    let matches = find_files(&root(), &files, "éü", &RecentFiles::new(), 10);
+    assert_eq!(matches[0].positions, vec![3, 8]);
+
+    let matches = find_files(&root(), &files, "xyz", &RecentFiles::new(), 10);
+    assert!(matches.is_empty());
+}
+
+#[test]
+fn quick_open_returns_the_best_matches() {
+    let files = entries(&["b.rs", "a.rs", "c/d.rs", "aa.rs"]);
+
+    let matches = find_files(&root(), &files, "", &RecentFiles::new(), 3);
+    assert_eq!(relative_paths(&matches), vec!["a.rs", "b.rs", "aa.rs"]);
+    assert!(matches.iter().all(|found| found.positions.is_empty()));
+
+    assert!(find_files(&root(), &files, "rs", &RecentFiles::new(), 0).is_empty());
+}
+
+#[test]
+fn quick_open_ranks_recent_files_higher() {
+    let files = entries(&["src/main.rs", "src/mod/main.rs", "src/lib.rs"]);
+    let mut recent_files = RecentFiles::new();
+
+    let matches = find_files(&root(), &files, "main", &recent_files, 10);
+    assert_eq!(
+        relative_paths(&matches),
+        vec!["src/main.rs", "src/mod/main.rs"]
+    );
+
+    recent_files.open(&path("src/mod/main.rs"));
+    let matches = find_files(&root(), &files, "main", &recent_files, 10);
+    assert_eq!(
+        relative_paths(&matches),
+        vec!["src/mod/main.rs", "src/main.rs"]
+    );
+
+    recent_files.open(&path("src/lib.rs"));
+    let matches = find_files(&root(), &files, "", &recent_files, 10);
+    assert_eq!(
+        relative_paths(&matches),
+        vec!["src/lib.rs", "src/mod/main.rs", "src/main.rs"]
+    );
+}
+
+#[test]
+fn recent_files_follow_renames() {
+    let mut recent_files = RecentFiles::new();
+    recent_files.open(&path("src/main.rs"));
+    recent_files.open(&path("src/lib.rs"));
+    recent_files.open(&path("src/main.rs"));
+    recent_files.open(&path("README.md"));
+
+    recent_files.rename(&path("src"), &path("app"));
+    recent_files.rename(&path("README.md"), &path("docs.md"));
+    let paths: Vec<&Path> = recent_files.paths().map(Path::new).collect();
+    assert_eq!(
+        paths,
+        vec![
+            root().join("docs.md"),
+            root().join("app/main.rs"),
+            root().join("app/lib.rs"),
+        ]
+    );
+}
@@ -0,0 +1,150 @@
+use std::collections::HashMap;
+use std::thread;
+use std::time::Duration;
+
+use crate::editor::language::Language;
+use crate::editor::state::EditorState;
+use crate::editor::text_buffer::{Cursor, LineTextBuffer};
+use crate::editor_io::file_handling::{check_unchanged, modified_time};
+use crate::editor_io::recovery::{self, BufferSnapshot, Recovery, SnapshotWrite};
+use crate::tests::create_temp_dir;
+
+fn written_paths(writes: &[SnapshotWrite]) -> Vec<Option<String>> {
+    writes
+        .iter()
+        .map(|write| match write {
+            SnapshotWrite::Write(_, snapshot) => snapshot.file_path.clone(),
+            SnapshotWrite::Remove(_) => None,
+        })
+        .collect()
+}
+
+#[test]
+fn snapshots_modified_buffers_once() {
+    let data_dir = create_temp_dir("papyrus_test_recovery_pending", &[]);
+    let mut state = EditorState::new();
+    state.add_buffer(LineTextBuffer::from_file(
+        "clean".into(),
+        "clean.txt".into(),
+    ));
+    let buffer_id = state.add_buffer(LineTextBuffer::from_file("a".into(), "dirty.txt".into()));
+    state
+        .get_buffer_mut(buffer_id)
+        .unwrap()
+        .insert_text("b".into(), Cursor { row: 0, column: 1 });
+    let mut recovery = Recovery::new(&data_dir);
+
+    let writes = recovery.pending_writes(&state.text_buffers);
+    assert_eq!(written_paths(&writes), vec![Some("dirty.txt".to_string())]);
+    assert!(recovery.pending_writes(&state.text_buffers).is_empty());
+
+    // Saved buffers no longer need their snapshot
+    state.get_buffer_mut(buffer_id).unwrap().mark_saved();
+    let writes = recovery.pending_writes(&state.text_buffers);
+    assert!(matches!(writes[..], [SnapshotWrite::Remove(_)]));
+}
+
+#[test]
+fn restores_snapshot() {
+    let mut buffer = LineTextBuffer::new("".into());
+    buffer.set_language(Language::Rust);
+    buffer.insert_text("fn main() {}\n".into(), Cursor { row: 0, column: 0 });
+    buffer.cursor = Cursor { row: 0, column: 3 };
+
+    let json = serde_json::to_string(&BufferSnapshot::new(&buffer)).unwrap();
+    let mut restored = serde_json::from_str::<BufferSnapshot>(&json)
+        .unwrap()
+        .restore();
+
+    assert_eq!(restored.file_path, None);
+    assert_eq!(restored.get_content("\n".into()), "fn main() {}\n");
+    assert_eq!(restored.language, Language::Rust);
+    assert_eq!(restored.cursor, Cursor { row: 0, column: 3 });
+    assert!(restored.is_dirty());
+    restored.undo();
+    assert_eq!(restored.get_content("\n".into()), "");
+    assert!(!restored.is_dirty());
+}
+
+#[test]
+fn lists_snapshots_of_other_sessions() {
+    let data_dir = create_temp_dir("papyrus_test_recovery_sessions", &[]);
+    let mut buffers = HashMap::new();
+    let mut state = EditorState::new();
+    let buffer_id = state.add_buffer(LineTextBuffer::new("".into()));
+    state
+        .get_buffer_mut(buffer_id)
+        .unwrap()
+        .insert_text("untitled".into(), Cursor { row: 0, column: 0 });
+    buffers.insert(buffer_id, state.remove_buffer(buffer_id).unwrap());
+
+    let mut crashed = Recovery::new(&data_dir);
+    recovery::write_snapshots(crashed.pending_writes(&buffers));
+    assert!(crashed.recoverable().is_empty());
+
+    thread::sleep(Duration::from_millis(5));
+    let current = Recovery::new(&data_dir);
+    let recoverable = current.recoverable();
+    assert_eq!(recoverable.len(), 1);
+    assert_eq!(recoverable[0].file_path, None);
+
+    let snapshot = current.read(&recoverable[0].id).unwrap();
+    assert_eq!(snapshot.content, "untitled");
+    assert!(current.read("../escape").is_err());
+
+    current.discard(&recoverable[0].id).unwrap();
+    assert!(current.recoverable().is_empty());
+}
+
+#[test]
+fn running_sessions_keep_their_snapshots() {
+    let data_dir = create_temp_dir("papyrus_test_recovery_running", &[]);
+    let mut state = EditorState::new();
+    let buffer_id = state.add_buffer(LineTextBuffer::new("".into()));
+    state
+        .get_buffer_mut(buffer_id)
+        .unwrap()
+        .insert_text("running".into(), Cursor { row: 0, column: 0 });
+
+    let mut running = Recovery::new(&data_dir);
+    running.lock_session().unwrap();
+    recovery::write_snapshots(running.pending_writes(&state.text_buffers));
+
+    thread::sleep(Duration::from_millis(5));
+    let current = Recovery::new(&data_dir);
+    assert!(current.recoverable().is_empty());
+
+    running.unlock_session().unwrap();
+    assert_eq!(current.recoverable().len(), 1);
+}
+
+#[test]
+fn failed_snapshot_writes_do_not_stop_the_others() {
+    let data_dir = create_temp_dir("papyrus_test_recovery_failed", &[("file", b"")]);
+    let snapshot = BufferSnapshot::new(&LineTextBuffer::new("text".into()));
+
+    recovery::write_snapshots(vec![
+        SnapshotWrite::Write(data_dir.join("file/a.json"), snapshot.clone()),
+        SnapshotWrite::Write(data_dir.join("b.json"), snapshot),
+    ]);
+
+    assert!(data_dir.join("b.json").exists());
+}
+
+#[test]
+fn restored_buffers_keep_the_modified_time() {
+    let root = create_temp_dir("papyrus_test_recovery_modified", &[("a.txt", b"a")]);
+    let path = root.join("a.txt").to_string_lossy().to_string();
+    let mut buffer = LineTextBuffer::from_file("a".into(), path.clone());
+    buffer.file_modified_time = modified_time(&path);
+    buffer.insert_text("b".into(), Cursor { row: 0, column: 1 });
+
+    let json = serde_json::to_string(&BufferSnapshot::new(&buffer)).unwrap();
+    let restored = serde_json::from_str::<BufferSnapshot>(&json)
+        .unwrap()
+        .restore();
+
+    // Saving without forcing checks the file is unchanged
+    assert_eq!(restored.file_modified_time, buffer.file_modified_time);
+    assert!(check_unchanged(&path, restored.file_modified_time).is_ok());
+}
@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+use std::fs;
+
+use crate::editor_io::encoding::{self, FileEncoding};
+use crate::editor_io::file_handling::write_file_atomically;
+use crate::error::PapyrusError;
+use crate::search::find_in_files::{FileSearch, FindInFilesOptions};
+use crate::search::query::SearchOptions;
+use crate::search::replace_in_files::{preview_replacements, replace_in_file, replace_text};
+use crate::tests::create_temp_dir;
+
+#[test]
+fn replaces_text_with_capture_groups() {
+    let options = SearchOptions {
+        is_regex: true,
+        ..Default::default()
+    };
+    let regex = options.to_regex(r"old_(\w+)").unwrap();
+    let (replaced, count) = replace_text(&regex, &options, "old_a + old_b\n", "new_$1");
+
+    assert_eq!(count, 2);
+    assert_eq!(replaced, "new_a + new_b\n");
+}
+
+#[test]
+fn previews_replacements_as_diffs() {
+    let root = create_temp_dir(
+        "papyrus_test_replace_preview",
+        &[
+            ("a.txt", b"one\ntwo\nold\nthree\n"),
+            ("b.txt", b"nothing here\n"),
+            ("c.txt", b"old on disk\n"),
+        ],
+    );
+    let options = FindInFilesOptions {
+        query: "old".into(),
+        ..Default::default()
+    };
+    let search = FileSearch::new(root.to_str().unwrap(), &options).unwrap();
+    let c_path = root.join("c.txt").to_string_lossy().to_string();
+    let open_buffers = HashMap::from([(c_path.clone(), "old\nold in buffer\n".to_string())]);
+
+    let mut previews = preview_replacements(&search, &options.search, "new", &open_buffers);
+    previews.sort_by(|a, b| a.path.cmp(&b.path));
+
+    assert_eq!(previews.len(), 2);
+    assert_eq!(previews[0].replacement_count, 1);
+    assert!(!previews[0].is_open);
+    assert!(previews[0].diff.contains("-old\n+new\n"));
+    assert!(previews[0].diff.contains(" two\n"));
+    assert_eq!(previews[1].path, c_path);
+    assert_eq!(previews[1].replacement_count, 2);
+    assert!(previews[1].is_open);
+    assert!(previews[1].diff.contains("+new in buffer\n"));
+
+    // Previewing does not change the files
+    assert_eq!(
+        fs::read_to_string(root.join("a.txt")).unwrap(),
+        "one\ntwo\nold\nthree\n"
+    );
+}
+
+#[test]
+fn replacing_keeps_encoding_and_line_endings() {
+    let file_encoding = FileEncoding::from_label("utf-16le", true).unwrap();
+    let content = encoding::encode("old\r\nkeep\r\nold\r\n", &file_encoding).unwrap();
+    let root = create_temp_dir("papyrus_test_replace_file", &[("a.txt", &content)]);
+    let path = root.join("a.txt").to_string_lossy().to_string();
+    let options = SearchOptions::default();
+    let regex = options.to_regex("old").unwrap();
+
+    let count = replace_in_file(&path, &regex, &options, "new").unwrap();
+
+    assert_eq!(count, 2);
+    let expected = encoding::encode("new\r\nkeep\r\nnew\r\n", &file_encoding).unwrap();
+    assert_eq!(fs::read(&path).unwrap(), expected);
+}
+
+#[test]
+fn files_with_malformed_bytes_are_not_replaced() {
+    let root = create_temp_dir(
+        "papyrus_test_replace_malformed",
+        &[("a.txt", b"\xEF\xBB\xBFold \xFF\n")],
+    );
+    let path = root.join("a.txt").to_string_lossy().to_string();
+    let options = SearchOptions::default();
+    let regex = options.to_regex("old").unwrap();
+
+    let err = PapyrusError::from(replace_in_file(&path, &regex, &options, "new").unwrap_err());
+
+    assert_eq!(err.kind(), "MalformedContent");
+    assert_eq!(fs::read(&path).unwrap(), b"\xEF\xBB\xBFold \xFF\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn atomic_writes_keep_permissions() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let root = create_temp_dir("papyrus_test_atomic_write", &[("run.sh", b"echo old\n")]);
+    let path = root.join("run.sh");
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+
+    write_file_atomically(path.to_str().unwrap(), b"echo new\n").unwrap();
+
+    assert_eq!(fs::read_to_string(&path).unwrap(), "echo new\n");
+    assert_eq!(
+        fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+        0o755
+    );
+    assert_eq!(fs::read_dir(&root).unwrap().count(), 1);
+}
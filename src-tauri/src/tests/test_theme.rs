@@ -0,0 +1,171 @@
+use crate::editor::highlight::HighlightType;
+use crate::editor::theme::{Style, Theme};
+
+#[test]
+fn toml_theme() {
+    let theme = Theme::from_toml(
+        r##"
+name = "Test"
+foreground = "#ffffff"
+background = "#000000"
+
+[scopes]
+keyword = "#ff0000"
+comment = { foreground = "#888888", italic = true }
+function = { bold = true }
+"unknown.scope" = "#123456"
+"##,
+    )
+    .unwrap();
+
+    assert_eq!(theme.name, "Test");
+    assert_eq!(
+        theme.resolve(HighlightType::Keyword).foreground,
+        Some("#ff0000".into())
+    );
+    assert_eq!(
+        theme.resolve(HighlightType::Comment),
+        Style {
+            foreground: Some("#888888".into()),
+            italic: true,
+            ..Default::default()
+        }
+    );
+    // Missing colours and scopes fall back to the parent scope and the
+    // theme foreground
+    assert_eq!(
+        theme.resolve(HighlightType::FunctionMethod),
+        Style {
+            foreground: Some("#ffffff".into()),
+            bold: true,
+            ..Default::default()
+        }
+    );
+    assert_eq!(
+        theme.resolve(HighlightType::String).foreground,
+        Some("#ffffff".into())
+    );
+    assert_eq!(theme.scopes.len(), 3);
+}
+
+#[test]
+fn json_theme() {
+    let theme = Theme::from_json(
+        r##"{
+            "name": "Test",
+            "scopes": { "string": "#00ff00", "type.builtin": { "underline": true } }
+        }"##,
+    )
+    .unwrap();
+
+    assert_eq!(
+        theme.resolve(HighlightType::StringEscape).foreground,
+        Some("#00ff00".into())
+    );
+    assert!(theme.resolve(HighlightType::TypeBuiltin).underline);
+    assert_eq!(theme.resolve(HighlightType::Type), Style::default());
+}
+
+#[test]
+fn tm_theme() {
+    let theme = Theme::from_tm_theme(
+        r##"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>name</key>
+    <string>Test</string>
+    <key>settings</key>
+    <array>
+        <dict>
+            <key>settings</key>
+            <dict>
+                <key>foreground</key>
+                <string>#ffffff</string>
+                <key>background</key>
+                <string>#000000</string>
+            </dict>
+        </dict>
+        <dict>
+            <key>scope</key>
+            <string>comment, string</string>
+            <key>settings</key>
+            <dict>
+                <key>foreground</key>
+                <string>#888888</string>
+                <key>fontStyle</key>
+                <string>italic</string>
+            </dict>
+        </dict>
+        <dict>
+            <key>scope</key>
+            <string>entity.name</string>
+            <key>settings</key>
+            <dict>
+                <key>foreground</key>
+                <string>#0000ff</string>
+            </dict>
+        </dict>
+        <dict>
+            <key>scope</key>
+            <string>entity.name.function</string>
+            <key>settings</key>
+            <dict>
+                <key>foreground</key>
+                <string>#00ffff</string>
+                <key>fontStyle</key>
+                <string>bold</string>
+            </dict>
+        </dict>
+    </array>
+</dict>
+</plist>"##,
+    )
+    .unwrap();
+
+    assert_eq!(theme.name, "Test");
+    assert_eq!(theme.background, Some("#000000".into()));
+    assert_eq!(
+        theme.resolve(HighlightType::Comment),
+        Style {
+            foreground: Some("#888888".into()),
+            italic: true,
+            ..Default::default()
+        }
+    );
+    assert_eq!(
+        theme.resolve(HighlightType::String).foreground,
+        Some("#888888".into())
+    );
+    assert_eq!(
+        theme.resolve(HighlightType::Function),
+        Style {
+            foreground: Some("#00ffff".into()),
+            bold: true,
+            ..Default::default()
+        }
+    );
+    assert_eq!(
+        theme.resolve(HighlightType::Type).foreground,
+        Some("#0000ff".into())
+    );
+    assert_eq!(
+        theme.resolve(HighlightType::Keyword).foreground,
+        Some("#ffffff".into())
+    );
+}
+
+#[test]
+fn default_theme_resolves_every_scope() {
+    let resolved_theme = Theme::default().resolve_all();
+
+    assert_eq!(resolved_theme.name, "One Dark");
+    assert!(resolved_theme
+        .styles
+        .values()
+        .all(|style| style.foreground.is_some()));
+    assert_eq!(
+        resolved_theme.styles[&HighlightType::Keyword].foreground,
+        Some("#c678dd".into())
+    );
+}
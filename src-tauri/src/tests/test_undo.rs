@@ -0,0 +1,192 @@
+use std::time::Duration;
+
+use crate::editor::text_buffer::{Cursor, LineTextBuffer, Selection};
+use crate::editor::undo_tree::UndoNodeId;
+
+fn cursor(row: usize, column: usize) -> Cursor {
+    Cursor { row, column }
+}
+
+fn content(buffer: &LineTextBuffer) -> String {
+    buffer.get_content("\n".into())
+}
+
+fn type_text(buffer: &mut LineTextBuffer, text: &str, mut at: Cursor) -> Cursor {
+    for c in text.chars() {
+        at = buffer.insert_text(c.to_string(), at);
+    }
+    at
+}
+
+#[test]
+fn typing_is_coalesced() {
+    let mut buffer = LineTextBuffer::new("".into());
+    type_text(&mut buffer, "hello", cursor(0, 0));
+
+    assert_eq!(buffer.history.len(), 1);
+    buffer.undo();
+    assert_eq!(content(&buffer), "");
+    buffer.redo();
+    assert_eq!(content(&buffer), "hello");
+}
+
+#[test]
+fn typing_breaks_at_words_and_lines() {
+    let mut buffer = LineTextBuffer::new("".into());
+    let end = type_text(&mut buffer, "hello world", cursor(0, 0));
+    type_text(&mut buffer, "\nnext", end);
+
+    buffer.undo();
+    assert_eq!(content(&buffer), "hello world\n");
+    buffer.undo();
+    assert_eq!(content(&buffer), "hello world");
+    buffer.undo();
+    assert_eq!(content(&buffer), "hello ");
+    buffer.undo();
+    assert_eq!(content(&buffer), "");
+}
+
+#[test]
+fn deleting_is_coalesced() {
+    let mut buffer = LineTextBuffer::new("abcdef".into());
+    // Backspace twice from the end
+    for column in (4..6).rev() {
+        buffer.remove_text(Selection {
+            start: cursor(0, column),
+            end: cursor(0, column + 1),
+        });
+    }
+    // Delete forwards twice from the start
+    for _ in 0..2 {
+        buffer.remove_text(Selection {
+            start: cursor(0, 0),
+            end: cursor(0, 1),
+        });
+    }
+    assert_eq!(content(&buffer), "cd");
+
+    buffer.undo();
+    assert_eq!(content(&buffer), "abcd");
+    buffer.undo();
+    assert_eq!(content(&buffer), "abcdef");
+}
+
+#[test]
+fn edits_in_group_are_undone_together() {
+    let mut buffer = LineTextBuffer::new("one".into());
+    buffer.begin_group();
+    buffer.remove_text(Selection {
+        start: cursor(0, 0),
+        end: cursor(0, 3),
+    });
+    buffer.begin_group();
+    buffer.insert_text("two\nthree".into(), cursor(0, 0));
+    buffer.end_group();
+    assert!(buffer.history.is_empty());
+    buffer.end_group();
+
+    assert_eq!(content(&buffer), "two\nthree");
+    buffer.undo();
+    assert_eq!(content(&buffer), "one");
+    buffer.redo();
+    assert_eq!(content(&buffer), "two\nthree");
+}
+
+#[test]
+fn undo_closes_open_group() {
+    let mut buffer = LineTextBuffer::new("".into());
+    buffer.begin_group();
+    buffer.insert_text("a\n".into(), cursor(0, 0));
+    buffer.insert_text("b\n".into(), cursor(1, 0));
+
+    buffer.undo();
+    assert_eq!(content(&buffer), "");
+}
+
+#[test]
+fn indentation_is_undoable() {
+    let mut buffer = LineTextBuffer::new("a\nb\n".into());
+    let selection = Selection {
+        start: cursor(0, 0),
+        end: cursor(1, 1),
+    };
+    let indented = buffer.add_indentation(selection, 4);
+    assert_eq!(content(&buffer), "    a\n    b\n");
+
+    buffer.remove_indentation(indented, 4);
+    assert_eq!(content(&buffer), "a\nb\n");
+
+    buffer.undo();
+    assert_eq!(content(&buffer), "    a\n    b\n");
+    buffer.undo();
+    assert_eq!(content(&buffer), "a\nb\n");
+    buffer.redo();
+    assert_eq!(content(&buffer), "    a\n    b\n");
+}
+
+/// Buffer with two branches: "ab" was replaced with "ac" after undoing
+fn branched_buffer() -> LineTextBuffer {
+    let mut buffer = LineTextBuffer::new("a".into());
+    buffer.insert_text("b\n".into(), cursor(0, 1));
+    buffer.undo();
+    buffer.insert_text("c\n".into(), cursor(0, 1));
+    buffer
+}
+
+#[test]
+fn edits_after_undo_keep_redo_branch() {
+    let mut buffer = branched_buffer();
+    assert_eq!(buffer.history.len(), 2);
+    assert_eq!(buffer.history.current(), UndoNodeId(2));
+
+    buffer.goto_history(UndoNodeId(1));
+    assert_eq!(content(&buffer), "ab\n");
+    buffer.undo();
+    assert_eq!(content(&buffer), "a");
+    // Redo follows the branch last visited
+    buffer.redo();
+    assert_eq!(content(&buffer), "ab\n");
+}
+
+#[test]
+fn switch_between_branches() {
+    let mut buffer = branched_buffer();
+
+    buffer.switch_branch(true);
+    assert_eq!(content(&buffer), "ab\n");
+    buffer.switch_branch(true);
+    assert_eq!(content(&buffer), "ac\n");
+    buffer.switch_branch(false);
+    assert_eq!(content(&buffer), "ab\n");
+
+    buffer.undo();
+    assert_eq!(buffer.switch_branch(true), None);
+}
+
+#[test]
+fn travel_in_time() {
+    let mut buffer = branched_buffer();
+
+    buffer.earlier(Duration::from_secs(3600));
+    assert_eq!(content(&buffer), "a");
+    assert_eq!(buffer.history.current(), UndoNodeId(0));
+
+    buffer.later(Duration::from_secs(3600));
+    assert_eq!(content(&buffer), "ac\n");
+}
+
+#[test]
+fn history_lists_every_node() {
+    let buffer = branched_buffer();
+    let history = buffer.history.history();
+
+    assert_eq!(history.len(), 3);
+    assert_eq!(history[0].parent, None);
+    assert_eq!(history[1].parent, Some(UndoNodeId(0)));
+    assert_eq!(history[2].parent, Some(UndoNodeId(0)));
+    assert!(history[2].is_current);
+    assert_eq!(history[1].description, "Insert \"b\\n\"");
+    assert!(history
+        .windows(2)
+        .all(|nodes| nodes[0].timestamp <= nodes[1].timestamp));
+}
@@ -0,0 +1,108 @@
+use std::fs;
+
+use crate::editor::text_buffer::{Cursor, LineTextBuffer};
+use crate::editor::undo_tree::UndoTree;
+use crate::editor_io::undo_history::{self, fnv1a_hash};
+use crate::tests::create_temp_dir;
+
+/// Buffer with two branches of edits, saved with the content of the last
+/// one
+fn edited_buffer(path: &str) -> LineTextBuffer {
+    let mut buffer = LineTextBuffer::from_file("one\n".into(), path.into());
+    buffer.insert_text("two\n".into(), Cursor { row: 1, column: 0 });
+    buffer.undo();
+    buffer.insert_text("three\n".into(), Cursor { row: 1, column: 0 });
+    buffer.mark_saved();
+    buffer
+}
+
+#[test]
+fn hash_is_stable() {
+    assert_eq!(fnv1a_hash("".bytes()), 0xcbf29ce484222325);
+    assert_eq!(fnv1a_hash("a".bytes()), 0xaf63dc4c8601ec8c);
+}
+
+#[test]
+fn trees_with_mismatched_links_are_invalid() {
+    let buffer = edited_buffer("file.txt");
+    assert!(buffer.history.is_valid());
+    let tree = serde_json::to_value(&buffer.history).unwrap();
+
+    // The second branch claims the first one as its parent
+    let mut mismatched_parent = tree.clone();
+    mismatched_parent["nodes"][2]["parent"] = 1.into();
+    let history: UndoTree = serde_json::from_value(mismatched_parent).unwrap();
+    assert!(!history.is_valid());
+
+    // The first branch lists the second one as its child
+    let mut mismatched_child = tree;
+    mismatched_child["nodes"][1]["children"] = serde_json::json!([2]);
+    let history: UndoTree = serde_json::from_value(mismatched_child).unwrap();
+    assert!(!history.is_valid());
+}
+
+#[test]
+fn restores_history_of_unchanged_file() {
+    let data_dir = create_temp_dir("papyrus_test_undo_history_restore", &[]);
+    let path = data_dir.join("file.txt").to_string_lossy().to_string();
+    let buffer = edited_buffer(&path);
+    undo_history::store(&data_dir, &buffer).unwrap();
+
+    let mut reopened = LineTextBuffer::from_file("one\nthree\n".into(), path);
+    assert!(undo_history::restore(&data_dir, &mut reopened));
+    assert_eq!(reopened.history.len(), 2);
+
+    reopened.undo();
+    assert_eq!(reopened.get_content("\n".into()), "one\n");
+    reopened.redo();
+    assert_eq!(reopened.get_content("\n".into()), "one\nthree\n");
+    reopened.switch_branch(true);
+    assert_eq!(reopened.get_content("\n".into()), "one\ntwo\n");
+}
+
+#[test]
+fn restores_history_of_buffer_closed_with_unsaved_changes() {
+    let data_dir = create_temp_dir("papyrus_test_undo_history_dirty", &[]);
+    let path = data_dir.join("file.txt").to_string_lossy().to_string();
+    let mut buffer = edited_buffer(&path);
+    buffer.insert_text("four\n".into(), Cursor { row: 2, column: 0 });
+    assert!(buffer.is_dirty());
+    undo_history::store(&data_dir, &buffer).unwrap();
+
+    // The unsaved change is lost but can be redone
+    let mut reopened = LineTextBuffer::from_file("one\nthree\n".into(), path);
+    assert!(undo_history::restore(&data_dir, &mut reopened));
+    assert!(!reopened.is_dirty());
+    reopened.redo();
+    assert_eq!(reopened.get_content("\n".into()), "one\nthree\nfour\n");
+    reopened.undo();
+    reopened.undo();
+    assert_eq!(reopened.get_content("\n".into()), "one\n");
+}
+
+#[test]
+fn drops_history_of_changed_file() {
+    let data_dir = create_temp_dir("papyrus_test_undo_history_changed", &[]);
+    let path = data_dir.join("file.txt").to_string_lossy().to_string();
+    undo_history::store(&data_dir, &edited_buffer(&path)).unwrap();
+    let history_file = undo_history::history_file(&data_dir, &path);
+    assert!(history_file.exists());
+
+    let mut reopened = LineTextBuffer::from_file("changed\n".into(), path);
+    assert!(!undo_history::restore(&data_dir, &mut reopened));
+    assert!(reopened.history.is_empty());
+    assert!(!history_file.exists());
+}
+
+#[test]
+fn drops_unreadable_history() {
+    let data_dir = create_temp_dir("papyrus_test_undo_history_corrupt", &[]);
+    let path = data_dir.join("file.txt").to_string_lossy().to_string();
+    undo_history::store(&data_dir, &edited_buffer(&path)).unwrap();
+    let history_file = undo_history::history_file(&data_dir, &path);
+    fs::write(&history_file, "{").unwrap();
+
+    let mut reopened = LineTextBuffer::from_file("one\nthree\n".into(), path);
+    assert!(!undo_history::restore(&data_dir, &mut reopened));
+    assert!(!history_file.exists());
+}
@@ -0,0 +1,183 @@
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use notify::event::{CreateKind, DataChange, ModifyKind, RemoveKind, RenameMode};
+use notify::{Event, EventKind};
+
+use crate::editor::text_buffer::{Cursor, LineTextBuffer};
+use crate::editor_io::watcher::{Debouncer, FileEvent, DEBOUNCE_DELAY, MAX_DEBOUNCE_DELAY};
+
+fn event(kind: EventKind, paths: &[&str]) -> Event {
+    paths.iter().fold(Event::new(kind), |event, path| {
+        event.add_path(PathBuf::from(path))
+    })
+}
+
+fn created(path: &str) -> Event {
+    event(EventKind::Create(CreateKind::File), &[path])
+}
+
+fn modified(path: &str) -> Event {
+    event(
+        EventKind::Modify(ModifyKind::Data(DataChange::Content)),
+        &[path],
+    )
+}
+
+fn removed(path: &str) -> Event {
+    event(EventKind::Remove(RemoveKind::File), &[path])
+}
+
+fn renamed(mode: RenameMode, paths: &[&str]) -> Event {
+    event(EventKind::Modify(ModifyKind::Name(mode)), paths)
+}
+
+/// Adds the events at the same time and returns the events once ready
+fn debounce(events: Vec<Event>) -> Vec<FileEvent> {
+    let mut debouncer = Debouncer::default();
+    let now = Instant::now();
+    for event in events {
+        debouncer.add(event, now);
+    }
+    debouncer.take_ready(now + DEBOUNCE_DELAY)
+}
+
+#[test]
+fn events_are_emitted_once_quiet() {
+    let mut debouncer = Debouncer::default();
+    let start = Instant::now();
+    assert_eq!(debouncer.timeout(start), None);
+
+    debouncer.add(modified("/ws/a.txt"), start);
+    assert_eq!(debouncer.timeout(start), Some(DEBOUNCE_DELAY));
+    assert!(debouncer.take_ready(start).is_empty());
+
+    let later = start + DEBOUNCE_DELAY / 2;
+    debouncer.add(modified("/ws/b.txt"), later);
+    assert!(debouncer.take_ready(start + DEBOUNCE_DELAY).is_empty());
+    assert_eq!(
+        debouncer.take_ready(later + DEBOUNCE_DELAY),
+        vec![
+            FileEvent::Modified {
+                path: "/ws/a.txt".into()
+            },
+            FileEvent::Modified {
+                path: "/ws/b.txt".into()
+            },
+        ]
+    );
+    assert_eq!(debouncer.timeout(later), None);
+}
+
+#[test]
+fn continuous_events_are_emitted_after_max_delay() {
+    let mut debouncer = Debouncer::default();
+    let start = Instant::now();
+    let step = DEBOUNCE_DELAY / 2;
+    let mut now = start;
+    while now < start + MAX_DEBOUNCE_DELAY {
+        debouncer.add(modified("/ws/log.txt"), now);
+        assert!(debouncer.take_ready(now).is_empty());
+        now += step;
+    }
+    assert_eq!(debouncer.timeout(now), Some(Duration::from_secs(0)));
+    assert_eq!(debouncer.take_ready(now).len(), 1);
+}
+
+#[test]
+fn events_of_the_same_file_are_merged() {
+    assert_eq!(
+        debounce(vec![created("/ws/a.txt"), modified("/ws/a.txt")]),
+        vec![FileEvent::Created {
+            path: "/ws/a.txt".into()
+        }]
+    );
+    assert_eq!(
+        debounce(vec![modified("/ws/a.txt"), removed("/ws/a.txt")]),
+        vec![FileEvent::Removed {
+            path: "/ws/a.txt".into()
+        }]
+    );
+    assert_eq!(
+        debounce(vec![removed("/ws/a.txt"), created("/ws/a.txt")]),
+        vec![FileEvent::Modified {
+            path: "/ws/a.txt".into()
+        }]
+    );
+    assert!(debounce(vec![created("/ws/a.txt"), removed("/ws/a.txt")]).is_empty());
+}
+
+#[test]
+fn renames_are_paired() {
+    let expected = vec![FileEvent::Renamed {
+        from: "/ws/a.txt".into(),
+        to: "/ws/b.txt".into(),
+    }];
+    assert_eq!(
+        debounce(vec![
+            renamed(RenameMode::From, &["/ws/a.txt"]),
+            renamed(RenameMode::To, &["/ws/b.txt"]),
+            renamed(RenameMode::Both, &["/ws/a.txt", "/ws/b.txt"]),
+        ]),
+        expected
+    );
+    assert_eq!(
+        debounce(vec![renamed(RenameMode::Both, &["/ws/a.txt", "/ws/b.txt"])]),
+        expected
+    );
+    assert_eq!(
+        debounce(vec![
+            created("/ws/a.txt"),
+            renamed(RenameMode::Both, &["/ws/a.txt", "/ws/b.txt"]),
+        ]),
+        vec![FileEvent::Created {
+            path: "/ws/b.txt".into()
+        }]
+    );
+}
+
+#[test]
+fn atomic_writes_are_modifications() {
+    assert_eq!(
+        debounce(vec![
+            created("/ws/.a.txt.papyrus-tmp"),
+            modified("/ws/.a.txt.papyrus-tmp"),
+            renamed(RenameMode::Both, &["/ws/.a.txt.papyrus-tmp", "/ws/a.txt"]),
+        ]),
+        vec![FileEvent::Modified {
+            path: "/ws/a.txt".into()
+        }]
+    );
+}
+
+#[test]
+fn git_folder_is_ignored() {
+    assert!(debounce(vec![
+        modified("/ws/.git/index"),
+        created("/ws/.git/objects/ab/cdef"),
+    ])
+    .is_empty());
+}
+
+#[test]
+fn reload_is_undoable_and_clean() {
+    let mut buffer = LineTextBuffer::from_file("abc\n".into(), "a.txt".into());
+    buffer.cursor = Cursor { row: 1, column: 0 };
+    buffer.reload("x\r\ny\r\n".into());
+
+    assert_eq!(buffer.get_content("\n".into()), "x\ny\n");
+    assert_eq!(buffer.get_content("\r\n".into()), "x\r\ny\r\n");
+    assert!(!buffer.is_dirty());
+
+    buffer.undo();
+    assert_eq!(buffer.get_content("\n".into()), "abc\n");
+    assert!(buffer.is_dirty());
+}
+
+#[test]
+fn reload_with_same_content_keeps_history() {
+    let mut buffer = LineTextBuffer::from_file("abc".into(), "a.txt".into());
+    buffer.reload("abc".into());
+    assert!(buffer.history.is_empty());
+    assert!(!buffer.is_dirty());
+}
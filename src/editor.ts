@@ -4,9 +4,11 @@ import { useWorkspaceStore } from "./stores/workspace";
 import { invoke } from "@tauri-apps/api";
 import { readText, writeText } from "@tauri-apps/api/clipboard";
 import FileIO from "./io";
+import Modals from "./modal";
 
 export default class Editor {
   fileIO: FileIO;
+  modals: Modals;
 
   constructor(
     public editorStore: ReturnType<typeof useEditorStore>,
@@ -14,9 +16,47 @@ export default class Editor {
     public workspaceStore: ReturnType<typeof useWorkspaceStore>,
   ) {
     this.fileIO = new FileIO(editorStore, settingsStore, workspaceStore);
+    this.modals = new Modals(editorStore, settingsStore, workspaceStore);
+  }
+
+  // Shortcuts shared by every editing mode
+  async searchMapping(e: KeyboardEvent) {
+    if (e.ctrlKey && e.key === "f") {
+      this.modals.promptUser(
+        "Find",
+        "Enter text to search for",
+        (query: string) => this.find(query),
+        null,
+        this.editorStore.searchQuery,
+      );
+      return true;
+    } else if (e.ctrlKey && e.key === "h") {
+      if (this.editorStore.searchQuery !== "") {
+        this.modals.promptUser(
+          "Replace All",
+          "Replace every match of " + this.editorStore.searchQuery + " with",
+          (replacement: string) => this.replace_all(replacement),
+          null,
+          "",
+        );
+      }
+      return true;
+    } else if (e.key === "F3") {
+      await this.find_next(e.shiftKey);
+      return true;
+    } else if (e.ctrlKey && e.key === "p") {
+      if (this.workspaceStore.workspaceFolder !== null) {
+        this.editorStore.quickOpenOpen = true;
+      }
+      return true;
+    }
+    return false;
   }
 
   async normalModeMapping(e: KeyboardEvent) {
+    if (await this.searchMapping(e)) {
+      return;
+    }
     if (e.ctrlKey) {
       if (e.key === "s") {
         await this.fileIO.saveCurrent();
@@ -42,14 +82,20 @@ export default class Editor {
         await this.move_cursor_document_start();
       } else if (e.key === "G") {
         await this.move_cursor_document_end();
+      } else if (e.key === "n") {
+        await this.find_next();
+      } else if (e.key === "N") {
+        await this.find_next(true);
       }
 
       // Editing
       else if (e.key === "o") {
         await this.move_cursor_line_end();
         const indentSize = await this.get_indent_size();
-        await this.insert_character("\n");
-        await this.add_indentation(indentSize);
+        await this.undo_group(async () => {
+          await this.insert_character("\n");
+          await this.add_indentation(indentSize);
+        });
         this.editorStore.editingMode = EditingMode.INSERT;
       } else if (e.key === "a") {
         await this.move_cursor_right();
@@ -59,6 +105,9 @@ export default class Editor {
   }
 
   async insertModeMapping(e: KeyboardEvent) {
+    if (await this.searchMapping(e)) {
+      return;
+    }
     if (e.ctrlKey) {
       if (e.key === "c") {
         const selected_text = await this.get_selected_text();
@@ -107,10 +156,12 @@ export default class Editor {
           key = "\n";
           indentSize = await this.get_indent_size();
         }
-        await this.insert_character(key);
-        if (e.key === "Enter") {
-          await this.add_indentation(indentSize);
-        }
+        await this.undo_group(async () => {
+          await this.insert_character(key);
+          if (e.key === "Enter") {
+            await this.add_indentation(indentSize);
+          }
+        });
       } else if (e.key === "Tab") {
         const spacing = " ".repeat(this.settingsStore.tabSize);
         await this.insert_character(spacing);
@@ -124,18 +175,45 @@ export default class Editor {
 
   async visualModeMapping(e: KeyboardEvent) {}
 
+  // Close the buffer, asking first if it has unsaved changes
   async closeBuffer(index: number) {
-    await invoke("delete_buffer", {
-      bufferIdx: this.editorStore.bufferIdx,
-    });
+    const bufferId = this.workspaceStore.openEditors[index].bufferId;
+    if (
+      bufferId !== undefined &&
+      (await invoke<boolean>("is_dirty", { bufferId: bufferId }))
+    ) {
+      this.modals.promptUser(
+        "Unsaved Changes",
+        "Discard unsaved changes? (yes/no)",
+        (response: string) => {
+          if (response === "yes") {
+            this.discardBuffer(index);
+          }
+        },
+        null,
+        "no",
+      );
+    } else {
+      await this.discardBuffer(index);
+    }
+  }
+
+  async discardBuffer(index: number) {
+    const bufferId = this.workspaceStore.openEditors[index].bufferId;
+    if (bufferId !== undefined) {
+      await invoke("delete_buffer", {
+        bufferId: bufferId,
+      });
+    }
     this.workspaceStore.openEditors.splice(index, 1);
     if (this.workspaceStore.openEditors.length == 0) {
       this.workspaceStore.currentEditorIndex = -1;
-      this.editorStore.bufferIdx = -1;
+      this.editorStore.bufferId = -1;
       this.editorStore.fileEntry = null;
       this.editorStore.encoding = "Unknown";
+      this.editorStore.lineEndings = null;
       this.editorStore.language = "Unknown";
-      this.editorStore.highlightedContent = [];
+      this.editorStore.resetHighlightedContent(0);
     } else {
       this.workspaceStore.switchEditor(0);
     }
@@ -149,7 +227,7 @@ export default class Editor {
   // Get lines length (total rows)
   async get_lines_length() {
     const lines_length = await invoke<number>("get_lines_length", {
-      bufferIdx: this.editorStore.bufferIdx,
+      bufferId: this.editorStore.bufferId,
     });
     return lines_length;
   }
@@ -157,7 +235,7 @@ export default class Editor {
   // Get row length
   async get_row_length(row_number: number) {
     const row_length = await invoke<number>("get_row_length", {
-      bufferIdx: this.editorStore.bufferIdx,
+      bufferId: this.editorStore.bufferId,
       row: row_number,
     });
     return row_length;
@@ -185,7 +263,7 @@ export default class Editor {
       };
 
       const selected_text = await invoke<string>("get_selected_text", {
-        bufferIdx: this.editorStore.bufferIdx,
+        bufferId: this.editorStore.bufferId,
         selection: s,
       });
       return selected_text;
@@ -197,7 +275,7 @@ export default class Editor {
   async get_token_under_cursor() {
     const s = this.workspaceStore.currentSelection;
     const selection = await invoke<ISelection>("select_token_under_cursor", {
-      bufferIdx: this.editorStore.bufferIdx,
+      bufferId: this.editorStore.bufferId,
       cursor: {
         row: s.end.row,
         column: s.end.column,
@@ -213,28 +291,27 @@ export default class Editor {
 
   // Insert character after cursor
   async insert_character(character: string) {
-    if (this.selection_made()) {
-      await this.remove_character();
-    }
-    const s = this.workspaceStore.currentSelection;
-    const update = await invoke("insert_text", {
-      bufferIdx: this.editorStore.bufferIdx,
-      text: character,
-      cursor: {
-        row: s.end.row,
-        column: s.end.column,
-      },
+    const update = await this.undo_group(async () => {
+      if (this.selection_made()) {
+        await this.remove_character();
+      }
+      const s = this.workspaceStore.currentSelection;
+      return await invoke("insert_text", {
+        bufferId: this.editorStore.bufferId,
+        text: character,
+        cursor: {
+          row: s.end.row,
+          column: s.end.column,
+        },
+      });
     });
-    this.editorStore.highlightedContent = update[0].text;
+    this.editorStore.applyLineChanges(update[0]);
     this.workspaceStore.updateSelection(
       update[1].row,
       update[1].column,
       update[1].row,
       update[1].column,
     );
-    this.workspaceStore.openEditors[
-      this.workspaceStore.currentEditorIndex
-    ].unsavedChanges = true;
   }
 
   // Remove character before cursor
@@ -323,10 +400,10 @@ export default class Editor {
         }
       }
       const update = await invoke("remove_text", {
-        bufferIdx: this.editorStore.bufferIdx,
+        bufferId: this.editorStore.bufferId,
         selection: s,
       });
-      this.editorStore.highlightedContent = update[0].text;
+      this.editorStore.applyLineChanges(update[0]);
       const removed_text = update[1];
       this.workspaceStore.updateSelection(
         update[2].row,
@@ -334,9 +411,6 @@ export default class Editor {
         update[2].row,
         update[2].column,
       );
-      this.workspaceStore.openEditors[
-        this.workspaceStore.currentEditorIndex
-      ].unsavedChanges = true;
       return removed_text;
     }
   }
@@ -470,12 +544,27 @@ export default class Editor {
   }
 
   // Undo last action
+  // Run edits so they are undone and redone as a single change
+  async undo_group<T>(edit: () => Promise<T>): Promise<T> {
+    const bufferId = this.editorStore.bufferId;
+    await invoke("begin_undo_group", {
+      bufferId: bufferId,
+    });
+    try {
+      return await edit();
+    } finally {
+      await invoke("end_undo_group", {
+        bufferId: bufferId,
+      });
+    }
+  }
+
   async undo() {
     const update = await invoke("undo", {
-      bufferIdx: this.editorStore.bufferIdx,
+      bufferId: this.editorStore.bufferId,
     });
     if (update != null) {
-      this.editorStore.highlightedContent = update[0].text;
+      this.editorStore.applyLineChanges(update[0]);
       this.workspaceStore.updateSelection(
         update[1].row,
         update[1].column,
@@ -483,18 +572,15 @@ export default class Editor {
         update[1].column,
       );
     }
-    this.workspaceStore.openEditors[
-      this.workspaceStore.currentEditorIndex
-    ].unsavedChanges = true;
   }
 
   // Redo last action
   async redo() {
     const update = await invoke("redo", {
-      bufferIdx: this.editorStore.bufferIdx,
+      bufferId: this.editorStore.bufferId,
     });
     if (update != null) {
-      this.editorStore.highlightedContent = update[0].text;
+      this.editorStore.applyLineChanges(update[0]);
       this.workspaceStore.updateSelection(
         update[1].row,
         update[1].column,
@@ -502,55 +588,236 @@ export default class Editor {
         update[1].column,
       );
     }
-    this.workspaceStore.openEditors[
-      this.workspaceStore.currentEditorIndex
-    ].unsavedChanges = true;
+  }
+
+  // Move through the undo tree with the given command
+  async travel_history(command: string, args: object) {
+    const update = await invoke(command, {
+      bufferId: this.editorStore.bufferId,
+      ...args,
+    });
+    if (update != null) {
+      this.editorStore.applyLineChanges(update[0]);
+      this.workspaceStore.updateSelection(
+        update[1].row,
+        update[1].column,
+        update[1].row,
+        update[1].column,
+      );
+    }
+  }
+
+  async switch_undo_branch(forward = true) {
+    await this.travel_history("switch_undo_branch", { forward: forward });
+  }
+
+  // Go back to the state of the buffer a number of minutes ago
+  async undo_earlier() {
+    this.modals.promptUser(
+      "Undo Earlier",
+      "Minutes to go back",
+      (minutes: string) => {
+        const seconds = Math.round(parseFloat(minutes) * 60);
+        if (seconds > 0) {
+          this.travel_history("undo_earlier", { seconds: seconds });
+        }
+      },
+      null,
+      "5",
+    );
+  }
+
+  // List the undo tree and go to the chosen state
+  async undo_history() {
+    const history = await invoke<Array<IHistoryNode>>("get_undo_history", {
+      bufferId: this.editorStore.bufferId,
+    });
+    const details = history
+      .map((node) => {
+        const time = new Date(node.timestamp).toLocaleTimeString();
+        const parent = node.parent === null ? "" : " <- " + node.parent;
+        const current = node.is_current ? " *" : "";
+        return (
+          node.id + parent + "  " + time + "  " + node.description + current
+        );
+      })
+      .join("\n");
+    const current = history.find((node) => node.is_current);
+    this.modals.promptUser(
+      "Undo History",
+      "State to go to",
+      (id: string) => {
+        const node = parseInt(id);
+        if (!isNaN(node)) {
+          this.travel_history("goto_history", { node: node });
+        }
+      },
+      null,
+      current ? current.id.toString() : "0",
+      details,
+    );
+  }
+
+  // Search for the query and select the next match
+  async find(query: string) {
+    this.editorStore.searchQuery = query;
+    await this.find_next();
+  }
+
+  // Select the next or previous match of the current search
+  async find_next(backwards = false) {
+    if (this.editorStore.searchQuery === "") {
+      return;
+    }
+    const s = this.workspaceStore.currentSelection;
+    invoke<ISelection | null>(backwards ? "find_prev" : "find_next", {
+      bufferId: this.editorStore.bufferId,
+      query: this.editorStore.searchQuery,
+      options: this.editorStore.searchOptions,
+      cursor: backwards ? s.start : s.end,
+    })
+      .then((selection) => {
+        if (selection != null) {
+          this.workspaceStore.updateSelection(
+            selection.start.row,
+            selection.start.column,
+            selection.end.row,
+            selection.end.column,
+          );
+        }
+      })
+      .catch((error: IPapyrusError) => {
+        console.error(error.message);
+      });
+  }
+
+  // Replace every match of the current search
+  async replace_all(replacement: string) {
+    invoke("replace_all", {
+      bufferId: this.editorStore.bufferId,
+      query: this.editorStore.searchQuery,
+      options: this.editorStore.searchOptions,
+      replacement: replacement,
+    })
+      .then((update) => {
+        this.editorStore.applyLineChanges(update[0]);
+      })
+      .catch((error: IPapyrusError) => {
+        console.error(error.message);
+      });
+  }
+
+  // Replace the current search in every workspace file after showing a
+  // preview of the changes
+  async replace_in_files() {
+    const root = this.workspaceStore.workspaceFolder;
+    if (root === null || this.editorStore.searchQuery === "") {
+      return;
+    }
+    this.modals.promptUser(
+      "Replace in Files",
+      "Replace every match of " + this.editorStore.searchQuery + " with",
+      async (replacement: string) => {
+        const previews = await invoke<Array<IFileReplacementPreview>>(
+          "preview_replace_in_files",
+          {
+            root: root,
+            options: {
+              query: this.editorStore.searchQuery,
+              ...this.editorStore.searchOptions,
+            },
+            replacement: replacement,
+          },
+        );
+        if (previews.length == 0) {
+          return;
+        }
+        const count = previews.reduce(
+          (total, preview) => total + preview.replacement_count,
+          0,
+        );
+        this.modals.promptUser(
+          "Replace in Files",
+          "Replace " + count + " matches in " + previews.length + " files?",
+          (response: string) => {
+            if (response === "yes") {
+              this.apply_replace_in_files(
+                replacement,
+                previews.map((preview) => preview.path),
+              );
+            }
+          },
+          null,
+          "yes",
+          previews.map((preview) => preview.diff).join("\n"),
+        );
+      },
+      null,
+      "",
+    );
+  }
+
+  async apply_replace_in_files(replacement: string, paths: Array<string>) {
+    const summary = await invoke<IReplaceInFilesSummary>("replace_in_files", {
+      query: this.editorStore.searchQuery,
+      options: this.editorStore.searchOptions,
+      replacement: replacement,
+      paths: paths,
+    });
+    for (const error of summary.errors) {
+      console.error(error.path + ": " + error.message);
+    }
+
+    // Open files are changed through their buffer
+    const currentEditor =
+      this.workspaceStore.openEditors[this.workspaceStore.currentEditorIndex];
+    if (
+      currentEditor?.entry &&
+      summary.changed_files.includes(currentEditor.entry.path)
+    ) {
+      const linesLength = await this.get_lines_length();
+      this.editorStore.resetHighlightedContent(linesLength);
+    }
   }
 
   // Add indentation
   async add_indentation(tabSize?: number) {
     const s = this.workspaceStore.currentSelection;
     const update = await invoke("add_indentation", {
-      bufferIdx: this.editorStore.bufferIdx,
+      bufferId: this.editorStore.bufferId,
       selection: s,
       tabSize: tabSize ?? this.settingsStore.tabSize,
     });
-    this.editorStore.highlightedContent = update[0].text;
+    this.editorStore.applyLineChanges(update[0]);
     this.workspaceStore.updateSelection(
       update[1].start.row,
       update[1].start.column,
       update[1].end.row,
       update[1].end.column,
     );
-    this.workspaceStore.openEditors[
-      this.workspaceStore.currentEditorIndex
-    ].unsavedChanges = true;
   }
 
   // Remove indentation
   async remove_indentation() {
     const s = this.workspaceStore.currentSelection;
     const update = await invoke("remove_indentation", {
-      bufferIdx: this.editorStore.bufferIdx,
+      bufferId: this.editorStore.bufferId,
       selection: s,
       tabSize: this.settingsStore.tabSize,
     });
-    this.editorStore.highlightedContent = update[0].text;
+    this.editorStore.applyLineChanges(update[0]);
     this.workspaceStore.updateSelection(
       update[1].start.row,
       update[1].start.column,
       update[1].end.row,
       update[1].end.column,
     );
-    this.workspaceStore.openEditors[
-      this.workspaceStore.currentEditorIndex
-    ].unsavedChanges = true;
   }
 
   // Get indent size
   async get_indent_size() {
     const indent_size = await invoke<number>("get_indent_size", {
-      bufferIdx: this.editorStore.bufferIdx,
+      bufferId: this.editorStore.bufferId,
       row: this.workspaceStore.currentSelection.start.row,
     });
     return indent_size;
@@ -6,10 +6,127 @@ declare interface IFileEntry {
   entries: Array<IFileEntry> | null;
 }
 
+declare type IFileEvent =
+  | { kind: "Created"; path: string }
+  | { kind: "Modified"; path: string }
+  | { kind: "Removed"; path: string }
+  | { kind: "Renamed"; from: string; to: string };
+
+declare interface IQuickOpenMatch {
+  entry: IFileEntry;
+  relative_path: string;
+  // Indices of the matched characters of the relative path, in code points
+  positions: Array<number>;
+  score: number;
+}
+
+declare interface IIndexOptions {
+  exclude: Array<string>;
+  exclude_hidden: boolean;
+}
+
 declare interface IHighlightedText {
   text: Array<Array<string>>;
 }
 
+declare interface IStyle {
+  foreground: string | null;
+  background: string | null;
+  bold: boolean;
+  italic: boolean;
+  underline: boolean;
+}
+
+declare interface ITheme {
+  name: string;
+  foreground: string | null;
+  background: string | null;
+  styles: Record<string, IStyle>;
+}
+
+declare interface ISearchOptions {
+  is_regex: boolean;
+  case_sensitive: boolean;
+  whole_word: boolean;
+}
+
+declare interface IFileReplacementPreview {
+  path: string;
+  replacement_count: number;
+  diff: string;
+  is_open: boolean;
+}
+
+declare interface IReplaceInFilesSummary {
+  replacement_count: number;
+  changed_files: Array<string>;
+  errors: Array<{ path: string; message: string }>;
+}
+
+declare interface IHistoryNode {
+  id: number;
+  parent: number | null;
+  timestamp: number;
+  is_current: boolean;
+  description: string;
+}
+
+declare interface IRecoverableBuffer {
+  id: string;
+  file_path: string | null;
+  timestamp: number;
+}
+
+declare interface IRestoredBuffer {
+  buffer_id: number;
+  file_path: string | null;
+  cursor: {
+    row: number;
+    column: number;
+  };
+}
+
+declare interface ILineEndings {
+  line_ending: "LF" | "CRLF" | "CR";
+  mixed: boolean;
+}
+
+declare interface IFileEncoding {
+  name: string;
+  bom: boolean;
+}
+
+declare interface ILineChanges {
+  start_row: number;
+  removed_rows: number;
+  inserted_rows: number;
+  invalidated_rows: Array<[number, number]>;
+  lines_length: number;
+}
+
+// Error returned by the Tauri commands
+declare interface IPapyrusError {
+  kind:
+    | "BufferNotFound"
+    | "Io"
+    | "Encoding"
+    | "NoFilePath"
+    | "FileChanged"
+    | "MalformedContent"
+    | "UnsavedChanges"
+    | "PathExists"
+    | "PathNotFound"
+    | "PtyNotInitialized"
+    | "Pty"
+    | "InvalidCursor"
+    | "InvalidSelection"
+    | "InvalidRow"
+    | "Theme"
+    | "Search"
+    | "Other";
+  message: string;
+}
+
 declare interface ITerminalPayload {
   output: string;
 }
@@ -27,6 +144,7 @@ declare interface ISelection {
 
 declare interface OpenEditor {
   entry?: IFileEntry;
+  bufferId?: number;
   unsavedChanges: boolean;
   content?: string;
   selection: {
@@ -3,13 +3,18 @@ import { invoke } from "@tauri-apps/api";
 import { useEditorStore } from "./stores/editor";
 import { useSettingsStore } from "./stores/settings";
 import { useWorkspaceStore } from "./stores/workspace";
+import Modals from "./modal";
 
 export default class FileIO {
+  modals: Modals;
+
   constructor(
     public editorStore: ReturnType<typeof useEditorStore>,
     public settingsStore: ReturnType<typeof useSettingsStore>,
     public workspaceStore: ReturnType<typeof useWorkspaceStore>,
-  ) {}
+  ) {
+    this.modals = new Modals(editorStore, settingsStore, workspaceStore);
+  }
 
   async openFileDialog() {
     const selected = await open({
@@ -25,9 +30,18 @@ export default class FileIO {
     invoke<number>("create_buffer_from_file_path", {
       path: path,
     })
-      .then(async (buffer_idx) => {
-        this.editorStore.bufferIdx = buffer_idx;
-        this.editorStore.encoding = "utf-8";
+      .then(async (buffer_id) => {
+        this.editorStore.bufferId = buffer_id;
+        const encoding = await invoke<IFileEncoding>("get_encoding", {
+          bufferId: buffer_id,
+        });
+        this.editorStore.encoding = encoding.name;
+        this.editorStore.lineEndings = await invoke<ILineEndings>(
+          "get_line_endings",
+          {
+            bufferId: buffer_id,
+          },
+        );
         const fileEntry = await invoke<IFileEntry>("get_file_info", {
           path: path,
         });
@@ -42,6 +56,7 @@ export default class FileIO {
         if (!entryExists) {
           this.workspaceStore.openEditors.push({
             entry: fileEntry,
+            bufferId: buffer_id,
             unsavedChanges: false,
             selection: {
               start: {
@@ -63,15 +78,17 @@ export default class FileIO {
           );
         }
 
-        invoke<IHighlightedText>("get_highlighted_text", {
-          bufferIdx: this.editorStore.bufferIdx,
-        }).then((content) => {
-          this.editorStore.highlightedContent = content.text;
+        invoke<number>("get_lines_length", {
+          bufferId: this.editorStore.bufferId,
+        }).then((linesLength) => {
+          this.editorStore.resetHighlightedContent(linesLength);
         });
       })
-      .catch((error) => {
-        this.editorStore.encoding = "Unknown";
-        console.error(error);
+      .catch((error: IPapyrusError) => {
+        if (error.kind === "Encoding") {
+          this.editorStore.encoding = "Unknown";
+        }
+        console.error(error.message);
       });
   }
 
@@ -82,12 +99,60 @@ export default class FileIO {
       .then((entries) => {
         this.workspaceStore.workspaceFolder = workspaceFolder;
         this.workspaceStore.folderEntries = entries;
+        // Changes made outside the editor are emitted as events
+        return invoke("watch_workspace", {
+          path: workspaceFolder,
+        });
+      })
+      .then(() => {
+        const options: IIndexOptions = {
+          exclude: this.settingsStore.indexExclude,
+          exclude_hidden: !this.settingsStore.showHiddenFiles,
+        };
+        return invoke("index_workspace", {
+          root: workspaceFolder,
+          options: options,
+        });
       })
       .catch((error) => {
         console.error(error);
       });
   }
 
+  // Reload the folders shown in the file tree, keeping expanded folders
+  // expanded
+  async refreshFileTree() {
+    const workspaceFolder = this.workspaceStore.workspaceFolder;
+    if (workspaceFolder === null) {
+      return;
+    }
+    this.loadFolder(workspaceFolder, this.workspaceStore.folderEntries)
+      .then((entries) => {
+        this.workspaceStore.folderEntries = entries;
+      })
+      .catch((error: IPapyrusError) => {
+        console.error(error.message);
+      });
+  }
+
+  async loadFolder(path: string, previousEntries: Array<IFileEntry>) {
+    const entries = await invoke<Array<IFileEntry>>("get_folder_content", {
+      path: path,
+    });
+    for (const entry of entries) {
+      const previous = previousEntries.find(
+        (previousEntry) => previousEntry.path == entry.path,
+      );
+      if (entry.is_dir && previous?.entries) {
+        entry.entries = await this.loadFolder(
+          entry.path,
+          previous.entries,
+        ).catch(() => null);
+      }
+    }
+    return entries;
+  }
+
   async openFolder() {
     const selected = await open({
       directory: true,
@@ -100,32 +165,63 @@ export default class FileIO {
     }
   }
 
-  async saveCurrent() {
+  async saveCurrent(force = false) {
     invoke<string>("save_buffer", {
-      bufferIdx: this.editorStore.bufferIdx,
-      eolSequence: this.settingsStore.eolSequence,
+      bufferId: this.editorStore.bufferId,
+      force: force,
     })
       .then(() => {
-        this.workspaceStore.openEditors[
-          this.workspaceStore.currentEditorIndex
-        ].unsavedChanges = false;
         console.log("File saved successfully");
       })
-      .catch((error) => {
-        console.error(error);
+      .catch(async (error: IPapyrusError) => {
+        if (error.kind === "NoFilePath") {
+          await this.saveAs();
+        } else if (
+          error.kind === "FileChanged" ||
+          error.kind === "MalformedContent"
+        ) {
+          this.confirmOverwrite(error, () => this.saveCurrent(true));
+        } else {
+          console.error(error.message);
+        }
       });
   }
 
+  // Ask before overwriting a file changed on disk by another program
+  confirmOverwrite(error: IPapyrusError, overwrite: () => void) {
+    this.modals.promptUser(
+      error.kind === "FileChanged" ? "File Changed" : "Malformed Content",
+      error.message + ". Overwrite it? (yes/no)",
+      (response: string) => {
+        if (response === "yes") {
+          overwrite();
+        }
+      },
+      null,
+      "no",
+    );
+  }
+
   async saveAs() {
     const selected = await save();
     if (selected !== null) {
       // user selected a single file
       invoke<string>("save_buffer_to_new_file", {
-        bufferIdx: this.editorStore.bufferIdx,
+        bufferId: this.editorStore.bufferId,
         path: selected,
-        eolSequence: this.settingsStore.eolSequence,
       })
-        .then(() => {
+        .then(async () => {
+          // Untitled buffers are linked to the file they are saved to
+          const openEditor =
+            this.workspaceStore.openEditors[
+              this.workspaceStore.currentEditorIndex
+            ];
+          if (openEditor.entry === undefined) {
+            openEditor.entry = await invoke<IFileEntry>("get_file_info", {
+              path: selected,
+            });
+            this.editorStore.fileEntry = openEditor.entry;
+          }
           console.log("File saved successfully");
         })
         .catch((error) => {
@@ -134,6 +230,151 @@ export default class FileIO {
     }
   }
 
+  // Decode the current file again with the given encoding, discarding
+  // unsaved changes
+  async reopenWithEncoding(encoding: string, force = false) {
+    invoke<IFileEncoding>("reopen_with_encoding", {
+      bufferId: this.editorStore.bufferId,
+      encoding: encoding,
+      force: force,
+    })
+      .then(async (fileEncoding) => {
+        this.editorStore.encoding = fileEncoding.name;
+        this.editorStore.lineEndings = await invoke<ILineEndings>(
+          "get_line_endings",
+          {
+            bufferId: this.editorStore.bufferId,
+          },
+        );
+        const linesLength = await invoke<number>("get_lines_length", {
+          bufferId: this.editorStore.bufferId,
+        });
+        this.editorStore.resetHighlightedContent(linesLength);
+      })
+      .catch((error: IPapyrusError) => {
+        if (error.kind === "UnsavedChanges") {
+          this.modals.promptUser(
+            "Unsaved Changes",
+            error.message + ". Discard them and reopen? (yes/no)",
+            (response: string) => {
+              if (response === "yes") {
+                this.reopenWithEncoding(encoding, true);
+              }
+            },
+            null,
+            "no",
+          );
+        } else {
+          console.error(error.message);
+        }
+      });
+  }
+
+  async saveWithEncoding(encoding: string, force = false) {
+    invoke<IFileEncoding>("save_with_encoding", {
+      bufferId: this.editorStore.bufferId,
+      encoding: encoding,
+      force: force,
+    })
+      .then((fileEncoding) => {
+        this.editorStore.encoding = fileEncoding.name;
+      })
+      .catch((error: IPapyrusError) => {
+        if (
+          error.kind === "FileChanged" ||
+          error.kind === "MalformedContent"
+        ) {
+          this.confirmOverwrite(error, () =>
+            this.saveWithEncoding(encoding, true),
+          );
+        } else {
+          console.error(error.message);
+        }
+      });
+  }
+
+  // Use the given line ending when the current file is next saved
+  async convertLineEndings(lineEnding: string) {
+    invoke<ILineEndings>("convert_line_endings", {
+      bufferId: this.editorStore.bufferId,
+      lineEnding: lineEnding,
+    })
+      .then((lineEndings) => {
+        this.editorStore.lineEndings = lineEndings;
+      })
+      .catch((error: IPapyrusError) => {
+        console.error(error.message);
+      });
+  }
+
+  // Offer to restore the unsaved buffers left by a crash or the last exit
+  async offerRecovery() {
+    const recoverable = await invoke<Array<IRecoverableBuffer>>(
+      "get_recoverable_buffers",
+    );
+    if (recoverable.length == 0) {
+      return;
+    }
+    const ids = recoverable.map((buffer) => buffer.id);
+    this.modals.promptUser(
+      "Restore Unsaved Files",
+      "Restore " + ids.length + " unsaved files? (yes/no)",
+      (response: string) => {
+        if (response === "yes") {
+          this.restoreBuffers(ids);
+        } else if (response === "no") {
+          invoke("discard_recoverable_buffers", {
+            ids: ids,
+          }).catch((error: IPapyrusError) => {
+            console.error(error.message);
+          });
+        }
+      },
+      null,
+      "yes",
+      recoverable
+        .map(
+          (buffer) =>
+            new Date(buffer.timestamp).toLocaleString() +
+            "  " +
+            (buffer.file_path ?? "Untitled"),
+        )
+        .join("\n"),
+    );
+  }
+
+  async restoreBuffers(ids: Array<string>) {
+    const restored = await invoke<Array<IRestoredBuffer>>("restore_buffers", {
+      ids: ids,
+    });
+    for (const buffer of restored) {
+      let entry: IFileEntry | undefined = undefined;
+      if (buffer.file_path !== null) {
+        entry = await invoke<IFileEntry>("get_file_info", {
+          path: buffer.file_path,
+        }).catch(() => undefined);
+      }
+      this.workspaceStore.openEditors.push({
+        entry: entry,
+        bufferId: buffer.buffer_id,
+        unsavedChanges: true,
+        selection: {
+          start: { ...buffer.cursor },
+          end: { ...buffer.cursor },
+        },
+        scroll: {
+          hOffset: 0,
+          vOffset: 0,
+        },
+      });
+    }
+    if (restored.length > 0) {
+      this.workspaceStore.switchEditor(
+        this.workspaceStore.openEditors.length - 1,
+      );
+    }
+  }
+
   async relative(from, to) {
     const path = await invoke<string>("get_relative_path", {
       from: from,
@@ -165,7 +406,7 @@ export default class FileIO {
     await invoke("create_file", {
       path: absolutePath,
     });
-    this.setFileTree(this.workspaceStore.workspaceFolder);
+    this.refreshFileTree();
   }
 
   async createNewFolder(path, context) {
@@ -176,6 +417,50 @@ export default class FileIO {
     await invoke("create_folder", {
       path: absolutePath,
     });
-    this.setFileTree(this.workspaceStore.workspaceFolder);
+    this.refreshFileTree();
+  }
+
+  // Rename or move a file or folder to a path relative to the workspace.
+  // Open buffers follow the file.
+  async renamePath(path: string, newPath: string) {
+    const absolutePath = await this.join(
+      this.workspaceStore.workspaceFolder,
+      newPath,
+    );
+    invoke("rename_path", {
+      from: path,
+      to: absolutePath,
+    })
+      .then(() => this.refreshFileTree())
+      .catch((error: IPapyrusError) => {
+        console.error(error.message);
+      });
+  }
+
+  async copyPath(path: string, newPath: string) {
+    const absolutePath = await this.join(
+      this.workspaceStore.workspaceFolder,
+      newPath,
+    );
+    invoke("copy_path", {
+      from: path,
+      to: absolutePath,
+    })
+      .then(() => this.refreshFileTree())
+      .catch((error: IPapyrusError) => {
+        console.error(error.message);
+      });
+  }
+
+  // Move a file or folder to the trash, or delete it permanently
+  async deletePath(path: string, permanently: boolean) {
+    invoke("delete_path", {
+      path: path,
+      permanently: permanently,
+    })
+      .then(() => this.refreshFileTree())
+      .catch((error: IPapyrusError) => {
+        console.error(error.message);
+      });
   }
 }
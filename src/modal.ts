@@ -9,9 +9,17 @@ export default class Modals {
     public workspaceStore: ReturnType<typeof useWorkspaceStore>,
   ) {}
 
-  promptUser(title, description, handler, context, defaultPrompt) {
+  promptUser(
+    title,
+    description,
+    handler,
+    context,
+    defaultPrompt,
+    details = "",
+  ) {
     this.editorStore.promptTitle = title;
     this.editorStore.promptDescription = description;
+    this.editorStore.promptDetails = details;
     this.editorStore.promptCallback = handler;
     this.editorStore.promptResponse = defaultPrompt;
     this.editorStore.promptContext = context;
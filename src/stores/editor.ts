@@ -9,32 +9,93 @@ export enum EditingMode {
 
 export const useEditorStore = defineStore("editor", () => {
   const fileEntry = ref<IFileEntry | null>(null);
-  const highlightedContent = ref<Array<Array<string>>>([]);
-  const bufferIdx = ref(-1);
+  // Rows set to null have not been highlighted yet or are outdated
+  const highlightedContent = ref<Array<Array<string> | null>>([]);
+  const bufferId = ref(-1);
   const language = ref("Unknown");
   const encoding = ref("utf-8");
+  const lineEndings = ref<ILineEndings | null>(null);
   const editingMode = ref(EditingMode.NORMAL);
+  const theme = ref<ITheme | null>(null);
+
+  // Search
+  const searchQuery = ref("");
+  const searchOptions = ref<ISearchOptions>({
+    is_regex: false,
+    case_sensitive: false,
+    whole_word: false,
+  });
 
   // Prompt
   const promptOpen = ref(false);
   const promptTitle = ref("Title");
   const promptDescription = ref("Description");
+  const promptDetails = ref("");
   const promptResponse = ref("");
   const promptCallback = ref(null);
   const promptContext = ref(null);
 
+  // Quick open
+  const quickOpenOpen = ref(false);
+
+  function resetHighlightedContent(linesLength: number) {
+    highlightedContent.value = new Array(linesLength).fill(null);
+  }
+
+  function applyLineChanges(changes: ILineChanges) {
+    const content = highlightedContent.value;
+    highlightedContent.value = content
+      .slice(0, changes.start_row)
+      .concat(
+        new Array(changes.inserted_rows).fill(null),
+        content.slice(changes.start_row + changes.removed_rows),
+      );
+    for (const [start, end] of changes.invalidated_rows) {
+      highlightedContent.value.fill(null, start, end);
+    }
+  }
+
+  // Inline CSS style of a token with the given highlight scope
+  function getTokenStyle(scope: string) {
+    const style = theme.value?.styles[scope];
+    if (!style) {
+      return {};
+    }
+    return {
+      color: style.foreground ?? undefined,
+      "background-color": style.background ?? undefined,
+      "font-weight": style.bold ? "bold" : undefined,
+      "font-style": style.italic ? "italic" : undefined,
+      "text-decoration": style.underline ? "underline" : undefined,
+    };
+  }
+
+  function patchHighlightedRows(startRow: number, rows: Array<Array<string>>) {
+    highlightedContent.value.splice(startRow, rows.length, ...rows);
+  }
+
   return {
     fileEntry,
     highlightedContent,
-    bufferIdx,
+    bufferId,
     language,
     encoding,
+    lineEndings,
     editingMode,
+    theme,
+    searchQuery,
+    searchOptions,
     promptOpen,
     promptTitle,
     promptDescription,
+    promptDetails,
     promptResponse,
     promptCallback,
     promptContext,
+    quickOpenOpen,
+    resetHighlightedContent,
+    applyLineChanges,
+    patchHighlightedRows,
+    getTokenStyle,
   };
 });
@@ -1,11 +1,6 @@
 import { defineStore } from "pinia";
 import { ref } from "vue";
 
-export enum EOLSequence {
-  CRLF = "\r\n",
-  LF = "\n",
-}
-
 export enum AutoSaveType {
   OFF,
   ON_FOCUS_CHANGE,
@@ -21,7 +16,8 @@ export const useSettingsStore = defineStore("settings", () => {
   const tabSize = ref(4);
   const autoSave = ref(AutoSaveType.OFF);
   const autoSaveDelay = ref(1000);
-  const eolSequence = ref(EOLSequence.LF);
+  const indexExclude = ref<Array<string>>([]);
+  const showHiddenFiles = ref(true);
 
   return {
     editorFontSize,
@@ -30,6 +26,7 @@ export const useSettingsStore = defineStore("settings", () => {
     tabSize,
     autoSave,
     autoSaveDelay,
-    eolSequence,
+    indexExclude,
+    showHiddenFiles,
   };
 });
@@ -1,4 +1,5 @@
 import { defineStore } from "pinia";
+import { invoke } from "@tauri-apps/api";
 import { computed, ref } from "vue";
 
 export const useWorkspaceStore = defineStore("workspace", () => {
@@ -30,10 +31,20 @@ export const useWorkspaceStore = defineStore("workspace", () => {
     end_row: number,
     end_column: number,
   ) {
-    openEditors.value[currentEditorIndex.value].selection = {
+    const openEditor = openEditors.value[currentEditorIndex.value];
+    openEditor.selection = {
       start: { row: start_row, column: start_column },
       end: { row: end_row, column: end_column },
     };
+    // The cursor is restored along with unsaved changes after a crash
+    if (openEditor.bufferId !== undefined) {
+      invoke("set_cursor", {
+        bufferId: openEditor.bufferId,
+        cursor: { row: end_row, column: end_column },
+      }).catch((error: IPapyrusError) => {
+        console.error(error.message);
+      });
+    }
   }
 
   return {